@@ -0,0 +1,274 @@
+//! Measurements taken from downloaded images.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Images with a mean luminance below this count as dark.
+pub const DARK_BELOW: f32 = 0.5;
+
+/// Perceptual hashes at most this many bits apart are taken to be the same
+/// picture, e.g. a resized or recompressed copy.
+pub const DUPLICATE_DISTANCE: u32 = 6;
+
+/// Images with less contrast than this have too little structure to hash:
+/// every flat colour hashes the same.
+pub const MIN_HASH_CONTRAST: f32 = 0.02;
+
+/// Copies differ in mean luminance by at most this much, which tells apart
+/// e.g. two gradients running the same way in different colours.
+pub const MAX_COPY_LUMINANCE_DIFFERENCE: f32 = 0.08;
+
+/// Images are scaled down to about this size before measuring, which is
+/// plenty for averages and much faster than decoding at full size.
+const SAMPLE_SIZE: u32 = 128;
+
+/// Whether an image (or a desktop theme) is dark or light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    Dark,
+    Light,
+}
+
+impl Tone {
+    #[must_use]
+    pub fn of(luminance: f32) -> Self {
+        if luminance < DARK_BELOW {
+            Self::Dark
+        } else {
+            Self::Light
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dark => "dark",
+            Self::Light => "light",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageStats {
+    /// Mean luma, from 0 (black) to 1 (white).
+    pub luminance: f32,
+    /// Standard deviation of luma (RMS contrast), from 0 to 0.5.
+    pub contrast: f32,
+    /// Difference hash: one bit per neighbouring pair of pixels in a 9x8
+    /// grayscale thumbnail, set where the left one is brighter.
+    pub hash: u64,
+}
+
+impl ImageStats {
+    /// Measure a decoded image.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn of(image: &image::DynamicImage) -> Self {
+        let sample = image.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE).into_rgb8();
+        let lumas: Vec<f32> = sample
+            .pixels()
+            .map(|p| {
+                let [r, g, b] = p.0.map(|c| f32::from(c) / 255.0);
+                0.2126 * r + 0.7152 * g + 0.0722 * b
+            })
+            .collect();
+        let count = lumas.len().max(1) as f32;
+        let luminance = lumas.iter().sum::<f32>() / count;
+        let variance = lumas.iter().map(|l| (l - luminance).powi(2)).sum::<f32>() / count;
+        Self {
+            luminance,
+            contrast: variance.sqrt(),
+            hash: dhash(&image::imageops::grayscale(&sample)),
+        }
+    }
+}
+
+fn dhash(gray: &image::GrayImage) -> u64 {
+    let small = image::imageops::resize(gray, 9, 8, image::imageops::FilterType::Triangle);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash = hash << 1 | u64::from(small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0]);
+        }
+    }
+    hash
+}
+
+/// How many bits two perceptual hashes differ in.
+#[must_use]
+pub const fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Whether two perceptual hashes are of the same picture.
+#[must_use]
+pub const fn is_duplicate(a: u64, b: u64) -> bool {
+    distance(a, b) <= DUPLICATE_DISTANCE
+}
+
+/// What's compared to tell whether two images are the same picture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fingerprint {
+    pub hash: u64,
+    pub luminance: Option<f32>,
+    pub contrast: Option<f32>,
+}
+
+/// Whether two images are the same picture: near-identical hashes and
+/// similar brightness. Flat images are never taken for copies, since their
+/// hashes say nothing.
+#[must_use]
+pub fn same_picture(a: Fingerprint, b: Fingerprint) -> bool {
+    let flat = |f: Fingerprint| f.contrast.is_some_and(|c| c < MIN_HASH_CONTRAST);
+    if flat(a) || flat(b) {
+        return false;
+    }
+    if let (Some(x), Some(y)) = (a.luminance, b.luminance)
+        && (x - y).abs() > MAX_COPY_LUMINANCE_DIFFERENCE
+    {
+        return false;
+    }
+    is_duplicate(a.hash, b.hash)
+}
+
+/// Group the indices of the same picture, in order of first appearance.
+/// Each index is compared against the first of every group so far, and only
+/// groups with more than one member are returned.
+#[must_use]
+pub fn duplicate_groups(fingerprints: &[Option<Fingerprint>]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<(Fingerprint, Vec<usize>)> = Vec::new();
+    for (index, fingerprint) in fingerprints.iter().enumerate() {
+        let Some(fingerprint) = *fingerprint else {
+            continue;
+        };
+        match groups
+            .iter_mut()
+            .find(|(first, _)| same_picture(*first, fingerprint))
+        {
+            Some((_, members)) => members.push(index),
+            None => groups.push((fingerprint, vec![index])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(_, members)| members)
+        .filter(|members| members.len() > 1)
+        .collect()
+}
+
+/// Measure the image file at `path`.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read or decoded.
+pub async fn analyze(path: &Path) -> Result<ImageStats> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<ImageStats> {
+        let image =
+            image::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(ImageStats::of(&image))
+    })
+    .await
+    .context("Image analysis task failed")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    #[test]
+    fn test_stats() {
+        let flat = DynamicImage::ImageRgb8(RgbImage::from_pixel(300, 200, Rgb([51, 51, 51])));
+        let stats = ImageStats::of(&flat);
+        assert!((stats.luminance - 0.2).abs() < 0.01);
+        assert!(stats.contrast < 0.01);
+        assert_eq!(Tone::of(stats.luminance), Tone::Dark);
+
+        // Half black, half white.
+        let split = DynamicImage::ImageRgb8(RgbImage::from_fn(256, 256, |x, _| {
+            if x < 128 {
+                Rgb([0, 0, 0])
+            } else {
+                Rgb([255, 255, 255])
+            }
+        }));
+        let stats = ImageStats::of(&split);
+        assert!((stats.luminance - 0.5).abs() < 0.01);
+        assert!((stats.contrast - 0.5).abs() < 0.01);
+        assert_eq!(Tone::of(0.8), Tone::Light);
+    }
+
+    fn pattern(seed: u32, width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let (u, v) = (x * 64 / width, y * 64 / height);
+            let value = (u * 7 + v * 13).wrapping_mul(seed) % 251;
+            Rgb([value as u8; 3])
+        }))
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn test_hash_survives_resizing() {
+        let original = ImageStats::of(&pattern(3, 640, 480)).hash;
+        let resized = ImageStats::of(&pattern(3, 320, 240)).hash;
+        let other = ImageStats::of(&pattern(5, 640, 480)).hash;
+        assert!(
+            is_duplicate(original, resized),
+            "{}",
+            distance(original, resized)
+        );
+        assert!(
+            !is_duplicate(original, other),
+            "{}",
+            distance(original, other)
+        );
+
+        let fingerprint = |hash| {
+            Some(Fingerprint {
+                hash,
+                luminance: Some(0.5),
+                contrast: Some(0.2),
+            })
+        };
+        let groups = duplicate_groups(&[
+            fingerprint(original),
+            None,
+            fingerprint(other),
+            fingerprint(resized),
+        ]);
+        assert_eq!(groups, vec![vec![0, 3]]);
+    }
+
+    #[test]
+    fn test_flat_and_differently_lit_images_are_not_copies() {
+        let flat = |value: u8| {
+            ImageStats::of(&DynamicImage::ImageRgb8(RgbImage::from_pixel(
+                64,
+                64,
+                Rgb([value; 3]),
+            )))
+        };
+        let (black, grey) = (flat(0), flat(128));
+        assert_eq!(black.hash, grey.hash);
+        let fingerprint = |stats: ImageStats| Fingerprint {
+            hash: stats.hash,
+            luminance: Some(stats.luminance),
+            contrast: Some(stats.contrast),
+        };
+        assert!(!same_picture(fingerprint(black), fingerprint(grey)));
+
+        // Gradients running the same way hash alike whatever their colours.
+        let gradient = |from: u8| {
+            ImageStats::of(&DynamicImage::ImageRgb8(RgbImage::from_fn(
+                64,
+                64,
+                |x, _| Rgb([from.saturating_add(u8::try_from(x).unwrap_or(0)); 3]),
+            )))
+        };
+        let (dark, light) = (gradient(0), gradient(180));
+        assert_eq!(dark.hash, light.hash);
+        assert!(!same_picture(fingerprint(dark), fingerprint(light)));
+        assert!(same_picture(fingerprint(dark), fingerprint(dark)));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::config::Config;
+use crate::http;
+use crate::unsplash::ImageQuality;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// NASA's shared key, limited to 30 requests an hour per IP address.
+pub const DEMO_KEY: &str = "DEMO_KEY";
+
+/// How many days back to look for an image when recent entries are videos.
+const LOOKBACK_DAYS: u64 = 7;
+
+#[derive(Debug, Deserialize)]
+pub struct ApodEntry {
+    /// e.g. `2026-10-18`.
+    pub date: String,
+    pub title: String,
+    pub explanation: Option<String>,
+    /// `image`, `video` or `other`.
+    pub media_type: String,
+    pub url: String,
+    pub hdurl: Option<String>,
+    /// Absent for public-domain NASA images.
+    pub copyright: Option<String>,
+}
+
+impl ApodEntry {
+    #[must_use]
+    pub fn is_image(&self) -> bool {
+        self.media_type == "image"
+    }
+
+    /// The HD image for full quality, the regular one otherwise.
+    #[must_use]
+    pub fn image_url(&self, quality: ImageQuality) -> &str {
+        match quality {
+            ImageQuality::Full => self.hdurl.as_deref().unwrap_or(&self.url),
+            ImageQuality::Reduced => &self.url,
+        }
+    }
+
+    /// The copyright holder on one line (the API sometimes wraps it).
+    #[must_use]
+    pub fn credit(&self) -> Option<String> {
+        let credit = self
+            .copyright
+            .as_deref()?
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        (!credit.is_empty()).then_some(credit)
+    }
+
+    /// The entry's page on apod.nasa.gov, e.g. `ap261018.html`.
+    #[must_use]
+    pub fn page_url(&self) -> Option<String> {
+        let date = chrono::NaiveDate::parse_from_str(&self.date, "%Y-%m-%d").ok()?;
+        Some(format!(
+            "https://apod.nasa.gov/apod/ap{}.html",
+            date.format("%y%m%d")
+        ))
+    }
+}
+
+pub struct ApodClient {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+}
+
+impl ApodClient {
+    /// A client using the API key, API URL and network settings from `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client can't be built.
+    pub fn for_config(config: &Config) -> Result<Self> {
+        Ok(Self {
+            client: http::build_client(&config.network)?,
+            base_url: config.apod_api_url(),
+            api_key: config
+                .apod
+                .api_key
+                .as_deref()
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .unwrap_or(DEMO_KEY)
+                .to_string(),
+        })
+    }
+
+    /// The most recent picture of the day up to `today`, skipping days whose
+    /// entry is a video.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network request fails, the API returns an error status,
+    /// or none of the last week's entries is an image.
+    pub async fn latest_image(&self, today: chrono::NaiveDate) -> Result<ApodEntry> {
+        let start = today - chrono::Days::new(LOOKBACK_DAYS);
+        let response = self
+            .client
+            .get(&self.base_url)
+            .query(&[
+                ("api_key", self.api_key.as_str()),
+                ("start_date", &start.to_string()),
+            ])
+            .send()
+            .await
+            .context("Failed to send APOD request")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("APOD API Error {status}: {text}");
+        }
+
+        let entries: Vec<ApodEntry> = response
+            .json()
+            .await
+            .context("Failed to parse APOD response")?;
+        latest(entries).context("No APOD image in the last week, only videos")
+    }
+}
+
+/// The newest image entry; dates are ISO-8601 so they sort as strings.
+fn latest(entries: Vec<ApodEntry>) -> Option<ApodEntry> {
+    entries
+        .into_iter()
+        .filter(ApodEntry::is_image)
+        .max_by(|a, b| a.date.cmp(&b.date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRIES: &str = r#"[
+        {"date": "2026-10-16", "title": "Orion", "media_type": "image",
+         "url": "https://apod.nasa.gov/apod/image/2610/orion_1024.jpg",
+         "hdurl": "https://apod.nasa.gov/apod/image/2610/orion.jpg",
+         "copyright": "\nJane\nDoe\n", "explanation": "A nebula."},
+        {"date": "2026-10-18", "title": "Launch", "media_type": "video",
+         "url": "https://www.youtube.com/embed/abc"},
+        {"date": "2026-10-17", "title": "Moon", "media_type": "image",
+         "url": "https://apod.nasa.gov/apod/image/2610/moon.jpg"}
+    ]"#;
+
+    #[test]
+    fn test_latest_skips_videos() -> anyhow::Result<()> {
+        let entries: Vec<ApodEntry> = serde_json::from_str(ENTRIES)?;
+        let entry = latest(entries).expect("an image entry");
+        assert_eq!(entry.title, "Moon");
+        // Without `hdurl` the regular image is used at any quality.
+        assert_eq!(
+            entry.image_url(ImageQuality::Full),
+            "https://apod.nasa.gov/apod/image/2610/moon.jpg"
+        );
+        assert_eq!(
+            entry.page_url().as_deref(),
+            Some("https://apod.nasa.gov/apod/ap261017.html")
+        );
+        assert!(entry.credit().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_credit_and_quality() -> anyhow::Result<()> {
+        let entries: Vec<ApodEntry> = serde_json::from_str(ENTRIES)?;
+        let orion = &entries[0];
+        assert_eq!(orion.credit().as_deref(), Some("Jane Doe"));
+        assert!(orion.image_url(ImageQuality::Full).ends_with("orion.jpg"));
+        assert!(
+            orion
+                .image_url(ImageQuality::Reduced)
+                .ends_with("orion_1024.jpg")
+        );
+        assert!(latest(entries.into_iter().skip(1).take(1).collect()).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use crate::config::Config;
+use crate::http;
+use crate::unsplash::ImageQuality;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct ArchiveResponse {
+    images: Vec<BingImage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BingImage {
+    /// Day the image was featured, e.g. `20261018`.
+    pub startdate: String,
+    /// Image path without the resolution suffix, e.g. `/th?id=OHR.Lake_EN-US123`.
+    pub urlbase: String,
+    pub title: Option<String>,
+    /// e.g. `Lake Bled, Slovenia (© Photographer/Getty Images)`.
+    pub copyright: Option<String>,
+    pub copyrightlink: Option<String>,
+}
+
+impl BingImage {
+    /// The image URL at `quality`: UHD for full, 1080p otherwise.
+    #[must_use]
+    pub fn image_url(&self, base_url: &str, quality: ImageQuality) -> String {
+        let suffix = match quality {
+            ImageQuality::Full => "_UHD.jpg",
+            ImageQuality::Reduced => "_1920x1080.jpg",
+        };
+        if self.urlbase.starts_with("http") {
+            format!("{}{suffix}", self.urlbase)
+        } else {
+            format!("{base_url}{}{suffix}", self.urlbase)
+        }
+    }
+
+    /// The credit inside the copyright's `(© ...)`.
+    #[must_use]
+    pub fn photographer(&self) -> Option<String> {
+        let copyright = self.copyright.as_deref()?;
+        let start = copyright.rfind("(©")?;
+        let credit = copyright[start + "(©".len()..].trim_end_matches(')').trim();
+        (!credit.is_empty()).then(|| credit.to_string())
+    }
+}
+
+pub struct BingClient {
+    client: reqwest::Client,
+    base_url: String,
+    market: String,
+}
+
+impl BingClient {
+    /// A client using the market, base URL and network settings from `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client can't be built.
+    pub fn for_config(config: &Config) -> Result<Self> {
+        Ok(Self {
+            client: http::build_client(&config.network)?,
+            base_url: config.bing_api_url(),
+            market: config.bing.market.clone(),
+        })
+    }
+
+    #[must_use]
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Today's image for the configured market.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network request fails, Bing returns an error status or
+    /// no image.
+    pub async fn image_of_the_day(&self) -> Result<BingImage> {
+        let response = self
+            .client
+            .get(format!("{}/HPImageArchive.aspx", self.base_url))
+            .query(&[
+                ("format", "js"),
+                ("idx", "0"),
+                ("n", "1"),
+                ("mkt", &self.market),
+            ])
+            .send()
+            .await
+            .context("Failed to send Bing request")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Bing Error {status}: {text}");
+        }
+
+        let archive: ArchiveResponse = response
+            .json()
+            .await
+            .context("Failed to parse Bing image archive")?;
+        archive
+            .images
+            .into_iter()
+            .next()
+            .context("Bing returned no image of the day")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_deserialization() -> anyhow::Result<()> {
+        let json = r#"{"images": [{
+            "startdate": "20261018",
+            "fullstartdate": "202610180700",
+            "url": "/th?id=OHR.LakeBled_EN-US123_1920x1080.jpg&rf=LaDigue_1920x1080.jpg",
+            "urlbase": "/th?id=OHR.LakeBled_EN-US123",
+            "copyright": "Lake Bled, Slovenia (© Jane Doe/Getty Images)",
+            "copyrightlink": "https://www.bing.com/search?q=Lake+Bled",
+            "title": "An island in the lake"
+        }]}"#;
+
+        let image = &serde_json::from_str::<ArchiveResponse>(json)?.images[0];
+        assert_eq!(image.startdate, "20261018");
+        assert_eq!(
+            image.image_url("https://www.bing.com", ImageQuality::Full),
+            "https://www.bing.com/th?id=OHR.LakeBled_EN-US123_UHD.jpg"
+        );
+        assert_eq!(
+            image.image_url("https://www.bing.com", ImageQuality::Reduced),
+            "https://www.bing.com/th?id=OHR.LakeBled_EN-US123_1920x1080.jpg"
+        );
+        assert_eq!(
+            image.photographer().as_deref(),
+            Some("Jane Doe/Getty Images")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_photographer_without_credit() {
+        let image = BingImage {
+            startdate: "20261018".to_string(),
+            urlbase: String::new(),
+            title: None,
+            copyright: Some("Lake Bled, Slovenia".to_string()),
+            copyrightlink: None,
+        };
+        assert!(image.photographer().is_none());
+    }
+}
@@ -0,0 +1,639 @@
+//! Full-screen history browser behind `wallp list` and `wallp set`.
+
+use crate::cli::format_datetime;
+use crate::config::{AppData, Wallpaper};
+use crate::manager;
+use crate::preview;
+use anyhow::Result;
+use image::RgbImage;
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Widget, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+/// How far back the date filter reaches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Period {
+    #[default]
+    Any,
+    Today,
+    Week,
+    Month,
+}
+
+impl Period {
+    const fn next(self) -> Self {
+        match self {
+            Self::Any => Self::Today,
+            Self::Today => Self::Week,
+            Self::Week => Self::Month,
+            Self::Month => Self::Any,
+        }
+    }
+
+    const fn days(self) -> Option<i64> {
+        match self {
+            Self::Any => None,
+            Self::Today => Some(1),
+            Self::Week => Some(7),
+            Self::Month => Some(30),
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Any => "any time",
+            Self::Today => "last day",
+            Self::Week => "last 7 days",
+            Self::Month => "last 30 days",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Filters {
+    search: String,
+    favorites_only: bool,
+    source: Option<String>,
+    period: Period,
+}
+
+impl Filters {
+    fn matches(&self, wallpaper: &Wallpaper, now: chrono::DateTime<chrono::Utc>) -> bool {
+        if self.favorites_only && !wallpaper.favorite {
+            return false;
+        }
+        if let Some(source) = &self.source
+            && wallpaper.source.as_ref() != Some(source)
+        {
+            return false;
+        }
+        if let Some(days) = self.period.days() {
+            let recent =
+                chrono::DateTime::parse_from_rfc3339(wallpaper.last_shown()).is_ok_and(|shown| {
+                    now - shown.with_timezone(&chrono::Utc) <= chrono::Duration::days(days)
+                });
+            if !recent {
+                return false;
+            }
+        }
+        let search = self.search.trim().to_lowercase();
+        search.is_empty()
+            || [&wallpaper.title, &wallpaper.author, &wallpaper.source]
+                .into_iter()
+                .flatten()
+                .any(|field| field.to_lowercase().contains(&search))
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.favorites_only {
+            parts.push("favorites".to_string());
+        }
+        if let Some(source) = &self.source {
+            parts.push(source.clone());
+        }
+        if self.period != Period::Any {
+            parts.push(self.period.label().to_string());
+        }
+        if !self.search.is_empty() {
+            parts.push(format!("\"{}\"", self.search));
+        }
+        parts.join(", ")
+    }
+}
+
+/// What a key press asks for that needs confirming first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    Delete,
+    Ban,
+}
+
+struct Browser {
+    app_data: AppData,
+    filters: Filters,
+    /// Numbers (0 is the most recent) of the wallpapers passing the filters.
+    shown: Vec<usize>,
+    list: ListState,
+    searching: bool,
+    pending: Option<Pending>,
+    message: Option<String>,
+    /// The selected wallpaper's filename and thumbnail, if it could be made.
+    thumbnail: Option<(String, Option<RgbImage>)>,
+    quit: bool,
+}
+
+/// Browse history until the user quits.
+///
+/// # Errors
+///
+/// Returns an error if the config can't be loaded or the terminal can't be
+/// drawn to.
+pub fn run(rt: &tokio::runtime::Runtime) -> Result<()> {
+    let mut browser = Browser::new(AppData::load()?);
+    let mut terminal = ratatui::init();
+    let result = browser.event_loop(&mut terminal, rt);
+    ratatui::restore();
+    result
+}
+
+impl Browser {
+    fn new(app_data: AppData) -> Self {
+        let mut browser = Self {
+            app_data,
+            filters: Filters::default(),
+            shown: Vec::new(),
+            list: ListState::default(),
+            searching: false,
+            pending: None,
+            message: None,
+            thumbnail: None,
+            quit: false,
+        };
+        browser.refilter();
+        // Start on the wallpaper that's on screen.
+        let current = browser
+            .app_data
+            .history
+            .len()
+            .checked_sub(1 + browser.app_data.state.current_history_index);
+        if let Some(row) = current.and_then(|n| browser.shown.iter().position(|&i| i == n)) {
+            browser.list.select(Some(row));
+        }
+        browser
+    }
+
+    fn event_loop(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        rt: &tokio::runtime::Runtime,
+    ) -> Result<()> {
+        while !self.quit {
+            self.load_thumbnail(rt);
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.handle_key(key, rt);
+            }
+        }
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<(usize, &Wallpaper)> {
+        let number = *self.shown.get(self.list.selected()?)?;
+        let history = &self.app_data.history;
+        Some((number, history.get(history.len().checked_sub(number + 1)?)?))
+    }
+
+    fn refilter(&mut self) {
+        let now = chrono::Utc::now();
+        let history = &self.app_data.history;
+        self.shown = history
+            .iter()
+            .rev()
+            .enumerate()
+            .filter(|(_, w)| self.filters.matches(w, now))
+            .map(|(number, _)| number)
+            .collect();
+        let row = self
+            .list
+            .selected()
+            .unwrap_or(0)
+            .min(self.shown.len().saturating_sub(1));
+        self.list.select(if self.shown.is_empty() {
+            None
+        } else {
+            Some(row)
+        });
+    }
+
+    fn reload(&mut self) {
+        match AppData::load() {
+            Ok(app_data) => self.app_data = app_data,
+            Err(e) => self.message = Some(format!("Failed to reload history: {e}")),
+        }
+        self.refilter();
+    }
+
+    fn load_thumbnail(&mut self, rt: &tokio::runtime::Runtime) {
+        let Some((_, wallpaper)) = self.selected() else {
+            self.thumbnail = None;
+            return;
+        };
+        if self
+            .thumbnail
+            .as_ref()
+            .is_some_and(|(filename, _)| *filename == wallpaper.filename)
+        {
+            return;
+        }
+        let filename = wallpaper.filename.clone();
+        let image = AppData::get_data_dir().ok().and_then(|data_dir| {
+            let source = data_dir.join("wallpapers").join(&filename);
+            let thumbnail = rt
+                .block_on(preview::thumbnail(&source, &data_dir.join("thumbnails")))
+                .ok()?;
+            Some(image::open(thumbnail).ok()?.into_rgb8())
+        });
+        self.thumbnail = Some((filename, image));
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, rt: &tokio::runtime::Runtime) {
+        if self.searching {
+            match key.code {
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.searching = false;
+                    self.filters.search.clear();
+                }
+                KeyCode::Backspace => {
+                    self.filters.search.pop();
+                }
+                KeyCode::Char(c) => self.filters.search.push(c),
+                _ => return,
+            }
+            self.refilter();
+            return;
+        }
+
+        if let Some(pending) = self.pending.take() {
+            if matches!(key.code, KeyCode::Char('y' | 'Y')) {
+                self.remove(pending == Pending::Ban);
+            } else {
+                self.message = Some("Cancelled.".to_string());
+            }
+            return;
+        }
+
+        self.message = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') => self.list.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.list.select_previous(),
+            KeyCode::PageDown => self.list.scroll_down_by(10),
+            KeyCode::PageUp => self.list.scroll_up_by(10),
+            KeyCode::Home | KeyCode::Char('g') => self.list.select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.list.select_last(),
+            KeyCode::Enter => self.apply(rt),
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Char('*') => self.toggle_favorite(),
+            KeyCode::Char('d') | KeyCode::Delete => self.confirm(Pending::Delete),
+            KeyCode::Char('b') => self.confirm(Pending::Ban),
+            KeyCode::Char('o') => self.open(),
+            KeyCode::Char('f') => {
+                self.filters.favorites_only = !self.filters.favorites_only;
+                self.refilter();
+            }
+            KeyCode::Char('s') => {
+                self.filters.source = self.next_source();
+                self.refilter();
+            }
+            KeyCode::Char('t') => {
+                self.filters.period = self.filters.period.next();
+                self.refilter();
+            }
+            _ => {}
+        }
+    }
+
+    /// The source after the one filtered on, in order of first appearance,
+    /// or no filter after the last.
+    fn next_source(&self) -> Option<String> {
+        let mut sources: Vec<&String> = Vec::new();
+        for source in self
+            .app_data
+            .history
+            .iter()
+            .rev()
+            .filter_map(|w| w.source.as_ref())
+        {
+            if !sources.contains(&source) {
+                sources.push(source);
+            }
+        }
+        let next = match &self.filters.source {
+            Some(current) => sources
+                .iter()
+                .position(|s| *s == current)
+                .map_or(0, |i| i + 1),
+            None => 0,
+        };
+        sources.get(next).map(|s| (*s).clone())
+    }
+
+    fn apply(&mut self, rt: &tokio::runtime::Runtime) {
+        let Some((number, wallpaper)) = self.selected() else {
+            return;
+        };
+        let title = display_title(wallpaper);
+        self.message = Some(match rt.block_on(manager::set_by_index(number)) {
+            Ok(()) => format!("✅ Set {title}"),
+            Err(e) => format!("Failed to set wallpaper: {e}"),
+        });
+        self.reload();
+    }
+
+    fn toggle_favorite(&mut self) {
+        let Some((number, _)) = self.selected() else {
+            return;
+        };
+        match manager::toggle_favorite(number) {
+            Ok(favorite) => {
+                self.message = Some(
+                    if favorite {
+                        "★ Added to favorites"
+                    } else {
+                        "Removed from favorites"
+                    }
+                    .to_string(),
+                );
+            }
+            Err(e) => self.message = Some(format!("Failed to update favorite: {e}")),
+        }
+        self.reload();
+    }
+
+    fn confirm(&mut self, action: Pending) {
+        let Some((_, wallpaper)) = self.selected() else {
+            return;
+        };
+        let verb = match action {
+            Pending::Delete => "Delete",
+            Pending::Ban => "Ban",
+        };
+        self.message = Some(format!("{verb} {}? (y/n)", display_title(wallpaper)));
+        self.pending = Some(action);
+    }
+
+    fn remove(&mut self, ban: bool) {
+        let Some((number, _)) = self.selected() else {
+            return;
+        };
+        self.message = Some(match manager::remove_from_history(number, ban) {
+            Ok(removed) if ban => format!("🚫 Banned {}", display_title(&removed)),
+            Ok(removed) => format!("🗑️ Deleted {}", display_title(&removed)),
+            Err(e) => format!("Failed to remove wallpaper: {e}"),
+        });
+        self.reload();
+    }
+
+    fn open(&mut self) {
+        let Some((_, wallpaper)) = self.selected() else {
+            return;
+        };
+        self.message = Some(match &wallpaper.url {
+            Some(url) => match open::that(url) {
+                Ok(()) => format!("Opened {url}"),
+                Err(e) => format!("Failed to open {url}: {e}"),
+            },
+            None => "This wallpaper has no web page.".to_string(),
+        });
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(body);
+
+        let filters = self.filters.describe();
+        let mut title = format!(
+            " wallp history: {} of {} ",
+            self.shown.len(),
+            self.app_data.history.len()
+        );
+        if !filters.is_empty() {
+            title = format!("{title}[{filters}] ");
+        }
+        frame.render_widget(Line::from(title).bold(), header);
+
+        let len = self.app_data.history.len();
+        let current = len.checked_sub(1 + self.app_data.state.current_history_index);
+        let items: Vec<ListItem> = self
+            .shown
+            .iter()
+            .filter_map(|&number| {
+                let wallpaper = self.app_data.history.get(len.checked_sub(number + 1)?)?;
+                let marker = if Some(number) == current { "●" } else { " " };
+                let star = if wallpaper.favorite { "★" } else { " " };
+                Some(ListItem::new(Line::from(vec![
+                    Span::raw(format!("{number:>3} {marker}{star} ")),
+                    Span::raw(display_title(wallpaper)),
+                ])))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered())
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        let [image_area, info_area] =
+            Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(detail_area);
+        let image_block = Block::bordered();
+        let inner = image_block.inner(image_area);
+        frame.render_widget(image_block, image_area);
+        if let Some((_, Some(image))) = &self.thumbnail {
+            frame.render_widget(Thumbnail(image), inner);
+        }
+        let details = self.selected().map(|(_, w)| details(w)).unwrap_or_default();
+        frame.render_widget(
+            Paragraph::new(details)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered()),
+            info_area,
+        );
+
+        let status = if self.searching {
+            Line::from(format!("/{}", self.filters.search))
+        } else if let Some(message) = &self.message {
+            Line::from(message.as_str())
+        } else {
+            Line::from(
+                "enter set · * favorite · d delete · b ban · o open · / search · f favorites · s source · t date · q quit",
+            )
+            .dim()
+        };
+        frame.render_widget(status, footer);
+    }
+}
+
+fn display_title(wallpaper: &Wallpaper) -> String {
+    let title = wallpaper.title.as_deref().unwrap_or("Untitled");
+    match wallpaper.author.as_deref() {
+        Some(author) if !author.is_empty() => format!("{title} by {author}"),
+        _ => title.to_string(),
+    }
+}
+
+fn details(wallpaper: &Wallpaper) -> Vec<Line<'static>> {
+    let field = |name: &str, value: String| {
+        Line::from(vec![
+            Span::raw(format!("{name}: ")).bold(),
+            Span::raw(value),
+        ])
+    };
+    let mut lines = vec![field(
+        "Title",
+        wallpaper
+            .title
+            .clone()
+            .unwrap_or_else(|| "Untitled".to_string()),
+    )];
+    if let Some(author) = &wallpaper.author {
+        lines.push(field("Author", author.clone()));
+    }
+    if let Some(source) = &wallpaper.source {
+        lines.push(field("Source", source.clone()));
+    }
+    lines.push(field("Last shown", format_datetime(wallpaper.last_shown())));
+    if let (Some(tone), Some(luminance)) = (wallpaper.tone(), wallpaper.luminance) {
+        lines.push(field(
+            "Brightness",
+            format!("{} ({:.0}%)", tone.as_str(), luminance * 100.0),
+        ));
+    }
+    if wallpaper.favorite {
+        lines.push(field("Favorite", "yes".to_string()));
+    }
+    if let Some(url) = &wallpaper.url {
+        lines.push(field("Web", url.clone()));
+    }
+    lines
+}
+
+/// A thumbnail drawn with half blocks, centered in its area.
+struct Thumbnail<'a>(&'a RgbImage);
+
+impl Widget for Thumbnail<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let image = preview::half_block_pixels(
+            &image::DynamicImage::ImageRgb8(self.0.clone()),
+            area.width,
+            Some(area.height),
+        );
+        let (width, height) = image.dimensions();
+        let columns = u16::try_from(width).unwrap_or(area.width).min(area.width);
+        let rows = u16::try_from(height.div_ceil(2))
+            .unwrap_or(area.height)
+            .min(area.height);
+        let left = area.x + (area.width - columns) / 2;
+        let top = area.y + (area.height - rows) / 2;
+        for row in 0..rows {
+            for column in 0..columns {
+                let (x, y) = (u32::from(column), u32::from(row) * 2);
+                let cell = &mut buf[(left + column, top + row)];
+                cell.set_char('▀').set_fg(rgb(*image.get_pixel(x, y)));
+                if y + 1 < height {
+                    cell.set_bg(rgb(*image.get_pixel(x, y + 1)));
+                }
+            }
+        }
+    }
+}
+
+const fn rgb(pixel: image::Rgb<u8>) -> Color {
+    let [r, g, b] = pixel.0;
+    Color::Rgb(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    fn history() -> AppData {
+        let now = chrono::Utc::now();
+        let mut app_data = AppData::default();
+        for (id, source, days_ago, favorite) in [
+            ("old", "bing", 20, true),
+            ("fjord", "unsplash", 3, false),
+            ("dunes", "unsplash", 0, false),
+        ] {
+            app_data.history.push(Wallpaper {
+                id: id.to_string(),
+                filename: format!("wallpaper_{id}.jpg"),
+                title: Some(format!("Photo of {id}")),
+                author: Some("Someone".to_string()),
+                source: Some(source.to_string()),
+                applied_at: (now - chrono::Duration::days(days_ago)).to_rfc3339(),
+                favorite,
+                ..Default::default()
+            });
+        }
+        app_data.state.current_history_index = 1;
+        app_data
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut browser = Browser::new(history());
+        assert_eq!(browser.shown, vec![0, 1, 2]);
+        // The current wallpaper (fjord) is selected to begin with.
+        assert_eq!(browser.selected().map(|(n, _)| n), Some(1));
+
+        browser.filters.favorites_only = true;
+        browser.refilter();
+        assert_eq!(browser.shown, vec![2]);
+
+        browser.filters = Filters::default();
+        browser.filters.source = browser.next_source();
+        assert_eq!(browser.filters.source.as_deref(), Some("unsplash"));
+        browser.filters.source = browser.next_source();
+        assert_eq!(browser.filters.source.as_deref(), Some("bing"));
+        browser.filters.source = browser.next_source();
+        assert_eq!(browser.filters.source, None);
+
+        browser.filters.period = Period::Week;
+        browser.refilter();
+        assert_eq!(browser.shown, vec![0, 1]);
+
+        browser.filters = Filters {
+            search: "FJORD".to_string(),
+            ..Filters::default()
+        };
+        browser.refilter();
+        assert_eq!(browser.shown, vec![1]);
+        assert_eq!(browser.filters.describe(), "\"FJORD\"");
+    }
+
+    #[test]
+    fn test_draw() -> anyhow::Result<()> {
+        let mut browser = Browser::new(history());
+        browser.thumbnail = Some((
+            "wallpaper_fjord.jpg".to_string(),
+            Some(RgbImage::from_pixel(32, 16, image::Rgb([10, 20, 30]))),
+        ));
+        let mut terminal = Terminal::new(TestBackend::new(100, 20))?;
+        terminal.draw(|frame| browser.draw(frame))?;
+
+        let buffer = terminal.backend().buffer();
+        let text: String = buffer
+            .content()
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect();
+        assert!(text.contains("wallp history: 3 of 3"));
+        assert!(text.contains("  1 ●  Photo of fjord by Someone"));
+        assert!(text.contains("  2  ★ Photo of old by Someone"));
+        assert!(text.contains("Title: Photo of fjord"));
+        assert!(
+            buffer
+                .content()
+                .iter()
+                .any(|cell| cell.symbol() == "▀" && cell.fg == Color::Rgb(10, 20, 30))
+        );
+        Ok(())
+    }
+}
@@ -1,15 +1,25 @@
-use crate::config::AppData;
+use crate::browser;
+use crate::config::{AccessKeySource, AppData, ContentFilter, CustomCollection, Wallpaper};
+use crate::credentials;
+use crate::http;
 use crate::manager;
+use crate::output::{self, Format};
+use crate::preview;
+use crate::scheduler;
+use crate::settings::{self, MIN_INTERVAL_MINUTES, parse_interval};
+use crate::sources;
+use crate::unsplash::UnsplashClient;
 use anyhow::{Context, Result};
 use chrono::DateTime;
 pub use clap::{Parser, Subcommand};
-use dialoguer::{Confirm, Input, MultiSelect};
+use dialoguer::{Confirm, Input, MultiSelect, Password, Select};
 use std::env;
 use std::fs;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-fn format_datetime(iso: &str) -> String {
+pub(crate) fn format_datetime(iso: &str) -> String {
     DateTime::parse_from_rfc3339(iso).map_or_else(
         |_| iso.to_string(),
         |dt| dt.format("%b %d, %Y at %l:%M %p").to_string(),
@@ -43,10 +53,24 @@ pub struct Cli {
     pub help: bool,
     #[arg(short = 'v', long, action = clap::ArgAction::Version)]
     pub version: Option<bool>,
+    /// print results as JSON, without prompting (same as --format json)
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// how to print results
+    #[arg(long, value_enum, global = true, default_value_t)]
+    pub format: Format,
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
+impl Cli {
+    /// The output format asked for by `--json` or `--format`.
+    #[must_use]
+    pub fn output_format(&self) -> Format {
+        if self.json { Format::Json } else { self.format }
+    }
+}
+
 #[derive(Subcommand)]
 #[allow(clippy::enum_variant_names)]
 pub enum Commands {
@@ -58,7 +82,7 @@ pub enum Commands {
     Prev,
     /// show current wallpaper details
     Info,
-    /// set wallpaper by number from history (shows list if no number provided)
+    /// set wallpaper by number from history (browse history if no number provided)
     Set {
         /// wallpaper number to set (see 'wallp list')
         index: Option<usize>,
@@ -66,14 +90,42 @@ pub enum Commands {
 
     /// show scheduler status
     Status,
-    /// list recent wallpaper history
-    List,
+    /// pause the scheduler (e.g., "2h", or --until 18:00; indefinitely if omitted)
+    Pause {
+        /// how long to pause for (e.g., 30m, 2h, 1d)
+        duration: Option<String>,
+        /// local time to resume at (HH:MM)
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// resume a paused scheduler
+    Resume,
+    /// browse wallpaper history (prints it when not in a terminal)
+    List {
+        /// draw a thumbnail of each wallpaper
+        #[arg(long)]
+        thumbs: bool,
+    },
+    /// show a wallpaper in the terminal (the current one if no number provided)
+    Preview {
+        /// wallpaper number to show (see 'wallp list')
+        index: Option<usize>,
+    },
     /// show current configuration settings
     Settings,
     /// open wallpapers folder in file manager
     Folder,
-    /// open configuration file in default editor
-    Config,
+    /// view or change settings (opens the config file if no action given)
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
+    /// find copies of the same photo among downloaded wallpapers
+    Dedupe {
+        /// delete the copies, keeping the current one, a favorite or the largest
+        #[arg(long)]
+        remove: bool,
+    },
 
     /// run interactive setup wizard
     Setup,
@@ -89,12 +141,58 @@ pub enum Commands {
     Uninstall,
 }
 
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// print a setting (e.g., "topics", "network.proxy")
+    Get {
+        /// setting name, with dots for nested settings
+        key: String,
+        /// show API keys in full instead of masked
+        #[arg(long)]
+        reveal: bool,
+    },
+    /// change a setting (e.g., "topics nature,travel", "overlay.enabled on")
+    Set {
+        /// setting name, with dots for nested settings
+        key: String,
+        /// new value: text, a number, true/false, a comma-separated list or JSON
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+        /// show API keys in full instead of masked
+        #[arg(long)]
+        reveal: bool,
+    },
+    /// put a setting back to its default
+    Unset {
+        /// setting name, with dots for nested settings
+        key: String,
+    },
+    /// print every setting
+    List,
+    /// print the path of the configuration file
+    Path,
+    /// open configuration file in default editor
+    Edit,
+}
+
 impl Commands {
     #[must_use]
     pub const fn group_index(&self) -> usize {
         match self {
-            Self::New | Self::Next | Self::Prev | Self::Info | Self::Set { .. } => 0,
-            Self::Status | Self::List | Self::Settings | Self::Folder | Self::Config => 1,
+            Self::New
+            | Self::Next
+            | Self::Prev
+            | Self::Info
+            | Self::Set { .. }
+            | Self::Preview { .. } => 0,
+            Self::Status
+            | Self::Pause { .. }
+            | Self::Resume
+            | Self::List { .. }
+            | Self::Settings
+            | Self::Folder
+            | Self::Config { .. }
+            | Self::Dedupe { .. } => 1,
             Self::Setup | Self::Uninstall | Self::Lockscreen { .. } => 2,
         }
     }
@@ -116,10 +214,17 @@ impl Commands {
                     "info" => Self::Info,
                     "set" => Self::Set { index: None },
                     "status" => Self::Status,
-                    "list" => Self::List,
+                    "pause" => Self::Pause {
+                        duration: None,
+                        until: None,
+                    },
+                    "resume" => Self::Resume,
+                    "list" => Self::List { thumbs: false },
+                    "preview" => Self::Preview { index: None },
                     "settings" => Self::Settings,
                     "folder" => Self::Folder,
-                    "config" => Self::Config,
+                    "config" => Self::Config { action: None },
+                    "dedupe" => Self::Dedupe { remove: false },
                     "setup" => Self::Setup,
                     "lockscreen" => Self::Lockscreen {
                         action: None,
@@ -161,6 +266,7 @@ pub fn print_grouped_help() {
     println!("\nOptions:");
     println!("  -h, --help     print help");
     println!("  -v, --version  print version");
+    println!("  --json         print results as JSON, without prompting");
 }
 
 const fn get_exe_name() -> &'static str {
@@ -174,24 +280,6 @@ const fn get_exe_name() -> &'static str {
     }
 }
 
-const MIN_INTERVAL_MINUTES: u64 = 30;
-
-fn parse_interval(input: &str) -> Result<u64, String> {
-    let input = input.trim().to_lowercase();
-    if let Ok(minutes) = input.parse::<u64>() {
-        return Ok(minutes);
-    }
-    let last_char = input.chars().last().ok_or("Empty input")?;
-    let number_part = &input[..input.len() - 1];
-    let value: u64 = number_part.parse().map_err(|_| "Invalid number")?;
-    match last_char {
-        'd' => Ok(value * 24 * 60),
-        'h' => Ok(value * 60),
-        'm' => Ok(value),
-        _ => Err("Use: d (days), h (hours), m (minutes)".to_string()),
-    }
-}
-
 fn format_interval_for_display(minutes: u64) -> String {
     if minutes >= 60 * 24 {
         format!("{}d", minutes / (60 * 24))
@@ -202,6 +290,54 @@ fn format_interval_for_display(minutes: u64) -> String {
     }
 }
 
+fn format_remaining(remaining: chrono::Duration) -> String {
+    let minutes = remaining.num_minutes();
+    if minutes < 1 {
+        return "<1m".to_string();
+    }
+    let (days, hours, mins) = (minutes / (60 * 24), (minutes / 60) % 24, minutes % 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {mins}m")
+    } else {
+        format!("{mins}m")
+    }
+}
+
+/// Parse a local "HH:MM" time into the next moment it occurs after `now`.
+fn parse_until(
+    input: &str,
+    now: DateTime<chrono::Local>,
+) -> Result<DateTime<chrono::Local>, String> {
+    let time = chrono::NaiveTime::parse_from_str(input.trim(), "%H:%M")
+        .map_err(|_| "Use HH:MM (24-hour), e.g. 18:00".to_string())?;
+    let mut date = now.date_naive();
+    if time <= now.time() {
+        date = date.succ_opt().ok_or("Date out of range")?;
+    }
+    date.and_time(time)
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .ok_or_else(|| "That time does not exist in the local timezone".to_string())
+}
+
+/// Split a comma-separated list, dropping blank entries.
+fn split_comma_list(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Treat blank text input as "not set".
+fn optional_text(input: &str) -> Option<String> {
+    let trimmed = input.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
 fn get_default_collections_info() -> Vec<(String, String)> {
     vec![
         ("1065976".to_string(), "Wallpapers".to_string()),
@@ -266,6 +402,99 @@ pub fn is_autostart_enabled() -> bool {
     auto.is_ok_and(|a| a.is_enabled().unwrap_or(false))
 }
 
+/// Ask where the access key should be kept, returning the source and the key
+/// to store in `wallp.json` (empty unless the source is the config file).
+fn prompt_access_key(app_data: &AppData) -> Result<(AccessKeySource, String)> {
+    let current = &app_data.config.access_key_source;
+    let sources = [
+        "Store in wallp.json",
+        "Environment variable",
+        "Key file (readable only by you)",
+        "Command (e.g. pass show unsplash)",
+    ];
+    let default = match current {
+        AccessKeySource::Config => 0,
+        AccessKeySource::Env(_) => 1,
+        AccessKeySource::File(_) => 2,
+        AccessKeySource::Command(_) => 3,
+    };
+
+    let choice = Select::new()
+        .with_prompt("Where should Wallp read the access key from?")
+        .items(sources)
+        .default(default)
+        .interact()
+        .context("Failed to select access key source")?;
+
+    let source = match choice {
+        1 => {
+            let var: String = Input::new()
+                .with_prompt("Environment variable")
+                .default(match current {
+                    AccessKeySource::Env(var) => var.clone(),
+                    _ => credentials::DEFAULT_ACCESS_KEY_ENV.to_string(),
+                })
+                .interact()
+                .context("Failed to get environment variable")?;
+            if env::var(&var).is_err() {
+                println!(
+                    "⚠️  {var} is not set in this shell. Make sure it is set wherever Wallp runs, including at login."
+                );
+            }
+            AccessKeySource::Env(var)
+        }
+        2 => {
+            let default_path = match current {
+                AccessKeySource::File(path) => path.clone(),
+                _ => AppData::get_config_dir()?.join("access_key"),
+            };
+            let path = PathBuf::from(
+                Input::<String>::new()
+                    .with_prompt("Key file")
+                    .default(default_path.display().to_string())
+                    .interact()
+                    .context("Failed to get key file path")?,
+            );
+            let key = Password::new()
+                .with_prompt("Unsplash Access Key (Enter to keep the current key)")
+                .allow_empty_password(true)
+                .interact()
+                .context("Failed to get access key")?;
+            let key = if key.trim().is_empty() && !path.exists() {
+                // Move a key previously stored in wallp.json into the file.
+                app_data.config.unsplash_access_key.clone()
+            } else {
+                key
+            };
+            if !key.trim().is_empty() {
+                credentials::write_key_file(&path, key.trim())?;
+                println!("✅ Access key saved to {}", path.display());
+            }
+            AccessKeySource::File(path)
+        }
+        3 => AccessKeySource::Command(
+            Input::new()
+                .with_prompt("Command that prints the key")
+                .default(match current {
+                    AccessKeySource::Command(command) => command.clone(),
+                    _ => credentials::DEFAULT_ACCESS_KEY_COMMAND.to_string(),
+                })
+                .interact()
+                .context("Failed to get command")?,
+        ),
+        _ => {
+            let key: String = Input::new()
+                .with_prompt("Unsplash Access Key")
+                .default(app_data.config.unsplash_access_key.clone())
+                .interact()
+                .context("Failed to get access key")?;
+            return Ok((AccessKeySource::Config, key));
+        }
+    };
+
+    Ok((source, String::new()))
+}
+
 /// Runs the interactive setup wizard.
 ///
 /// # Errors
@@ -310,11 +539,14 @@ pub fn setup_wizard() -> Result<()> {
     println!("📋 Configuration");
     println!("Get your API key at: https://unsplash.com/oauth/applications");
 
-    let access_key: String = Input::new()
-        .with_prompt("Unsplash Access Key")
-        .default(app_data.config.unsplash_access_key.clone())
-        .interact()
-        .context("Failed to get access key")?;
+    let (access_key_source, stored_key) = prompt_access_key(&app_data)?;
+    let mut key_config = app_data.config.clone();
+    key_config.access_key_source = access_key_source.clone();
+    key_config.unsplash_access_key.clone_from(&stored_key);
+    let access_key = credentials::access_key(&key_config).unwrap_or_else(|e| {
+        println!("⚠️  Could not read the access key: {e:#}");
+        String::new()
+    });
 
     // Parse interval with validation
     let interval = loop {
@@ -352,8 +584,16 @@ pub fn setup_wizard() -> Result<()> {
         .map(|(id, desc)| (id.clone(), format!("{desc} - {id}"), false))
         .collect();
 
-    for (id, desc) in &custom_collections {
-        all_items.push((id.clone(), format!("Custom: {desc} - {id}"), true));
+    for collection in &custom_collections {
+        all_items.push((
+            collection.id.clone(),
+            format!("Custom: {} - {}", collection.title, collection.id),
+            true,
+        ));
+    }
+
+    for (id, desc) in sources::selectable_entries(&app_data.config) {
+        all_items.push((id.clone(), format!("{desc} - {id}"), false));
     }
 
     let add_custom_index = all_items.len();
@@ -372,7 +612,7 @@ pub fn setup_wizard() -> Result<()> {
             if id.is_empty() {
                 false
             } else if *is_custom {
-                custom_collections.iter().any(|(cid, _)| cid == id)
+                custom_collections.iter().any(|c| c.id == *id)
             } else {
                 current_collections.contains(id)
             }
@@ -391,7 +631,7 @@ pub fn setup_wizard() -> Result<()> {
 
     // Handle "Add custom collection(s)" if selected
     let mut new_collections: Vec<String> = Vec::new();
-    let mut updated_custom_collections: Vec<(String, String)> = custom_collections;
+    let mut updated_custom_collections: Vec<CustomCollection> = custom_collections;
 
     for idx in &selections {
         if *idx == add_custom_index {
@@ -400,26 +640,61 @@ pub fn setup_wizard() -> Result<()> {
                 .interact()
                 .context("Failed to get collection IDs")?;
 
+            let rt = tokio::runtime::Runtime::new().context("Failed to create tokio runtime")?;
+            let client = UnsplashClient::new(&access_key, &app_data.config.network)?
+                .with_base_url(&app_data.config.unsplash_api_url());
+
             for id in ids_input
                 .split(',')
                 .map(str::trim)
                 .filter(|s| !s.is_empty())
             {
-                let desc: String = Input::new()
-                    .with_prompt(format!(
-                        "Description for {id} (optional, press Enter to skip):"
-                    ))
-                    .default(format!("Collection {id}"))
-                    .interact()
-                    .context("Failed to get description")?;
-
-                let final_desc = if desc.is_empty() || desc == format!("Collection {id}") {
-                    format!("Collection {id}")
-                } else {
-                    desc
+                let collection = match rt.block_on(manager::lookup_collection(&client, id)) {
+                    Ok(collection) if collection.unavailable => {
+                        println!("⚠️  Collection {id} was not found or is private.");
+                        let add_anyway = Confirm::new()
+                            .with_prompt("Add it anyway?")
+                            .default(false)
+                            .interact()
+                            .context("Failed to get confirmation")?;
+                        if !add_anyway {
+                            continue;
+                        }
+                        collection
+                    }
+                    Ok(collection) => {
+                        let photos = collection.total_photos.unwrap_or_default();
+                        println!("✅ Found \"{}\" ({photos} photos)", collection.title);
+                        if photos == 0 {
+                            println!("⚠️  This collection is empty.");
+                        }
+                        collection
+                    }
+                    Err(e) => {
+                        println!("⚠️  Could not verify collection {id}: {e}");
+                        let desc: String = Input::new()
+                            .with_prompt(format!(
+                                "Description for {id} (optional, press Enter to skip):"
+                            ))
+                            .default(format!("Collection {id}"))
+                            .interact()
+                            .context("Failed to get description")?;
+
+                        let title = if desc.is_empty() {
+                            format!("Collection {id}")
+                        } else {
+                            desc
+                        };
+                        CustomCollection {
+                            id: id.to_string(),
+                            title,
+                            ..Default::default()
+                        }
+                    }
                 };
 
-                updated_custom_collections.push((id.to_string(), final_desc));
+                updated_custom_collections.retain(|c| c.id != id);
+                updated_custom_collections.push(collection);
                 new_collections.push(id.to_string());
             }
         } else {
@@ -427,6 +702,57 @@ pub fn setup_wizard() -> Result<()> {
         }
     }
 
+    // Search query, topics and photographer (all optional)
+    println!();
+    println!("🔎 Search & Topics (optional)");
+    println!(
+        "A search query replaces the collections above. Browse topics at: https://unsplash.com/t"
+    );
+
+    let query = optional_text(
+        &Input::<String>::new()
+            .with_prompt("Search query (e.g., mountains fog)")
+            .default(app_data.config.query.clone().unwrap_or_default())
+            .allow_empty(true)
+            .interact()
+            .context("Failed to get search query")?,
+    );
+
+    let topics = split_comma_list(
+        &Input::<String>::new()
+            .with_prompt("Topics (comma-separated slugs, e.g., wallpapers,nature)")
+            .default(app_data.config.topics.join(","))
+            .allow_empty(true)
+            .interact()
+            .context("Failed to get topics")?,
+    );
+
+    let username = optional_text(
+        &Input::<String>::new()
+            .with_prompt("Only photos by Unsplash user")
+            .default(app_data.config.username.clone().unwrap_or_default())
+            .allow_empty(true)
+            .interact()
+            .context("Failed to get username")?,
+    );
+
+    let content_filter = if Confirm::new()
+        .with_prompt("Use Unsplash's strict content filter?")
+        .default(app_data.config.content_filter == ContentFilter::High)
+        .interact()
+        .context("Failed to get content filter")?
+    {
+        ContentFilter::High
+    } else {
+        ContentFilter::Low
+    };
+
+    if new_collections.is_empty() && topics.is_empty() && query.is_none() && username.is_none() {
+        println!(
+            "⚠️  No collections, topics or search query selected - wallp won't be able to fetch wallpapers."
+        );
+    }
+
     // Retention prompt
     let retention_days: Option<u64> = loop {
         let input: String = Input::new()
@@ -532,10 +858,15 @@ pub fn setup_wizard() -> Result<()> {
         }
 
         // Save configuration
-        app_data.config.unsplash_access_key = access_key;
+        app_data.config.unsplash_access_key.clone_from(&stored_key);
+        app_data.config.access_key_source = access_key_source.clone();
         app_data.config.interval_minutes = interval;
         app_data.config.collections = new_collections;
         app_data.config.custom_collections = updated_custom_collections;
+        app_data.config.query = query;
+        app_data.config.topics = topics;
+        app_data.config.username = username;
+        app_data.config.content_filter = content_filter;
         app_data.config.retention_days = retention_days;
         app_data.config.lockscreen_enabled = enable_lockscreen;
         app_data.config.lockscreen_collections = lockscreen_collections;
@@ -613,10 +944,15 @@ pub fn setup_wizard() -> Result<()> {
         let final_exe_path = final_exe_path.canonicalize().unwrap_or(final_exe_path);
 
         // Save configuration
-        app_data.config.unsplash_access_key = access_key;
+        app_data.config.unsplash_access_key.clone_from(&stored_key);
+        app_data.config.access_key_source = access_key_source.clone();
         app_data.config.interval_minutes = interval;
         app_data.config.collections = new_collections;
         app_data.config.custom_collections = updated_custom_collections;
+        app_data.config.query = query;
+        app_data.config.topics = topics;
+        app_data.config.username = username;
+        app_data.config.content_filter = content_filter;
         app_data.config.retention_days = retention_days;
         app_data.config.lockscreen_enabled = enable_lockscreen;
         app_data.config.lockscreen_collections = lockscreen_collections;
@@ -980,43 +1316,210 @@ fn start_background_process(exe_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Draw the thumbnail of `wallpaper`, making it first if needed.
+fn print_thumbnail(
+    rt: &tokio::runtime::Runtime,
+    wallpaper: &Wallpaper,
+    protocol: preview::Protocol,
+) -> Result<()> {
+    let data_dir = AppData::get_data_dir()?;
+    let source = data_dir.join("wallpapers").join(&wallpaper.filename);
+    let thumbnail = rt.block_on(preview::thumbnail(&source, &data_dir.join("thumbnails")))?;
+    print!(
+        "{}",
+        preview::render(&thumbnail, preview::LIST_COLUMNS, protocol)?
+    );
+    Ok(())
+}
+
+/// Print every wallpaper in history, most recent (0) first, for when the
+/// browser can't be shown.
+fn print_history(rt: &tokio::runtime::Runtime, data: &AppData, thumbs: bool) {
+    let protocol = preview::Protocol::resolve(data.config.preview_protocol);
+    for (i, w) in data.history.iter().rev().enumerate() {
+        if thumbs && let Err(e) = print_thumbnail(rt, w, protocol) {
+            tracing::warn!("Failed to draw a thumbnail of {}: {e:#}", w.filename);
+        }
+        println!(
+            "{i}: {} by {}",
+            w.title.as_deref().unwrap_or_default(),
+            w.author.as_deref().unwrap_or_default()
+        );
+    }
+}
+
+/// A setting's value as typed on the command line: text without quotes,
+/// anything else as JSON.
+fn setting_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn handle_config(action: Option<&ConfigAction>, json: bool) -> Result<()> {
+    let path = AppData::get_config_path()?;
+    match action {
+        Some(ConfigAction::Path) | None if json => {
+            output::print_json(&output::PathReport { path })?;
+        }
+        Some(ConfigAction::Edit) | None => {
+            if json {
+                anyhow::bail!("Editing the config file can't be done with --json");
+            }
+            open::that(path)?;
+        }
+        Some(ConfigAction::Path) => println!("{}", path.display()),
+        Some(ConfigAction::Get { key, reveal }) => {
+            let mut value = settings::get(&AppData::load()?.config, key)?;
+            if !reveal {
+                settings::redact(key, &mut value);
+            }
+            if json {
+                output::print_json(&output::ConfigValueReport {
+                    key: key.clone(),
+                    value,
+                })?;
+            } else {
+                println!("{}", setting_text(&value));
+            }
+        }
+        Some(ConfigAction::Set { key, value, reveal }) => {
+            let mut data = AppData::load()?;
+            let mut value = settings::set(&mut data.config, key, value)?;
+            data.save()?;
+            if !reveal {
+                settings::redact(key, &mut value);
+            }
+            if json {
+                output::print_json(&output::ConfigValueReport {
+                    key: key.clone(),
+                    value,
+                })?;
+            } else {
+                println!("✅ {key} = {}", setting_text(&value));
+            }
+        }
+        Some(ConfigAction::Unset { key }) => {
+            let mut data = AppData::load()?;
+            let value = settings::unset(&mut data.config, key)?;
+            data.save()?;
+            if json {
+                output::print_json(&output::ConfigValueReport {
+                    key: key.clone(),
+                    value,
+                })?;
+            } else {
+                println!("✅ {key} reset to {}", setting_text(&value));
+            }
+        }
+        Some(ConfigAction::List) => {
+            let entries = settings::list(&AppData::load()?.config)?;
+            if json {
+                output::print_json(&output::ConfigListReport {
+                    settings: entries.into_iter().collect(),
+                })?;
+            } else {
+                for (key, value) in entries {
+                    println!("{key} = {value}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print the wallpaper on screen after `action` as JSON.
+fn print_action(action: &'static str) -> Result<()> {
+    let data = AppData::load()?;
+    output::print_json(&output::ActionReport {
+        action,
+        wallpaper: output::WallpaperReport::current(&data)?,
+    })
+}
+
 /// Handle the parsed CLI command.
 ///
 /// # Errors
 ///
 /// Returns an error if the command fails to execute or if the tokio runtime fails to create.
 #[allow(clippy::too_many_lines)]
-pub fn handle_command(cmd: &Commands) -> Result<()> {
+pub fn handle_command(cmd: &Commands, format: Format) -> Result<()> {
     let rt = tokio::runtime::Runtime::new().context("Failed to create tokio runtime")?;
+    let json = format == Format::Json;
 
     match cmd {
+        Commands::Setup | Commands::Uninstall if json => {
+            anyhow::bail!("This command asks questions and can't be used with --json");
+        }
         Commands::Setup => {
             setup_wizard()?;
         }
         Commands::New => {
             rt.block_on(manager::new())?;
-            println!("✨ New wallpaper set.");
+            if json {
+                print_action("new")?;
+            } else {
+                println!("✨ New wallpaper set.");
+            }
         }
         Commands::Next => {
             rt.block_on(manager::next())?;
-            println!("⏩ Next wallpaper set.");
+            if json {
+                print_action("next")?;
+            } else {
+                println!("⏩ Next wallpaper set.");
+            }
         }
         Commands::Prev => {
             rt.block_on(manager::prev())?;
-            println!("⏪ Previous wallpaper set.");
+            if json {
+                print_action("prev")?;
+            } else {
+                println!("⏪ Previous wallpaper set.");
+            }
+        }
+        Commands::Status if json => {
+            let data = AppData::load()?;
+            let state = if data.state.is_running {
+                "running"
+            } else if data.state.pause_ends_at().is_some() {
+                "paused"
+            } else {
+                "stopped"
+            };
+            output::print_json(&output::StatusReport {
+                state,
+                paused_until: data.state.pause_ends_at().map(|at| at.to_rfc3339()),
+                next_run_at: data.state.next_run_at.clone(),
+                last_run_at: data.state.last_run_at.clone(),
+                prefetched: data.queue.len(),
+                prefetch_count: data.config.prefetch_count,
+                current: output::WallpaperReport::current(&data)?,
+            })?;
         }
         Commands::Status => {
             let data = AppData::load()?;
-            println!(
-                "Status: {}",
-                if data.state.is_running {
-                    "Running"
-                } else {
-                    "Stopped"
-                }
-            );
+            if data.state.is_running {
+                println!("Status: Running");
+            } else if let Some(until) = data.state.pause_ends_at() {
+                println!(
+                    "Status: Paused until {} ({} remaining)",
+                    format_datetime(&until.with_timezone(&chrono::Local).to_rfc3339()),
+                    format_remaining(until - chrono::Utc::now())
+                );
+            } else {
+                println!("Status: Stopped");
+            }
             println!("Next Run: {}", format_datetime(&data.state.next_run_at));
             println!("Last Run: {}", format_datetime(&data.state.last_run_at));
+            if data.config.prefetch_count > 0 {
+                println!(
+                    "Prefetched: {}/{}",
+                    data.queue.len(),
+                    data.config.prefetch_count
+                );
+            }
             if let Some(w) = manager::get_current_wallpaper()? {
                 let title = w.title.unwrap_or_default();
                 let author = w.author.unwrap_or_default();
@@ -1025,204 +1528,274 @@ pub fn handle_command(cmd: &Commands) -> Result<()> {
                 }
             }
         }
+        Commands::Pause { duration, until } => {
+            let resume_at = match (duration, until) {
+                (Some(_), Some(_)) => anyhow::bail!("Use either a duration or --until, not both"),
+                (Some(d), None) => {
+                    let minutes = parse_interval(d).map_err(|e| anyhow::anyhow!(e))?;
+                    let resume_at = i64::try_from(minutes)
+                        .ok()
+                        .and_then(chrono::TimeDelta::try_minutes)
+                        .and_then(|pause| chrono::Utc::now().checked_add_signed(pause))
+                        .context("Pause is too long")?;
+                    Some(resume_at)
+                }
+                (None, Some(t)) => Some(
+                    parse_until(t, chrono::Local::now())
+                        .map_err(|e| anyhow::anyhow!(e))?
+                        .with_timezone(&chrono::Utc),
+                ),
+                (None, None) => None,
+            };
+            scheduler::pause(resume_at)?;
+            if json {
+                return output::print_json(&output::SchedulerReport {
+                    action: "pause",
+                    running: false,
+                    paused_until: resume_at.map(|at| at.to_rfc3339()),
+                });
+            }
+            match resume_at {
+                Some(at) => println!(
+                    "⏸️ Scheduler paused until {}.",
+                    format_datetime(&at.with_timezone(&chrono::Local).to_rfc3339())
+                ),
+                None => println!("⏸️ Scheduler paused. Run 'wallp resume' to continue."),
+            }
+        }
+        Commands::Resume => {
+            scheduler::resume()?;
+            if json {
+                return output::print_json(&output::SchedulerReport {
+                    action: "resume",
+                    running: true,
+                    paused_until: None,
+                });
+            }
+            println!("▶️ Scheduler resumed.");
+        }
+        Commands::Info if json => {
+            print_action("info")?;
+        }
         Commands::Info => {
             if let Some(w) = manager::get_current_wallpaper()? {
+                let tone = w.tone();
                 println!("Title: {}", w.title.unwrap_or_default());
                 println!("Author: {}", w.author.unwrap_or_default());
+                if let Some(source) = w.source {
+                    println!("Source: {source}");
+                }
+                if let Some(copyright) = w.copyright {
+                    println!("Copyright: {copyright}");
+                }
+                if let (Some(tone), Some(luminance)) = (tone, w.luminance) {
+                    let contrast = w.contrast.unwrap_or_default();
+                    println!(
+                        "Brightness: {} ({:.0}%, contrast {:.0}%)",
+                        tone.as_str(),
+                        luminance * 100.0,
+                        contrast * 100.0
+                    );
+                }
+                if let Some(explanation) = w.explanation {
+                    println!();
+                    println!("{explanation}");
+                }
                 if let Some(url) = w.url {
                     println!();
                     println!("View: {url}");
                 }
+                if let Some(author_url) = w.author_url {
+                    println!("Photographer: {author_url}");
+                }
             } else {
                 println!("No wallpaper in history.");
             }
         }
         Commands::Set { index } => {
             let data = AppData::load()?;
-            let history_len = data.history.len();
-
-            if history_len == 0 {
+            if data.history.is_empty() {
+                if json {
+                    anyhow::bail!("No wallpaper in history");
+                }
                 println!("No wallpaper in history.");
                 return Ok(());
             }
 
             if let Some(idx) = index {
                 rt.block_on(manager::set_by_index(*idx))?;
-                println!("✅ Wallpaper set to index {idx}");
-            } else {
-                println!("Select a wallpaper (most recent is 0):");
-                println!();
-
-                let mut shown = 0;
-                let mut total_shown = 0;
-                let max_initial = 5;
-                let max_more = 10;
-
-                loop {
-                    let data = AppData::load()?;
-                    let history_len = data.history.len();
-
-                    if shown >= history_len {
-                        break;
-                    }
-
-                    let to_show = if shown == 0 { max_initial } else { max_more };
-                    let items: Vec<String> = data
-                        .history
-                        .iter()
-                        .rev()
-                        .skip(shown)
-                        .take(to_show)
-                        .enumerate()
-                        .map(|(i, w)| {
-                            let idx = shown + i;
-                            format!(
-                                "{}: {} by {}",
-                                idx,
-                                w.title.clone().unwrap_or_default(),
-                                w.author.clone().unwrap_or_default()
-                            )
-                        })
-                        .collect();
-
-                    for item in &items {
-                        println!("{item}");
-                    }
-
-                    shown += items.len();
-                    total_shown += items.len();
-
-                    if shown >= history_len {
-                        break;
-                    }
-
-                    let prompt = if total_shown == max_initial {
-                        "Enter number (or 'm' for more):"
-                    } else {
-                        "Enter number (or 'm' for more, 'q' to quit):"
-                    };
-
-                    let input: String = Input::new()
-                        .with_prompt(prompt)
-                        .interact()
-                        .context("Failed to get input")?;
-
-                    if input.eq_ignore_ascii_case("q") {
-                        println!("Cancelled.");
-                        return Ok(());
-                    }
-
-                    if input.eq_ignore_ascii_case("m") {
-                        continue;
-                    }
-
-                    match input.trim().parse::<usize>() {
-                        Ok(idx) => {
-                            rt.block_on(manager::set_by_index(idx))?;
-                            println!("✅ Wallpaper set to index {idx}");
-                            break;
-                        }
-                        Err(_) => {
-                            println!(
-                                "Invalid input. Enter a number, 'm' for more, or 'q' to quit."
-                            );
-                        }
-                    }
+                if json {
+                    print_action("set")?;
+                } else {
+                    println!("✅ Wallpaper set to index {idx}");
                 }
+            } else if json {
+                anyhow::bail!("Pass the number of the wallpaper to set with --json");
+            } else if std::io::stdout().is_terminal() {
+                browser::run(&rt)?;
+            } else {
+                print_history(&rt, &data, false);
+                anyhow::bail!("Run 'wallp set <number>' to pick one of these wallpapers");
             }
         }
         Commands::Folder => {
             let path = AppData::get_data_dir()?.join("wallpapers");
-            open::that(path)?;
+            if json {
+                output::print_json(&output::PathReport { path })?;
+            } else {
+                open::that(path)?;
+            }
         }
-        Commands::Config => {
-            let path = AppData::get_config_path()?;
-            open::that(path)?;
+        Commands::Config { action } => handle_config(action.as_ref(), json)?,
+        Commands::Dedupe { remove } => {
+            let groups = rt.block_on(manager::dedupe(*remove))?;
+            if json {
+                return output::print_json(&output::DedupeReport {
+                    removed: *remove,
+                    groups: groups
+                        .into_iter()
+                        .map(|group| output::DuplicateGroupReport {
+                            kept: group.kept.filename,
+                            copies: group.copies.into_iter().map(|w| w.filename).collect(),
+                        })
+                        .collect(),
+                });
+            }
+            for group in &groups {
+                println!("{} (kept)", group.kept.filename);
+                for copy in &group.copies {
+                    println!("  ≈ {}", copy.filename);
+                }
+            }
+            let copies: usize = groups.iter().map(|g| g.copies.len()).sum();
+            if copies == 0 {
+                println!("✅ No duplicate wallpapers found.");
+            } else if *remove {
+                println!("🗑️ Removed {copies} duplicate wallpaper(s).");
+            } else {
+                println!(
+                    "Found {copies} duplicate wallpaper(s). Run 'wallp dedupe --remove' to delete them."
+                );
+            }
         }
-        Commands::List => {
+        Commands::Preview { .. } if json => {
+            anyhow::bail!("Previews are drawn in the terminal and can't be printed as JSON");
+        }
+        Commands::Preview { index } => {
             let data = AppData::load()?;
-            let history_len = data.history.len();
-            let max_initial = 5;
-            let max_more = 10;
-
-            let mut shown = 0;
-            loop {
-                let to_show = if shown == 0 { max_initial } else { max_more };
-                let items: Vec<String> = data
+            let wallpaper = match index {
+                Some(idx) => data
                     .history
                     .iter()
                     .rev()
-                    .skip(shown)
-                    .take(to_show)
-                    .enumerate()
-                    .map(|(i, w)| {
-                        let idx = shown + i;
-                        format!(
-                            "{}: {} by {}",
-                            idx,
-                            w.title.clone().unwrap_or_default(),
-                            w.author.clone().unwrap_or_default()
-                        )
-                    })
-                    .collect();
-
-                for item in &items {
-                    println!("{item}");
-                }
-
-                shown += items.len();
-
-                if shown >= history_len {
-                    break;
-                }
-
-                let prompt = "'m' for more, 'q' to quit:";
-
-                print!("Enter {prompt} ");
-                let input: String = Input::new().interact().context("Failed to get input")?;
-
-                if input.eq_ignore_ascii_case("q") {
-                    break;
-                }
-
-                if !input.eq_ignore_ascii_case("m") {
-                    break;
-                }
+                    .nth(*idx)
+                    .with_context(|| format!("No wallpaper number {idx} in history"))?,
+                None => data
+                    .history
+                    .get(data.state.current_history_index)
+                    .context("No wallpaper in history")?,
+            };
+            let path = AppData::get_data_dir()?
+                .join("wallpapers")
+                .join(&wallpaper.filename);
+            let (columns, _) = preview::terminal_size();
+            let protocol = preview::Protocol::resolve(data.config.preview_protocol);
+            print!(
+                "{}",
+                preview::render(&path, columns.saturating_sub(1).min(120), protocol)?
+            );
+            println!(
+                "{} by {}",
+                wallpaper.title.as_deref().unwrap_or_default(),
+                wallpaper.author.as_deref().unwrap_or_default()
+            );
+        }
+        Commands::List { .. } if json => {
+            output::print_json(&output::ListReport {
+                wallpapers: output::WallpaperReport::all(&AppData::load()?)?,
+            })?;
+        }
+        Commands::List { thumbs } => {
+            if !*thumbs && std::io::stdout().is_terminal() {
+                browser::run(&rt)?;
+            } else {
+                print_history(&rt, &AppData::load()?, *thumbs);
             }
         }
         Commands::Settings => {
+            let access_key = credentials::AccessKey::default();
+            if let Err(e) = rt.block_on(manager::refresh_custom_collections(&access_key)) {
+                eprintln!("Warning: Failed to refresh custom collections: {e}");
+            }
+
             let data = AppData::load()?;
             let config = &data.config;
 
             // Format API key (masked)
-            let api_key = if config.unsplash_access_key.is_empty() {
-                "Not set".to_string()
+            let api_key = if config.has_access_key() {
+                match access_key.get(config) {
+                    Ok(key) => settings::mask(key),
+                    Err(e) => format!("Unavailable ({e:#})"),
+                }
             } else {
-                format!(
-                    "****{}",
-                    &config.unsplash_access_key[config.unsplash_access_key.len() - 4..]
-                )
+                "Not set".to_string()
             };
 
             // Build collections list with descriptions
             let default_collections = get_default_collections_info();
-            let mut collection_lines = Vec::new();
+            let mut collections = Vec::new();
 
             for col_id in &config.collections {
                 let desc = default_collections
                     .iter()
                     .find(|(id, _)| id == col_id)
                     .map(|(_, d)| d.clone())
+                    .or_else(|| sources::describe_entry(config, col_id))
                     .or_else(|| {
                         config
                             .custom_collections
                             .iter()
-                            .find(|(id, _)| id == col_id)
-                            .map(|(_, d)| d.clone())
+                            .find(|c| &c.id == col_id)
+                            .map(|c| match c.total_photos {
+                                Some(n) => format!("{}, {n} photos", c.title),
+                                None => c.title.clone(),
+                            })
                     })
                     .unwrap_or_else(|| "Unknown".to_string());
 
-                collection_lines.push(format!("  - {desc} ({col_id})"));
+                collections.push(output::CollectionReport {
+                    id: col_id.clone(),
+                    description: desc,
+                    problem: config
+                        .custom_collections
+                        .iter()
+                        .find(|c| &c.id == col_id)
+                        .and_then(CustomCollection::problem),
+                });
+            }
+
+            if json {
+                return output::print_json(&output::SettingsReport {
+                    api_key: config.has_access_key().then_some(api_key),
+                    api_key_source: credentials::describe(&config.access_key_source),
+                    wallhaven_api_key_set: config.wallhaven.api_key.is_some(),
+                    collections,
+                    query: config.query.clone(),
+                    topics: config.topics.clone(),
+                    username: config.username.clone(),
+                    content_filter: config.content_filter.as_str(),
+                    proxy: config.network.proxy.as_deref().map(http::redact_url),
+                    no_proxy: config.network.no_proxy.clone(),
+                    ca_bundle: config.network.ca_bundle.clone(),
+                    interval_minutes: config.interval_minutes,
+                    retention_days: config.retention_days,
+                    autostart: is_autostart_enabled(),
+                    in_path: which::which("wallp").is_ok(),
+                    lockscreen: output::LockscreenReport {
+                        enabled: config.lockscreen_enabled,
+                        collections: config.lockscreen_collections.clone(),
+                    },
+                });
             }
 
             // Format interval
@@ -1251,9 +1824,45 @@ pub fn handle_command(cmd: &Commands) -> Result<()> {
 
             println!();
             println!("API Key: {api_key}");
+            println!(
+                "API Key Source: {}",
+                credentials::describe(&config.access_key_source)
+            );
+            if config.wallhaven.api_key.is_some() {
+                println!("Wallhaven API Key: Set");
+            }
             println!("Collections:");
-            for line in collection_lines {
-                println!("{line}");
+            for collection in &collections {
+                println!("  - {} ({})", collection.description, collection.id);
+            }
+            for collection in &config.custom_collections {
+                if let Some(problem) = collection.problem()
+                    && config.collections.contains(&collection.id)
+                {
+                    println!(
+                        "⚠️  Collection \"{}\" ({}) {problem}",
+                        collection.title, collection.id
+                    );
+                }
+            }
+            if let Some(query) = &config.query {
+                println!("Search Query: {query} (overrides collections and topics)");
+            }
+            if !config.topics.is_empty() {
+                println!("Topics: {}", config.topics.join(", "));
+            }
+            if let Some(username) = &config.username {
+                println!("Photographer: {username}");
+            }
+            println!("Content Filter: {}", config.content_filter.as_str());
+            if let Some(proxy) = &config.network.proxy {
+                println!("Proxy: {}", http::redact_url(proxy));
+                if !config.network.no_proxy.is_empty() {
+                    println!("No Proxy: {}", config.network.no_proxy.join(", "));
+                }
+            }
+            if let Some(ca_bundle) = &config.network.ca_bundle {
+                println!("CA Bundle: {}", ca_bundle.display());
             }
             println!("Update Interval: {interval_str}");
             println!("Retention: {retention_str}");
@@ -1290,7 +1899,25 @@ pub fn handle_command(cmd: &Commands) -> Result<()> {
                     .map(String::from)
                     .collect();
                 data.save()?;
-                println!("✅ LockScreen collections updated.");
+                if !json {
+                    println!("✅ LockScreen collections updated.");
+                }
+            }
+
+            if json {
+                match action.as_deref() {
+                    Some("on" | "enable") => data.config.lockscreen_enabled = true,
+                    Some("off" | "disable") => data.config.lockscreen_enabled = false,
+                    Some("status") | None => {}
+                    Some(other) => {
+                        anyhow::bail!("Unknown action: {other}. Use 'on', 'off', or 'status'.")
+                    }
+                }
+                data.save()?;
+                return output::print_json(&output::LockscreenReport {
+                    enabled: data.config.lockscreen_enabled,
+                    collections: data.config.lockscreen_collections,
+                });
             }
 
             match action.as_deref() {
@@ -1772,6 +2399,7 @@ fn remove_from_path_unix() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::Timelike;
 
     #[test]
     fn test_get_shell_files_bash() {
@@ -1925,43 +2553,68 @@ export EDITOR=vim"#;
     }
 
     #[test]
-    fn test_parse_interval_valid_minutes() -> anyhow::Result<()> {
-        assert_eq!(parse_interval("30m").map_err(|e| anyhow::anyhow!(e))?, 30);
-        Ok(())
+    fn test_format_interval_for_display() {
+        assert_eq!(format_interval_for_display(45), "45m");
+        assert_eq!(format_interval_for_display(60), "1h");
+        assert_eq!(format_interval_for_display(90), "1h");
+        assert_eq!(format_interval_for_display(1440), "1d");
+        assert_eq!(format_interval_for_display(2880), "2d");
     }
 
     #[test]
-    fn test_parse_interval_valid_hours() -> anyhow::Result<()> {
-        assert_eq!(parse_interval("2h").map_err(|e| anyhow::anyhow!(e))?, 120);
-        Ok(())
+    fn test_format_remaining() {
+        assert_eq!(format_remaining(chrono::Duration::seconds(20)), "<1m");
+        assert_eq!(format_remaining(chrono::Duration::minutes(45)), "45m");
+        assert_eq!(format_remaining(chrono::Duration::minutes(83)), "1h 23m");
+        assert_eq!(format_remaining(chrono::Duration::hours(50)), "2d 2h");
     }
 
     #[test]
-    fn test_parse_interval_valid_days() -> anyhow::Result<()> {
-        assert_eq!(parse_interval("1d").map_err(|e| anyhow::anyhow!(e))?, 1440);
+    fn test_parse_until_later_today() -> anyhow::Result<()> {
+        let now = chrono::Local::now()
+            .with_hour(9)
+            .and_then(|t| t.with_minute(0))
+            .context("invalid time")?;
+        let until = parse_until("18:00", now).map_err(|e| anyhow::anyhow!(e))?;
+        assert_eq!(until.date_naive(), now.date_naive());
+        assert_eq!(until.hour(), 18);
         Ok(())
     }
 
     #[test]
-    fn test_parse_interval_default() -> anyhow::Result<()> {
-        assert_eq!(parse_interval("45").map_err(|e| anyhow::anyhow!(e))?, 45);
+    fn test_parse_until_rolls_over_to_tomorrow() -> anyhow::Result<()> {
+        let now = chrono::Local::now()
+            .with_hour(20)
+            .and_then(|t| t.with_minute(0))
+            .context("invalid time")?;
+        let until = parse_until("18:00", now).map_err(|e| anyhow::anyhow!(e))?;
+        assert_eq!(Some(until.date_naive()), now.date_naive().succ_opt());
         Ok(())
     }
 
     #[test]
-    fn test_parse_interval_invalid() {
-        assert!(parse_interval("").is_err());
-        assert!(parse_interval("abc").is_err());
-        assert!(parse_interval("1x").is_err());
+    fn test_parse_until_invalid() {
+        let now = chrono::Local::now();
+        assert!(parse_until("6pm", now).is_err());
+        assert!(parse_until("25:00", now).is_err());
     }
 
     #[test]
-    fn test_format_interval_for_display() {
-        assert_eq!(format_interval_for_display(45), "45m");
-        assert_eq!(format_interval_for_display(60), "1h");
-        assert_eq!(format_interval_for_display(90), "1h");
-        assert_eq!(format_interval_for_display(1440), "1d");
-        assert_eq!(format_interval_for_display(2880), "2d");
+    fn test_split_comma_list() {
+        assert_eq!(
+            split_comma_list("wallpapers, nature ,,"),
+            vec!["wallpapers", "nature"]
+        );
+        assert!(split_comma_list("").is_empty());
+    }
+
+    #[test]
+    fn test_optional_text() {
+        assert_eq!(optional_text("  "), None);
+        assert_eq!(
+            optional_text(" mountains fog "),
+            Some("mountains fog".to_string())
+        );
     }
 
     #[test]
@@ -0,0 +1,247 @@
+use crate::config::{AccessKeySource, Config};
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Environment variable the setup wizard suggests for the access key.
+pub const DEFAULT_ACCESS_KEY_ENV: &str = "UNSPLASH_ACCESS_KEY";
+
+/// Command the setup wizard suggests for reading the access key.
+pub const DEFAULT_ACCESS_KEY_COMMAND: &str = "pass show unsplash";
+
+/// Read the Unsplash access key from wherever `config` says it's kept. Only
+/// the first line is used, so `pass show` output works as-is.
+///
+/// # Errors
+///
+/// Returns an error if the variable is unset, the file is unreadable or
+/// accessible to other users, the command fails, or the key is empty.
+pub fn access_key(config: &Config) -> Result<String> {
+    let raw = match &config.access_key_source {
+        AccessKeySource::Config => config.unsplash_access_key.clone(),
+        AccessKeySource::Env(var) => {
+            std::env::var(var).with_context(|| format!("Environment variable {var} is not set"))?
+        }
+        AccessKeySource::File(path) => read_key_file(path)?,
+        AccessKeySource::Command(command) => run_key_command(command)?,
+    };
+
+    let key = raw.lines().next().unwrap_or_default().trim();
+    if key.is_empty() {
+        anyhow::bail!("Unsplash Access Key is missing. Run 'wallp setup' to configure.");
+    }
+    Ok(key.to_string())
+}
+
+/// The Unsplash access key, read at most once however often it's needed, as
+/// that may mean running a command. Make one per operation, so a key changed
+/// in the meantime is picked up next time.
+#[derive(Debug, Default)]
+pub struct AccessKey(std::sync::OnceLock<String>);
+
+impl AccessKey {
+    /// The key, read from wherever `config` says on first use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key can't be read; see [`access_key`].
+    pub fn get(&self, config: &Config) -> Result<&str> {
+        if let Some(key) = self.0.get() {
+            return Ok(key);
+        }
+        let key = access_key(config)?;
+        Ok(self.0.get_or_init(|| key))
+    }
+}
+
+/// A short description of `source` for `wallp settings`.
+#[must_use]
+pub fn describe(source: &AccessKeySource) -> String {
+    match source {
+        AccessKeySource::Config => "wallp.json".to_string(),
+        AccessKeySource::Env(var) => format!("environment variable {var}"),
+        AccessKeySource::File(path) => format!("file {}", path.display()),
+        AccessKeySource::Command(command) => format!("command `{command}`"),
+    }
+}
+
+/// Save `key` to `path`, readable and writable only by the current user.
+///
+/// # Errors
+///
+/// Returns an error if the file or its directory can't be written.
+pub fn write_key_file(path: &Path, key: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("Failed to create key file directory")?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .with_context(|| format!("Failed to create key file {}", path.display()))?;
+        // `mode` only applies to new files.
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .context("Failed to restrict key file permissions")?;
+        writeln!(file, "{key}").context("Failed to write key file")?;
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::write(path, format!("{key}\n"))
+            .with_context(|| format!("Failed to write key file {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+fn read_key_file(path: &Path) -> Result<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = std::fs::metadata(path)
+            .with_context(|| format!("Failed to read key file {}", path.display()))?
+            .permissions()
+            .mode();
+        if mode & 0o077 != 0 {
+            anyhow::bail!(
+                "Key file {} is accessible by other users; run `chmod 600 {}`",
+                path.display(),
+                path.display()
+            );
+        }
+    }
+
+    std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read key file {}", path.display()))
+}
+
+fn run_key_command(command: &str) -> Result<String> {
+    #[cfg(target_os = "windows")]
+    let output = std::process::Command::new("cmd")
+        .args(["/C", command])
+        .output();
+    #[cfg(not(target_os = "windows"))]
+    let output = std::process::Command::new("sh")
+        .args(["-c", command])
+        .output();
+
+    let output = output.with_context(|| format!("Failed to run `{command}`"))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`{command}` failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn config_with(source: AccessKeySource) -> Config {
+        Config {
+            access_key_source: source,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_access_key_from_config() -> anyhow::Result<()> {
+        let mut config = Config::default();
+        assert!(access_key(&config).is_err());
+
+        config.unsplash_access_key = "  abc123  ".to_string();
+        assert_eq!(access_key(&config)?, "abc123");
+        Ok(())
+    }
+
+    #[test]
+    fn test_access_key_from_unset_env_var() {
+        let config = config_with(AccessKeySource::Env(
+            "WALLP_TEST_UNSET_ACCESS_KEY_VAR".to_string(),
+        ));
+        let error = access_key(&config).expect_err("variable is unset");
+        assert!(error.to_string().contains("is not set"));
+    }
+
+    #[test]
+    fn test_access_key_from_file() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("keys").join("unsplash");
+        write_key_file(&path, "file_key")?;
+
+        let config = config_with(AccessKeySource::File(path.clone()));
+        assert_eq!(access_key(&config)?, "file_key");
+
+        // An operation keeps the key it read first.
+        let key = AccessKey::default();
+        assert_eq!(key.get(&config)?, "file_key");
+        write_key_file(&path, "new_key")?;
+        assert_eq!(key.get(&config)?, "file_key");
+        assert_eq!(AccessKey::default().get(&config)?, "new_key");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_access_key_file_must_be_private() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("unsplash");
+        std::fs::write(&path, "file_key\n")?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+
+        let config = config_with(AccessKeySource::File(path.clone()));
+        let error = access_key(&config).expect_err("file is world-readable");
+        assert!(error.to_string().contains("chmod 600"));
+
+        // Rewriting the key tightens the permissions again.
+        write_key_file(&path, "file_key")?;
+        assert_eq!(
+            std::fs::metadata(&path)?.permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(access_key(&config)?, "file_key");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_access_key_from_command() -> anyhow::Result<()> {
+        let config = config_with(AccessKeySource::Command(
+            "printf 'cmd_key\\nurl: https://unsplash.com\\n'".to_string(),
+        ));
+        assert_eq!(access_key(&config)?, "cmd_key");
+
+        let failing = config_with(AccessKeySource::Command(
+            "echo oops >&2; exit 3".to_string(),
+        ));
+        let error = access_key(&failing).expect_err("command fails");
+        assert!(error.to_string().contains("oops"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe(&AccessKeySource::Config), "wallp.json");
+        assert_eq!(
+            describe(&AccessKeySource::Env("UNSPLASH_ACCESS_KEY".to_string())),
+            "environment variable UNSPLASH_ACCESS_KEY"
+        );
+        assert_eq!(
+            describe(&AccessKeySource::Command("pass show unsplash".to_string())),
+            "command `pass show unsplash`"
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Image effects applied to copies of the wallpaper, cached per effects chain
+//! so going back and forth through history doesn't redo the work.
+
+use crate::config::Effect;
+use crate::overlay::parse_color;
+use crate::sources::short_hash;
+use anyhow::{Context, Result};
+use image::{Rgb, RgbImage};
+use std::path::{Path, PathBuf};
+
+/// Run `chain` over `image`, in order.
+///
+/// # Errors
+///
+/// Returns an error if a tint color is invalid.
+pub fn apply(mut image: RgbImage, chain: &[Effect]) -> Result<RgbImage> {
+    for effect in chain {
+        image = match effect {
+            Effect::Blur(sigma) if *sigma > 0.0 => image::imageops::fast_blur(&image, *sigma),
+            Effect::Blur(_) => image,
+            Effect::Brightness(factor) => map_pixels(image, |rgb| rgb.map(|c| c * factor.max(0.0))),
+            Effect::Desaturate(amount) => {
+                let amount = amount.clamp(0.0, 1.0);
+                map_pixels(image, |[r, g, b]| {
+                    let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+                    [r, g, b].map(|c| c + (luma - c) * amount)
+                })
+            }
+            Effect::Vignette(strength) => vignette(image, strength.clamp(0.0, 1.0)),
+            Effect::Tint { color, amount } => {
+                let tint = parse_color(color)?;
+                let tint = [tint[0], tint[1], tint[2]].map(f32::from);
+                let amount = amount.clamp(0.0, 1.0);
+                map_pixels(image, |rgb| {
+                    let mut out = rgb;
+                    for (c, t) in out.iter_mut().zip(tint) {
+                        *c += (t - *c) * amount;
+                    }
+                    out
+                })
+            }
+        };
+    }
+    Ok(image)
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn map_pixels(mut image: RgbImage, f: impl Fn([f32; 3]) -> [f32; 3]) -> RgbImage {
+    for pixel in image.pixels_mut() {
+        let out = f(pixel.0.map(f32::from));
+        *pixel = Rgb(out.map(|c| c.round().clamp(0.0, 255.0) as u8));
+    }
+    image
+}
+
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn vignette(mut image: RgbImage, strength: f32) -> RgbImage {
+    let (cx, cy) = (image.width() as f32 / 2.0, image.height() as f32 / 2.0);
+    let corner = cx.hypot(cy).max(1.0);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        // 0 in the middle, 1 in the corners; the falloff starts halfway out.
+        let distance = (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy) / corner;
+        let t = ((distance - 0.5) / 0.5).clamp(0.0, 1.0);
+        let factor = 1.0 - strength * t * t * (3.0 - 2.0 * t);
+        *pixel = Rgb(pixel
+            .0
+            .map(|c| (f32::from(c) * factor).round().clamp(0.0, 255.0) as u8));
+    }
+    image
+}
+
+/// The image at `source` with `chain` applied, from `cache_dir` when it has
+/// been processed with this chain before. Returns `source` itself for an empty
+/// chain.
+///
+/// # Errors
+///
+/// Returns an error if the image can't be read, an effect is invalid, or the
+/// result can't be saved.
+pub async fn processed(chain: &[Effect], source: &Path, cache_dir: &Path) -> Result<PathBuf> {
+    if chain.is_empty() {
+        return Ok(source.to_path_buf());
+    }
+
+    let stem = source
+        .file_stem()
+        .context("Wallpaper path has no file name")?
+        .to_string_lossy();
+    let key = short_hash(&serde_json::to_string(chain)?);
+    let dest = cache_dir.join(format!("{stem}-{key}.jpg"));
+    if dest.exists() {
+        return Ok(dest);
+    }
+
+    let chain = chain.to_vec();
+    let source = source.to_path_buf();
+    let cache_dir = cache_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<PathBuf> {
+        let image = image::open(&source)
+            .with_context(|| format!("Failed to open {}", source.display()))?
+            .into_rgb8();
+        let image = apply(image, &chain)?;
+
+        std::fs::create_dir_all(&cache_dir).context("Failed to create effects cache")?;
+        crate::http::write_jpeg(&image, &dest, 92).context("Failed to save processed wallpaper")?;
+        Ok(dest)
+    })
+    .await
+    .context("Effects task failed")?
+}
+
+/// Remove cached copies of wallpapers that aren't in `filenames` any more,
+/// from this cache or any other named the same way (like the thumbnails).
+/// Returns how many files were removed.
+#[must_use]
+pub fn prune(cache_dir: &Path, filenames: &[&str]) -> usize {
+    let Ok(entries) = std::fs::read_dir(cache_dir) else {
+        return 0;
+    };
+    let stems: Vec<&str> = filenames
+        .iter()
+        .filter_map(|name| Path::new(name).file_stem()?.to_str())
+        .collect();
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        // Cached copies are named `<stem>-<chain hash or other suffix>.jpg`.
+        let source_stem = name
+            .rsplit_once('-')
+            .map_or(name.as_ref(), |(stem, _)| stem);
+        if !stems.contains(&source_stem) && std::fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(value: u8) -> RgbImage {
+        RgbImage::from_pixel(40, 20, Rgb([value, value, value]))
+    }
+
+    #[test]
+    fn test_color_effects() -> anyhow::Result<()> {
+        let image = RgbImage::from_pixel(4, 4, Rgb([200, 100, 0]));
+
+        let dimmed = apply(image.clone(), &[Effect::Brightness(0.5)])?;
+        assert_eq!(*dimmed.get_pixel(0, 0), Rgb([100, 50, 0]));
+
+        let grayscale = apply(image.clone(), &[Effect::Desaturate(1.0)])?;
+        let Rgb([r, g, b]) = *grayscale.get_pixel(0, 0);
+        assert!(r == g && g == b);
+
+        let tinted = apply(
+            image,
+            &[Effect::Tint {
+                color: "#0000ff".to_string(),
+                amount: 0.5,
+            }],
+        )?;
+        assert_eq!(*tinted.get_pixel(0, 0), Rgb([100, 50, 128]));
+
+        let bad_tint = Effect::Tint {
+            color: "blue".to_string(),
+            amount: 0.5,
+        };
+        assert!(apply(gray(10), &[bad_tint]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_vignette_darkens_corners_only() -> anyhow::Result<()> {
+        let image = apply(gray(200), &[Effect::Vignette(1.0)])?;
+        assert_eq!(*image.get_pixel(20, 10), Rgb([200, 200, 200]));
+        assert!(image.get_pixel(0, 0)[0] < 20);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blur_smooths_edges() -> anyhow::Result<()> {
+        let mut image = gray(0);
+        for y in 0..20 {
+            for x in 20..40 {
+                image.put_pixel(x, y, Rgb([255, 255, 255]));
+            }
+        }
+        let blurred = apply(image, &[Effect::Blur(3.0)])?;
+        let edge = blurred.get_pixel(20, 10)[0];
+        assert!(edge > 0 && edge < 255, "{edge}");
+        assert_eq!(apply(gray(10), &[Effect::Blur(0.0)])?, gray(10));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_processed_is_cached_per_chain() -> anyhow::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let source = dir.path().join("wallpaper_abc.jpg");
+        gray(120).save(&source)?;
+        let cache = dir.path().join("effects");
+
+        assert_eq!(processed(&[], &source, &cache).await?, source);
+
+        let dim = [Effect::Brightness(0.5)];
+        let first = processed(&dim, &source, &cache).await?;
+        assert!(first.starts_with(&cache));
+        // A cache hit returns the file without redoing the work.
+        std::fs::write(&first, b"cached")?;
+        assert_eq!(processed(&dim, &source, &cache).await?, first);
+        assert_eq!(std::fs::read(&first)?, b"cached");
+
+        let blur = processed(&[Effect::Blur(2.0)], &source, &cache).await?;
+        assert_ne!(blur, first);
+
+        assert_eq!(prune(&cache, &["wallpaper_abc.jpg"]), 0);
+        assert_eq!(prune(&cache, &["wallpaper_other.jpg"]), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+use crate::config::FeedSource;
+use anyhow::{Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use std::borrow::Cow;
+
+/// An entry from an RSS or Atom feed that carries an image.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeedItem {
+    /// `<guid>` or `<id>`.
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub link: Option<String>,
+    pub author: Option<String>,
+    pub image_url: String,
+}
+
+/// What an `<item>`/`<entry>` has been found to contain so far.
+#[derive(Default)]
+struct ItemBuilder {
+    id: Option<String>,
+    title: Option<String>,
+    link: Option<String>,
+    author: Option<String>,
+    media_content: Option<String>,
+    enclosure: Option<String>,
+    inline_image: Option<String>,
+    thumbnail: Option<String>,
+}
+
+impl ItemBuilder {
+    /// The item, using the best image it has: `media:content`, then an image
+    /// enclosure, then the first `<img>` in its HTML, then `media:thumbnail`.
+    fn build(self) -> Option<FeedItem> {
+        let image_url = self
+            .media_content
+            .or(self.enclosure)
+            .or(self.inline_image)
+            .or(self.thumbnail)?;
+        Some(FeedItem {
+            id: self.id,
+            title: self.title,
+            link: self.link,
+            author: self.author,
+            image_url,
+        })
+    }
+
+    fn start(&mut self, element: &BytesStart) -> Result<()> {
+        let attr = |name: &str| -> Result<Option<String>> {
+            Ok(match element.try_get_attribute(name)? {
+                Some(value) => Some(value.unescape_value()?.trim().to_string()),
+                None => None,
+            })
+        };
+
+        match element.name().as_ref() {
+            b"media:content" if self.media_content.is_none() => {
+                let is_image = attr("medium")?.is_none_or(|m| m == "image")
+                    && attr("type")?.is_none_or(|t| t.starts_with("image/"));
+                self.media_content = attr("url")?.filter(|_| is_image);
+            }
+            b"media:thumbnail" if self.thumbnail.is_none() => self.thumbnail = attr("url")?,
+            b"enclosure" if self.enclosure.is_none() => {
+                let is_image = attr("type")?.is_some_and(|t| t.starts_with("image/"));
+                self.enclosure = attr("url")?.filter(|_| is_image);
+            }
+            // Atom links; RSS ones are text and handled in `text`.
+            b"link" if self.link.is_none() => {
+                let is_alternate = attr("rel")?.is_none_or(|rel| rel == "alternate");
+                self.link = attr("href")?.filter(|_| is_alternate);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle text inside `element` (whose parent is `parent`).
+    fn text(&mut self, element: &[u8], parent: Option<&[u8]>, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        let set = |field: &mut Option<String>, value: &str| {
+            if field.is_none() {
+                *field = Some(value.to_string());
+            }
+        };
+
+        match (element, parent) {
+            (b"title", None) => set(&mut self.title, text),
+            (b"link", None) => set(&mut self.link, text),
+            (b"guid" | b"id", None) => set(&mut self.id, text),
+            (b"dc:creator", None) | (b"name", Some(b"author")) => set(&mut self.author, text),
+            // RSS `<author>` is `email (Name)`.
+            (b"author", None) => set(&mut self.author, rss_author_name(text)),
+            (b"description" | b"content:encoded" | b"content" | b"summary", None)
+                if self.inline_image.is_none() =>
+            {
+                self.inline_image = first_img_src(text);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse the items with images out of an RSS 2.0 or Atom document.
+///
+/// # Errors
+///
+/// Returns an error if the document isn't well-formed XML.
+pub fn parse(xml: &str) -> Result<Vec<FeedItem>> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut items = Vec::new();
+    let mut current: Option<ItemBuilder> = None;
+    // Elements open inside the current item.
+    let mut open: Vec<Vec<u8>> = Vec::new();
+
+    loop {
+        match reader.read_event().context("Invalid feed XML")? {
+            Event::Start(element) => {
+                let name = element.name().as_ref().to_vec();
+                if let Some(item) = current.as_mut() {
+                    item.start(&element)?;
+                    open.push(name);
+                } else if name == b"item" || name == b"entry" {
+                    current = Some(ItemBuilder::default());
+                    open.clear();
+                }
+            }
+            Event::Empty(element) => {
+                if let Some(item) = current.as_mut() {
+                    item.start(&element)?;
+                }
+            }
+            Event::Text(text) => {
+                if let Some(item) = current.as_mut()
+                    && let Some(element) = open.last()
+                {
+                    let parent = open.len().checked_sub(2).map(|i| open[i].as_slice());
+                    // Fall back to the raw text for HTML entities XML doesn't know.
+                    let text = text.unescape().map_or_else(
+                        |_| String::from_utf8_lossy(&text).into_owned(),
+                        Cow::into_owned,
+                    );
+                    item.text(element, parent, &text);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(item) = current.as_mut()
+                    && let Some(element) = open.last()
+                {
+                    let parent = open.len().checked_sub(2).map(|i| open[i].as_slice());
+                    item.text(element, parent, &String::from_utf8_lossy(&data));
+                }
+            }
+            Event::End(_) => {
+                if open.pop().is_none()
+                    && let Some(item) = current.take()
+                    && let Some(item) = item.build()
+                {
+                    items.push(item);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(items)
+}
+
+/// Download and parse `feed`, resolving relative image and page URLs.
+///
+/// # Errors
+///
+/// Returns an error if the request fails, the server returns an error status,
+/// the feed can't be parsed, or it has no items with images.
+pub async fn fetch(client: &reqwest::Client, feed: &FeedSource) -> Result<Vec<FeedItem>> {
+    let response = client
+        .get(&feed.url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch feed '{}'", feed.name))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("Feed '{}' returned {status}", feed.name);
+    }
+
+    let base = response.url().clone();
+    let body = response.text().await.context("Failed to read feed")?;
+    let mut items =
+        parse(&body).with_context(|| format!("Failed to parse feed '{}'", feed.name))?;
+    if items.is_empty() {
+        anyhow::bail!("Feed '{}' has no items with images", feed.name);
+    }
+
+    for item in &mut items {
+        item.image_url = resolve(&base, &item.image_url);
+        item.link = item.link.as_deref().map(|link| resolve(&base, link));
+    }
+    Ok(items)
+}
+
+/// `url` made absolute against the feed's own URL.
+fn resolve(base: &reqwest::Url, url: &str) -> String {
+    base.join(url)
+        .map_or_else(|_| url.to_string(), String::from)
+}
+
+/// The `src` of the first `<img>` in an HTML fragment.
+fn first_img_src(html: &str) -> Option<String> {
+    let tag_start = html.find("<img")?;
+    let tag = &html[tag_start..];
+    let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+    // Only a whole `src` attribute, not the end of `data-src` or `srcset`.
+    let (at, _) = tag
+        .match_indices("src=")
+        .find(|(at, _)| tag[..*at].ends_with(|c: char| c.is_ascii_whitespace()))?;
+    let src = &tag[at + "src=".len()..];
+    let quote = src.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &src[1..];
+    let value = &value[..value.find(quote)?];
+    (!value.is_empty()).then(|| value.replace("&amp;", "&"))
+}
+
+fn rss_author_name(author: &str) -> &str {
+    author
+        .split_once('(')
+        .and_then(|(_, rest)| rest.strip_suffix(')'))
+        .map_or(author, str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rss() -> anyhow::Result<()> {
+        let xml = r#"<?xml version="1.0"?>
+            <rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/"
+                 xmlns:dc="http://purl.org/dc/elements/1.1/">
+              <channel>
+                <title>Photo blog</title>
+                <link>https://blog.example.com</link>
+                <item>
+                  <title>Harbour at dawn</title>
+                  <link>https://blog.example.com/harbour</link>
+                  <guid>post-1</guid>
+                  <dc:creator>Ana</dc:creator>
+                  <media:content url="https://cdn.example.com/harbour.jpg" medium="image"/>
+                  <media:thumbnail url="https://cdn.example.com/harbour_t.jpg"/>
+                </item>
+                <item>
+                  <title>Enclosed</title>
+                  <author>bo@example.com (Bo)</author>
+                  <enclosure url="https://cdn.example.com/podcast.mp3" type="audio/mpeg"/>
+                  <enclosure url="https://cdn.example.com/fog.png" type="image/png"/>
+                </item>
+                <item>
+                  <title>Inline</title>
+                  <description><![CDATA[<p>Look: <img alt="x" src='/img/snow.jpg?a=1&amp;b=2'></p>]]></description>
+                </item>
+                <item>
+                  <title>No image here</title>
+                  <description>Just words.</description>
+                </item>
+              </channel>
+            </rss>"#;
+
+        let items = parse(xml)?;
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            items[0],
+            FeedItem {
+                id: Some("post-1".to_string()),
+                title: Some("Harbour at dawn".to_string()),
+                link: Some("https://blog.example.com/harbour".to_string()),
+                author: Some("Ana".to_string()),
+                image_url: "https://cdn.example.com/harbour.jpg".to_string(),
+            }
+        );
+        assert_eq!(items[1].image_url, "https://cdn.example.com/fog.png");
+        assert_eq!(items[1].author.as_deref(), Some("Bo"));
+        assert_eq!(items[2].image_url, "/img/snow.jpg?a=1&b=2");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_atom() -> anyhow::Result<()> {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom">
+              <title>r/EarthPorn</title>
+              <entry>
+                <author><name>/u/hiker</name></author>
+                <id>t3_abc</id>
+                <link href="https://www.reddit.com/r/EarthPorn/comments/abc/"/>
+                <title>Alpine lake [4000x3000]</title>
+                <content type="html">&lt;a href="x"&gt;&lt;img src="https://preview.redd.it/lake.jpg?width=640&amp;amp;s=1" alt="Alpine lake" /&gt;&lt;/a&gt;</content>
+              </entry>
+            </feed>"#;
+
+        let items = parse(xml)?;
+        assert_eq!(items.len(), 1);
+        let item = &items[0];
+        assert_eq!(item.id.as_deref(), Some("t3_abc"));
+        assert_eq!(item.author.as_deref(), Some("/u/hiker"));
+        assert_eq!(item.title.as_deref(), Some("Alpine lake [4000x3000]"));
+        assert_eq!(
+            item.link.as_deref(),
+            Some("https://www.reddit.com/r/EarthPorn/comments/abc/")
+        );
+        assert_eq!(
+            item.image_url,
+            "https://preview.redd.it/lake.jpg?width=640&s=1"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_xml() {
+        assert!(parse("<rss><channel><item><title>x</item></rss>").is_err());
+    }
+
+    #[test]
+    fn test_resolve_relative_urls() -> anyhow::Result<()> {
+        let base = reqwest::Url::parse("https://blog.example.com/feeds/rss.xml")?;
+        assert_eq!(
+            resolve(&base, "/img/snow.jpg"),
+            "https://blog.example.com/img/snow.jpg"
+        );
+        assert_eq!(
+            resolve(&base, "https://cdn.example.com/a.jpg"),
+            "https://cdn.example.com/a.jpg"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_img_src_skips_other_attributes() {
+        assert_eq!(
+            first_img_src(
+                r#"<p><img data-src="lazy.jpg" srcset="a.jpg 2x" src="real.jpg?a=1&amp;b=2"></p>"#
+            )
+            .as_deref(),
+            Some("real.jpg?a=1&b=2")
+        );
+        assert_eq!(first_img_src(r#"<img data-src="lazy.jpg">"#), None);
+    }
+}
@@ -0,0 +1,417 @@
+//! Procedural wallpapers that need no network.
+//!
+//! A [`Pattern`] is fully described by its style, seed and size, which are all
+//! kept in its wallpaper ID, so a generated wallpaper whose file is gone can be
+//! rendered again from its history record.
+
+use anyhow::{Context, Result};
+use image::{Rgb, RgbImage};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Size used when the screen size can't be determined.
+pub const DEFAULT_SIZE: (u32, u32) = (1920, 1080);
+
+const ID_PREFIX: &str = "generated-";
+
+/// Whether a wallpaper ID is of a generated pattern.
+#[must_use]
+pub fn is_generated(id: &str) -> bool {
+    id.starts_with(ID_PREFIX)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorStyle {
+    /// A linear blend of two or three colors at a random angle.
+    Gradient,
+    Solid,
+    /// Smooth fractal value noise between two colors.
+    Noise,
+    /// Soft blobs of color blended around a few random points.
+    Mesh,
+    /// Squares split along a diagonal into differently shaded triangles.
+    Tiles,
+}
+
+impl GeneratorStyle {
+    pub const ALL: [Self; 5] = [
+        Self::Gradient,
+        Self::Solid,
+        Self::Noise,
+        Self::Mesh,
+        Self::Tiles,
+    ];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Gradient => "gradient",
+            Self::Solid => "solid",
+            Self::Noise => "noise",
+            Self::Mesh => "mesh",
+            Self::Tiles => "tiles",
+        }
+    }
+
+    /// How the style is shown to the user, e.g. in a wallpaper's title.
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Gradient => "Gradient",
+            Self::Solid => "Solid color",
+            Self::Noise => "Noise",
+            Self::Mesh => "Mesh gradient",
+            Self::Tiles => "Geometric tiles",
+        }
+    }
+}
+
+impl fmt::Display for GeneratorStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for GeneratorStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|style| style.as_str() == s)
+            .with_context(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|s| s.as_str()).collect();
+                format!(
+                    "Unknown generator style '{s}' (expected one of {})",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Everything needed to render one generated wallpaper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pattern {
+    pub style: GeneratorStyle,
+    pub seed: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Pattern {
+    /// A pattern of `style` with a fresh random seed.
+    #[must_use]
+    pub fn random(style: GeneratorStyle, (width, height): (u32, u32)) -> Self {
+        Self {
+            style,
+            seed: fastrand::u64(..),
+            width,
+            height,
+        }
+    }
+
+    /// e.g. `generated-mesh-00c0ffee00c0ffee-2560x1440`.
+    #[must_use]
+    pub fn id(&self) -> String {
+        format!(
+            "{ID_PREFIX}{}-{:016x}-{}x{}",
+            self.style, self.seed, self.width, self.height
+        )
+    }
+
+    /// The pattern a wallpaper ID was generated from, if it is one.
+    #[must_use]
+    pub fn from_id(id: &str) -> Option<Self> {
+        let mut parts = id.strip_prefix(ID_PREFIX)?.split('-');
+        let style = parts.next()?.parse().ok()?;
+        let seed = u64::from_str_radix(parts.next()?, 16).ok()?;
+        let (width, height) = parts.next()?.split_once('x')?;
+        let pattern = Self {
+            style,
+            seed,
+            width: width.parse().ok().filter(|w| *w > 0)?,
+            height: height.parse().ok().filter(|h| *h > 0)?,
+        };
+        parts.next().is_none().then_some(pattern)
+    }
+
+    #[must_use]
+    pub fn render(&self) -> RgbImage {
+        let mut rng = Rng(self.seed);
+        let palette = palette(&mut rng);
+        let (width, height) = (self.width, self.height);
+        match self.style {
+            GeneratorStyle::Solid => RgbImage::from_pixel(width, height, to_rgb(palette[0])),
+            GeneratorStyle::Gradient => gradient(&mut rng, &palette, width, height),
+            GeneratorStyle::Noise => noise(&mut rng, &palette, width, height),
+            GeneratorStyle::Mesh => mesh(&mut rng, &palette, width, height),
+            GeneratorStyle::Tiles => tiles(&mut rng, &palette, width, height),
+        }
+    }
+}
+
+/// Render `pattern` and save it to `path` as a JPEG.
+///
+/// # Errors
+///
+/// Returns an error if the image can't be encoded or written.
+pub async fn save(pattern: Pattern, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .context("Failed to create wallpaper directory")?;
+    }
+
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let image = pattern.render();
+        crate::http::write_jpeg(&image, &path, 92)
+    })
+    .await
+    .context("Wallpaper generation task failed")?
+    .with_context(|| format!("Failed to generate {}", pattern.id()))
+}
+
+/// `SplitMix64`. Unlike `fastrand`, whose output may change between versions,
+/// this stays fixed, so old seeds keep rendering the same picture.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.0)
+    }
+
+    /// A number in `[0, 1)`.
+    #[allow(clippy::cast_precision_loss)]
+    fn unit(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + self.unit() * (max - min)
+    }
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A stable pseudo-random number in `[0, 1)` for a lattice point.
+#[allow(clippy::cast_precision_loss)]
+fn hash_unit(seed: u64, x: u32, y: u32) -> f32 {
+    let key = seed ^ (u64::from(x) << 32 | u64::from(y)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    (mix(key) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+type Color = [f32; 3];
+
+/// Three colors with neighbouring hues, so they go together.
+fn palette(rng: &mut Rng) -> [Color; 3] {
+    let hue = rng.unit();
+    let spread = rng.range(0.04, 0.12);
+    let saturation = rng.range(0.35, 0.75);
+    [
+        hsl(hue, saturation, rng.range(0.18, 0.32)),
+        hsl(hue + spread, saturation, rng.range(0.38, 0.52)),
+        hsl(hue - spread, saturation * 0.8, rng.range(0.55, 0.7)),
+    ]
+}
+
+fn hsl(hue: f32, saturation: f32, lightness: f32) -> Color {
+    let hue = hue.rem_euclid(1.0) * 6.0;
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let second = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let rgb = match hue {
+        h if h < 1.0 => [chroma, second, 0.0],
+        h if h < 2.0 => [second, chroma, 0.0],
+        h if h < 3.0 => [0.0, chroma, second],
+        h if h < 4.0 => [0.0, second, chroma],
+        h if h < 5.0 => [second, 0.0, chroma],
+        _ => [chroma, 0.0, second],
+    };
+    let offset = lightness - chroma / 2.0;
+    rgb.map(|c| c + offset)
+}
+
+fn lerp(a: Color, b: Color, t: f32) -> Color {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_rgb(color: Color) -> Rgb<u8> {
+    Rgb(color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+}
+
+/// Position in `[0, 1)` along a two- or three-stop ramp through `palette`.
+fn ramp(palette: &[Color; 3], t: f32, stops: usize) -> Color {
+    if stops < 3 {
+        return lerp(palette[0], palette[2], t);
+    }
+    if t < 0.5 {
+        lerp(palette[0], palette[1], t * 2.0)
+    } else {
+        lerp(palette[1], palette[2], t * 2.0 - 1.0)
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn gradient(rng: &mut Rng, palette: &[Color; 3], width: u32, height: u32) -> RgbImage {
+    let angle = rng.range(0.0, std::f32::consts::TAU);
+    let (dx, dy) = (angle.cos(), angle.sin());
+    let stops = if rng.unit() < 0.5 { 2 } else { 3 };
+    // Project the corners to normalise the ramp across the whole image.
+    let (w, h) = (width as f32, height as f32);
+    let projections = [0.0, w * dx, h * dy, w * dx + h * dy];
+    let min = projections.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = projections
+        .iter()
+        .copied()
+        .fold(f32::NEG_INFINITY, f32::max);
+    let span = (max - min).max(1.0);
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let t = ((x as f32 * dx + y as f32 * dy) - min) / span;
+        to_rgb(ramp(palette, t, stops))
+    })
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn noise(rng: &mut Rng, palette: &[Color; 3], width: u32, height: u32) -> RgbImage {
+    const OCTAVES: u32 = 4;
+    let seed = rng.next_u64();
+    let cell = width.max(height) as f32 / rng.range(3.0, 6.0);
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let (mut value, mut amplitude, mut total, mut scale) = (0.0, 1.0, 0.0, cell);
+        for octave in 0..OCTAVES {
+            let octave_seed = seed.wrapping_add(u64::from(octave));
+            value += amplitude * value_noise(octave_seed, x as f32 / scale, y as f32 / scale);
+            total += amplitude;
+            amplitude *= 0.5;
+            scale *= 0.5;
+        }
+        to_rgb(ramp(palette, value / total, 3))
+    })
+}
+
+/// Smoothly interpolated random values on an integer lattice.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (ix, iy) = (x0 as u32, y0 as u32);
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, ty) = (smooth(x - x0), smooth(y - y0));
+    let top = hash_unit(seed, ix, iy) * (1.0 - tx) + hash_unit(seed, ix + 1, iy) * tx;
+    let bottom = hash_unit(seed, ix, iy + 1) * (1.0 - tx) + hash_unit(seed, ix + 1, iy + 1) * tx;
+    top * (1.0 - ty) + bottom * ty
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn mesh(rng: &mut Rng, palette: &[Color; 3], width: u32, height: u32) -> RgbImage {
+    let (w, h) = (width as f32, height as f32);
+    let points: Vec<(f32, f32, Color)> = (0..5)
+        .map(|i| {
+            let color = lerp(palette[i % 3], palette[(i + 1) % 3], rng.unit());
+            (rng.range(-0.1, 1.1) * w, rng.range(-0.1, 1.1) * h, color)
+        })
+        .collect();
+    // Keeps weights finite at the points themselves.
+    let softness = (w * w + h * h) * 0.002;
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let (mut color, mut total) = ([0.0; 3], 0.0);
+        for &(px, py, point_color) in &points {
+            let distance = (x as f32 - px).powi(2) + (y as f32 - py).powi(2);
+            let weight = 1.0 / (distance + softness).powi(2);
+            for (channel, value) in color.iter_mut().zip(point_color) {
+                *channel += value * weight;
+            }
+            total += weight;
+        }
+        to_rgb(color.map(|c| c / total))
+    })
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn tiles(rng: &mut Rng, palette: &[Color; 3], width: u32, height: u32) -> RgbImage {
+    let seed = rng.next_u64();
+    let size = (height / (4 + (rng.next_u64() % 9) as u32)).max(8);
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let (cx, cy) = (x / size, y / size);
+        let (fx, fy) = ((x % size) as f32, (y % size) as f32);
+        let size = size as f32;
+        // Which diagonal splits this square, and which half the pixel is in.
+        let upper = if hash_unit(seed, cx, cy) < 0.5 {
+            fx + fy < size
+        } else {
+            fx < fy
+        };
+        let half = u32::from(upper);
+        let pick = hash_unit(seed ^ 1, cx * 2 + half, cy);
+        let shade = hash_unit(seed ^ 2, cx * 2 + half, cy) * 0.25;
+        let base = palette[usize::from(pick >= 1.0 / 3.0) + usize::from(pick >= 2.0 / 3.0)];
+        to_rgb(lerp(base, palette[0], shade))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(style: GeneratorStyle, seed: u64) -> Pattern {
+        Pattern {
+            style,
+            seed,
+            width: 64,
+            height: 36,
+        }
+    }
+
+    #[test]
+    fn test_id_round_trip() {
+        let pattern = pattern(GeneratorStyle::Mesh, 0x00c0_ffee);
+        assert_eq!(pattern.id(), "generated-mesh-0000000000c0ffee-64x36");
+        assert_eq!(Pattern::from_id(&pattern.id()), Some(pattern));
+
+        assert_eq!(Pattern::from_id("wallhaven-abc123"), None);
+        assert_eq!(Pattern::from_id("generated-plaid-00-64x36"), None);
+        assert_eq!(Pattern::from_id("generated-mesh-00-64x0"), None);
+        assert_eq!(Pattern::from_id("generated-mesh-00-64x36-extra"), None);
+    }
+
+    #[test]
+    fn test_render_is_deterministic() {
+        for style in GeneratorStyle::ALL {
+            let image = pattern(style, 42).render();
+            assert_eq!(image.dimensions(), (64, 36));
+            assert_eq!(image, pattern(style, 42).render(), "{style}");
+            assert_ne!(image, pattern(style, 43).render(), "{style}");
+        }
+    }
+
+    #[test]
+    fn test_rng_is_fixed() {
+        // Changing these breaks re-rendering of existing history entries.
+        let mut rng = Rng(0);
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+    }
+
+    #[test]
+    fn test_style_from_str() {
+        assert_eq!(
+            "tiles".parse::<GeneratorStyle>().ok(),
+            Some(GeneratorStyle::Tiles)
+        );
+        assert!("plaid".parse::<GeneratorStyle>().is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use crate::config::JsonSource;
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// A picture found by a JSON source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JsonItem {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub link: Option<String>,
+    pub image_url: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    /// `*` or `[*]`: every element of an array or value of an object.
+    All,
+}
+
+/// Parse a JSONPath-like selector: an optional leading `$`, then `.key`,
+/// `["key"]`, `[0]`, `.*` or `[*]` steps, e.g. `$.data.children[*].data`.
+fn parse_selector(selector: &str) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    let mut rest = selector.trim();
+    rest = rest.strip_prefix('$').unwrap_or(rest);
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after
+                .find(']')
+                .with_context(|| format!("Unclosed '[' in selector '{selector}'"))?;
+            let inner = after[..end].trim();
+            steps.push(if inner == "*" {
+                Step::All
+            } else if let Ok(index) = inner.parse() {
+                Step::Index(index)
+            } else {
+                let key = inner.trim_matches(|c| c == '"' || c == '\'');
+                if key.is_empty() || key.len() == inner.len() {
+                    anyhow::bail!("Invalid step '[{inner}]' in selector '{selector}'");
+                }
+                Step::Key(key.to_string())
+            });
+            rest = &after[end + 1..];
+        } else {
+            let after = rest.strip_prefix('.').unwrap_or(rest);
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = &after[..end];
+            if key.is_empty() {
+                anyhow::bail!("Empty key in selector '{selector}'");
+            }
+            steps.push(if key == "*" {
+                Step::All
+            } else {
+                Step::Key(key.to_string())
+            });
+            rest = &after[end..];
+        }
+    }
+    Ok(steps)
+}
+
+/// Every value `selector` matches in `value`.
+///
+/// # Errors
+///
+/// Returns an error if the selector can't be parsed.
+pub fn select<'a>(value: &'a Value, selector: &str) -> Result<Vec<&'a Value>> {
+    let mut matches = vec![value];
+    for step in parse_selector(selector)? {
+        matches = matches
+            .into_iter()
+            .flat_map(|value| -> Vec<&Value> {
+                match (&step, value) {
+                    (Step::Key(key), Value::Object(map)) => map.get(key).into_iter().collect(),
+                    (Step::Index(index), Value::Array(items)) => {
+                        items.get(*index).into_iter().collect()
+                    }
+                    (Step::All, Value::Array(items)) => items.iter().collect(),
+                    (Step::All, Value::Object(map)) => map.values().collect(),
+                    _ => Vec::new(),
+                }
+            })
+            .collect();
+    }
+    Ok(matches)
+}
+
+/// The first string (or number) `selector` matches in `value`.
+fn select_text(value: &Value, selector: Option<&str>) -> Result<Option<String>> {
+    let Some(selector) = selector else {
+        return Ok(None);
+    };
+    Ok(select(value, selector)?.into_iter().find_map(|v| match v {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }))
+}
+
+/// Pick the items out of a JSON response as `source` describes. Items without
+/// an image URL are skipped.
+///
+/// # Errors
+///
+/// Returns an error if a selector can't be parsed.
+pub fn extract(response: &Value, source: &JsonSource) -> Result<Vec<JsonItem>> {
+    let mut items = Vec::new();
+    for item in select(response, source.items.as_deref().unwrap_or("$"))? {
+        let Some(image_url) = select_text(item, Some(&source.image))? else {
+            continue;
+        };
+        items.push(JsonItem {
+            id: select_text(item, source.id.as_deref())?,
+            title: select_text(item, source.title.as_deref())?,
+            author: select_text(item, source.author.as_deref())?,
+            link: select_text(item, source.link.as_deref())?,
+            image_url,
+        });
+    }
+    Ok(items)
+}
+
+/// Request `source.url` and extract its items.
+///
+/// # Errors
+///
+/// Returns an error if the request fails, the server returns an error status,
+/// the body isn't JSON, a selector is invalid, or no items have images.
+pub async fn fetch(client: &reqwest::Client, source: &JsonSource) -> Result<Vec<JsonItem>> {
+    let mut request = client.get(&source.url);
+    for (name, value) in &source.headers {
+        request = request.header(name, value);
+    }
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch JSON source '{}'", source.name))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("JSON source '{}' returned {status}", source.name);
+    }
+
+    let base = response.url().clone();
+    let body: Value = response
+        .json()
+        .await
+        .with_context(|| format!("JSON source '{}' did not return JSON", source.name))?;
+    let mut items = extract(&body, source)?;
+    if items.is_empty() {
+        anyhow::bail!(
+            "JSON source '{}' matched no images with '{}'",
+            source.name,
+            source.image
+        );
+    }
+
+    for item in &mut items {
+        if let Ok(url) = base.join(&item.image_url) {
+            item.image_url = url.into();
+        }
+        if let Some(link) = &item.link
+            && let Ok(url) = base.join(link)
+        {
+            item.link = Some(url.into());
+        }
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reddit_listing() -> Value {
+        serde_json::json!({
+            "kind": "Listing",
+            "data": {"children": [
+                {"data": {"id": "a1", "title": "Fjord", "author": "hiker",
+                          "url": "https://i.redd.it/fjord.jpg",
+                          "permalink": "/r/EarthPorn/comments/a1/"}},
+                {"data": {"id": "a2", "title": "Text post", "author": "talker"}},
+                {"data": {"id": 3, "title": "Dunes", "author": "walker",
+                          "url": "https://i.redd.it/dunes.png"}}
+            ]}
+        })
+    }
+
+    #[test]
+    fn test_parse_selector() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_selector("$.data.children[*].data['url']")?,
+            vec![
+                Step::Key("data".to_string()),
+                Step::Key("children".to_string()),
+                Step::All,
+                Step::Key("data".to_string()),
+                Step::Key("url".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_selector("images[0].*")?,
+            vec![Step::Key("images".to_string()), Step::Index(0), Step::All]
+        );
+        assert!(parse_selector("$").unwrap_or_default().is_empty());
+        assert!(parse_selector("data[").is_err());
+        assert!(parse_selector("data..url").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select() -> anyhow::Result<()> {
+        let listing = reddit_listing();
+        let titles: Vec<&Value> = select(&listing, "$.data.children[*].data.title")?;
+        assert_eq!(titles, ["Fjord", "Text post", "Dunes"]);
+        assert_eq!(select(&listing, "$.data.children[1].data.id")?, ["a2"]);
+        assert!(select(&listing, "$.missing.path")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_skips_items_without_images() -> anyhow::Result<()> {
+        let source = JsonSource {
+            name: "earthporn".to_string(),
+            items: Some("$.data.children[*].data".to_string()),
+            image: "url".to_string(),
+            id: Some("id".to_string()),
+            title: Some("title".to_string()),
+            author: Some("author".to_string()),
+            ..Default::default()
+        };
+
+        let items = extract(&reddit_listing(), &source)?;
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            items[0],
+            JsonItem {
+                id: Some("a1".to_string()),
+                title: Some("Fjord".to_string()),
+                author: Some("hiker".to_string()),
+                link: None,
+                image_url: "https://i.redd.it/fjord.jpg".to_string(),
+            }
+        );
+        assert_eq!(items[1].id.as_deref(), Some("3"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_single_object() -> anyhow::Result<()> {
+        let response = serde_json::json!({"image": {"src": "/daily.jpg"}, "caption": "Today"});
+        let source = JsonSource {
+            image: "image.src".to_string(),
+            title: Some("caption".to_string()),
+            ..Default::default()
+        };
+        let items = extract(&response, &source)?;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].image_url, "/daily.jpg");
+        assert_eq!(items[0].title.as_deref(), Some("Today"));
+        Ok(())
+    }
+}
@@ -1,7 +1,27 @@
+pub mod analysis;
+pub mod apod;
+pub mod bing;
+pub mod browser;
 pub mod cli;
 pub mod config;
+pub mod credentials;
+pub mod effects;
+pub mod feed;
+pub mod generator;
+pub mod http;
+pub mod json_source;
 pub mod logging;
 pub mod manager;
+pub mod output;
+pub mod overlay;
+pub mod plugin;
+pub mod power;
+pub mod preview;
 pub mod scheduler;
+pub mod screen;
+pub mod settings;
+pub mod sources;
+pub mod theme;
 pub mod tray;
 pub mod unsplash;
+pub mod wallhaven;
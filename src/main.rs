@@ -4,13 +4,33 @@
 use clap::Parser;
 use std::process::ExitCode;
 
+mod analysis;
+mod apod;
+mod bing;
+mod browser;
 mod cli;
 mod config;
+mod credentials;
+mod effects;
+mod feed;
+mod generator;
+mod http;
+mod json_source;
 mod logging;
 mod manager;
+mod output;
+mod overlay;
+mod plugin;
+mod power;
+mod preview;
 mod scheduler;
+mod screen;
+mod settings;
+mod sources;
+mod theme;
 mod tray;
 mod unsplash;
+mod wallhaven;
 
 #[cfg(target_os = "windows")]
 mod win_utils {
@@ -58,6 +78,7 @@ const ASCII_ART: &str = concat!(
 );
 
 use cli::{Cli, Commands, print_grouped_help};
+use output::Format;
 
 fn main() -> ExitCode {
     #[allow(clippy::single_match_else)]
@@ -79,18 +100,26 @@ fn main() -> ExitCode {
     #[allow(clippy::single_match_else)]
     match &cli.command {
         Some(cmd) => {
-            // Allow settings/info/status/list/config/folder commands without initialization
+            // Allow settings/info/status/list/preview/config/folder commands without initialization
             let needs_init = !matches!(
                 cmd,
                 Commands::Settings
                     | Commands::Info
                     | Commands::Status
-                    | Commands::List
-                    | Commands::Config
+                    | Commands::List { .. }
+                    | Commands::Preview { .. }
+                    | Commands::Config { .. }
                     | Commands::Folder
             );
+            let format = cli.output_format();
             // Auto-run setup on first install
             if needs_init && !cli::is_initialized() {
+                if format == Format::Json {
+                    return report_error(
+                        "wallp isn't set up yet. Run 'wallp setup' first.",
+                        format,
+                    );
+                }
                 println!("{ASCII_ART}");
                 if let Err(e) = cli::setup_wizard() {
                     eprintln!("Error during setup: {e}");
@@ -99,9 +128,8 @@ fn main() -> ExitCode {
                 return ExitCode::SUCCESS;
             }
 
-            if let Err(e) = cli::handle_command(cmd) {
-                eprintln!("Error: {e}");
-                return ExitCode::FAILURE;
+            if let Err(e) = cli::handle_command(cmd, format) {
+                return report_error(&e.to_string(), format);
             }
         }
         None => {
@@ -146,3 +174,20 @@ fn main() -> ExitCode {
 
     ExitCode::SUCCESS
 }
+
+/// Print a failed command's error where the caller will look for it: as JSON
+/// on stdout with `--json`, otherwise on stderr.
+fn report_error(error: &str, format: Format) -> ExitCode {
+    match format {
+        Format::Json => {
+            let report = output::ErrorReport {
+                error: error.to_string(),
+            };
+            if let Err(e) = output::print_json(&report) {
+                eprintln!("Error: {error} ({e})");
+            }
+        }
+        Format::Text => eprintln!("Error: {error}"),
+    }
+    ExitCode::FAILURE
+}
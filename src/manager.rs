@@ -1,7 +1,18 @@
-use crate::config::{AppData, Wallpaper};
-use crate::unsplash::UnsplashClient;
-use anyhow::Result;
+use crate::analysis::{self, Tone};
+use crate::config::{
+    AppData, BannedPhoto, Brightness, Config, CustomCollection, Effect, OfflineFallback, Wallpaper,
+};
+use crate::credentials::AccessKey;
+use crate::effects;
+use crate::generator::{self, GeneratorStyle, Pattern};
+use crate::http;
+use crate::overlay;
+use crate::preview;
+use crate::sources::{self, Candidate, WallpaperSource};
+use crate::unsplash::{ImageQuality, UnsplashClient, UnsplashCollection};
+use anyhow::{Context, Result};
 use chrono::Utc;
+use std::path::{Path, PathBuf};
 
 #[allow(clippy::missing_errors_doc, clippy::unused_async)]
 pub async fn set_lockscreen_wallpaper(path: &std::path::Path) -> Result<()> {
@@ -35,33 +46,38 @@ async fn set_lockscreen_from_file(app_data: &AppData, path: &std::path::Path) ->
     if !app_data.config.lockscreen_enabled {
         return Ok(());
     }
-    set_lockscreen_wallpaper(path).await
+    let path = with_effects(&app_data.config.effects.lockscreen, path).await;
+    set_lockscreen_wallpaper(&path).await
 }
 
 #[allow(clippy::missing_errors_doc)]
 pub async fn new() -> Result<()> {
+    new_with_quality(ImageQuality::Full).await
+}
+
+/// Like [`new`], but downloads `quality`.
+#[allow(clippy::missing_errors_doc)]
+pub async fn new_with_quality(quality: ImageQuality) -> Result<()> {
     let mut app_data = AppData::load()?;
-    fetch_and_set_new(&mut app_data).await
+    fetch_and_set_new(&mut app_data, quality).await
 }
 
 #[allow(clippy::missing_errors_doc)]
 pub async fn next() -> Result<()> {
+    next_with_quality(ImageQuality::Full).await
+}
+
+/// Like [`next`], but downloads `quality` if a new photo has to be fetched.
+#[allow(clippy::missing_errors_doc)]
+pub async fn next_with_quality(quality: ImageQuality) -> Result<()> {
     let mut app_data = AppData::load()?;
 
     while app_data.state.current_history_index < app_data.history.len().saturating_sub(1) {
         let target_index = app_data.state.current_history_index + 1;
-        let wallpaper = &app_data.history[target_index];
 
-        match set_wallpaper_from_history(wallpaper).await {
+        match set_wallpaper_from_history(&app_data.history[target_index]).await {
             Ok(()) => {
-                app_data.state.current_history_index = target_index;
-                app_data.state.current_wallpaper_id = Some(wallpaper.id.clone());
-
-                #[allow(clippy::cast_possible_wrap)]
-                let next_run =
-                    Utc::now() + chrono::Duration::minutes(app_data.config.interval_minutes as i64);
-                app_data.state.next_run_at = next_run.to_rfc3339();
-
+                mark_applied(&mut app_data, target_index);
                 app_data.save()?;
                 return Ok(());
             }
@@ -75,7 +91,166 @@ pub async fn next() -> Result<()> {
     }
 
     app_data.save()?;
-    fetch_and_set_new(&mut app_data).await
+    fetch_and_set_new(&mut app_data, quality).await
+}
+
+/// Apply the next wallpaper from local history without touching the network,
+/// wrapping around to the oldest one after the newest. Wallpapers of the
+/// wrong brightness are passed over unless no other is left.
+#[allow(clippy::missing_errors_doc)]
+pub async fn rotate_local() -> Result<()> {
+    let mut app_data = AppData::load()?;
+    let wanted = wanted_tone(app_data.config.brightness).await;
+
+    while !app_data.history.is_empty() {
+        let target_index = next_local(
+            &app_data.history,
+            app_data.state.current_history_index,
+            wanted,
+        );
+
+        match set_wallpaper_from_history(&app_data.history[target_index]).await {
+            Ok(()) => {
+                mark_applied(&mut app_data, target_index);
+                app_data.save()?;
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("Warning: Failed to rotate wallpaper: {e}. Removing from history.");
+                app_data.history.remove(target_index);
+                if target_index < app_data.state.current_history_index {
+                    app_data.state.current_history_index -= 1;
+                }
+            }
+        }
+    }
+
+    app_data.save()?;
+    anyhow::bail!("No downloaded wallpapers available to rotate through");
+}
+
+/// The history entry after `current` that matches `wanted`, wrapping around,
+/// or simply the next one if none does.
+fn next_local(history: &[Wallpaper], current: usize, wanted: Option<Tone>) -> usize {
+    let len = history.len();
+    (1..len)
+        .map(|step| (current + step) % len)
+        .find(|&i| history[i].matches(wanted))
+        .unwrap_or((current + 1) % len)
+}
+
+/// The tone photos need right now. With [`Brightness::System`] this runs a
+/// command to ask the desktop, so it's resolved once per operation and off
+/// the async runtime.
+async fn wanted_tone(brightness: Brightness) -> Option<Tone> {
+    tokio::task::spawn_blocking(move || brightness.wanted())
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Show a wallpaper from history chosen by `strategy` because the network is
+/// unavailable, and remember that so the next scheduled run fetches again.
+#[allow(clippy::missing_errors_doc)]
+pub async fn rotate_offline(strategy: OfflineFallback) -> Result<()> {
+    let mut app_data = AppData::load()?;
+    let wanted = wanted_tone(app_data.config.brightness).await;
+
+    while let Some(target_index) = pick_offline_candidate(
+        &app_data.history,
+        app_data.state.current_history_index,
+        strategy,
+        wanted,
+    ) {
+        match set_wallpaper_from_history(&app_data.history[target_index]).await {
+            Ok(()) => {
+                mark_applied(&mut app_data, target_index);
+                app_data.state.offline_rotation = true;
+                app_data.save()?;
+                return Ok(());
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Offline fallback failed to set wallpaper: {e}. Removing from history."
+                );
+                app_data.history.remove(target_index);
+                if target_index < app_data.state.current_history_index {
+                    app_data.state.current_history_index -= 1;
+                }
+            }
+        }
+    }
+
+    if app_data.config.generated_fallback {
+        tracing::info!("Nothing downloaded to fall back to, generating a wallpaper");
+        let style = GeneratorStyle::ALL[fastrand::usize(..GeneratorStyle::ALL.len())];
+        let wallpapers_dir = AppData::get_data_dir()?.join("wallpapers");
+        let access_key = AccessKey::default();
+        let wallpaper = download_from(
+            &mut app_data,
+            &WallpaperSource::Generated(style),
+            ImageQuality::Full,
+            &wallpapers_dir,
+            wanted,
+            &access_key,
+        )
+        .await?;
+        apply_new(&mut app_data, wallpaper, &wallpapers_dir, &access_key).await?;
+        app_data.state.offline_rotation = true;
+        app_data.save()?;
+        return Ok(());
+    }
+
+    app_data.save()?;
+    anyhow::bail!("No downloaded wallpapers available for offline fallback");
+}
+
+/// Pick the history entry to show offline, avoiding the current wallpaper
+/// unless it's the only one left, and preferring ones of the `wanted` tone.
+fn pick_offline_candidate(
+    history: &[Wallpaper],
+    current_index: usize,
+    strategy: OfflineFallback,
+    wanted: Option<Tone>,
+) -> Option<usize> {
+    if history.is_empty() || strategy == OfflineFallback::Off {
+        return None;
+    }
+    if history.len() == 1 {
+        return Some(0);
+    }
+
+    let others: Vec<usize> = (0..history.len()).filter(|&i| i != current_index).collect();
+    let matching: Vec<usize> = others
+        .iter()
+        .copied()
+        .filter(|&i| history[i].matches(wanted))
+        .collect();
+    let candidates = if matching.is_empty() {
+        others
+    } else {
+        matching
+    };
+    match strategy {
+        OfflineFallback::Off => None,
+        OfflineFallback::Random => candidates.get(fastrand::usize(..candidates.len())).copied(),
+        OfflineFallback::LeastRecent => candidates.into_iter().min_by(|&a, &b| {
+            let shown_at =
+                |i: usize| chrono::DateTime::parse_from_rfc3339(history[i].last_shown()).ok();
+            shown_at(a).cmp(&shown_at(b))
+        }),
+    }
+}
+
+/// Whether `error` was caused by failing to reach the network (as opposed to,
+/// say, an API error response or a full disk).
+#[must_use]
+pub fn is_network_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout() || e.is_request())
+    })
 }
 
 #[allow(clippy::missing_errors_doc)]
@@ -84,18 +259,10 @@ pub async fn prev() -> Result<()> {
 
     while app_data.state.current_history_index > 0 {
         let prev_index = app_data.state.current_history_index - 1;
-        let wallpaper = &app_data.history[prev_index];
 
-        match set_wallpaper_from_history(wallpaper).await {
+        match set_wallpaper_from_history(&app_data.history[prev_index]).await {
             Ok(()) => {
-                app_data.state.current_history_index = prev_index;
-                app_data.state.current_wallpaper_id = Some(wallpaper.id.clone());
-
-                #[allow(clippy::cast_possible_wrap)]
-                let next_run =
-                    Utc::now() + chrono::Duration::minutes(app_data.config.interval_minutes as i64);
-                app_data.state.next_run_at = next_run.to_rfc3339();
-
+                mark_applied(&mut app_data, prev_index);
                 app_data.save()?;
                 return Ok(());
             }
@@ -126,9 +293,10 @@ pub async fn set_by_index(index: usize) -> Result<()> {
         anyhow::bail!("Invalid index {} (max is {})", index, history_len - 1);
     }
 
-    let wallpaper = &app_data.history[actual_index];
-    if let Err(e) = set_wallpaper_from_history(wallpaper).await {
-        eprintln!("Warning: Failed to set wallpaper by index: {e}. Removing from history.");
+    if let Err(e) = set_wallpaper_from_history(&app_data.history[actual_index]).await {
+        // Reported through the error rather than printed, as the history
+        // browser owns the terminal.
+        tracing::warn!("Failed to set wallpaper by index: {e}. Removing from history.");
         app_data.history.remove(actual_index);
 
         if actual_index <= app_data.state.current_history_index {
@@ -137,19 +305,105 @@ pub async fn set_by_index(index: usize) -> Result<()> {
         }
 
         app_data.save()?;
-        return Err(e);
+        let message = format!("{e}. Removed it from history");
+        return Err(e.context(message));
     }
 
-    app_data.state.current_history_index = actual_index;
-    app_data.state.current_wallpaper_id = Some(wallpaper.id.clone());
+    mark_applied(&mut app_data, actual_index);
+    app_data.save()?;
 
-    #[allow(clippy::cast_possible_wrap)]
-    let next_run = Utc::now() + chrono::Duration::minutes(app_data.config.interval_minutes as i64);
-    app_data.state.next_run_at = next_run.to_rfc3339();
+    Ok(())
+}
 
+/// Position in `history` of the wallpaper numbered `index` by `wallp list`,
+/// where 0 is the most recent.
+fn history_position(app_data: &AppData, index: usize) -> Result<usize> {
+    let len = app_data.history.len();
+    if len == 0 {
+        anyhow::bail!("No wallpaper in history");
+    }
+    if index >= len {
+        anyhow::bail!("Invalid index {index} (max is {})", len - 1);
+    }
+    Ok(len - 1 - index)
+}
+
+/// Mark or unmark the wallpaper numbered `index` (0 is the most recent) as a
+/// favorite. Returns whether it is one now.
+///
+/// # Errors
+///
+/// Returns an error if the index is out of range or the config can't be saved.
+pub fn toggle_favorite(index: usize) -> Result<bool> {
+    let mut app_data = AppData::load()?;
+    let position = history_position(&app_data, index)?;
+    let wallpaper = &mut app_data.history[position];
+    wallpaper.favorite = !wallpaper.favorite;
+    let favorite = wallpaper.favorite;
     app_data.save()?;
+    Ok(favorite)
+}
 
-    Ok(())
+/// Remove the wallpaper numbered `index` (0 is the most recent) from history
+/// and delete its file. With `ban`, the photo and copies of it are also never
+/// fetched again. The wallpaper on screen stays until something else is shown.
+///
+/// # Errors
+///
+/// Returns an error if the index is out of range or the config can't be saved.
+pub fn remove_from_history(index: usize, ban: bool) -> Result<Wallpaper> {
+    let mut app_data = AppData::load()?;
+    let position = history_position(&app_data, index)?;
+    let removed = app_data.history.remove(position);
+    let mut doomed = vec![removed.clone()];
+    if ban {
+        app_data.banned.push(BannedPhoto::from(&removed));
+        let (copies, queue) = app_data
+            .queue
+            .drain(..)
+            .partition(|w| w.same_picture(&removed));
+        app_data.queue = queue;
+        doomed.extend(copies);
+    }
+
+    let wallpapers_dir = AppData::get_data_dir()?.join("wallpapers");
+    for wallpaper in &doomed {
+        let shared = app_data
+            .history
+            .iter()
+            .chain(&app_data.queue)
+            .any(|w| w.filename == wallpaper.filename);
+        if shared {
+            continue;
+        }
+        if let Err(e) = std::fs::remove_file(wallpapers_dir.join(&wallpaper.filename))
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::warn!("Failed to delete {}: {e}", wallpaper.filename);
+        }
+    }
+
+    let current = &mut app_data.state.current_history_index;
+    if position < *current || *current >= app_data.history.len() {
+        *current = current.saturating_sub(1);
+    }
+    app_data.save()?;
+    Ok(removed)
+}
+
+/// Record that the history entry at `index` is now on screen and restart the
+/// schedule from now.
+fn mark_applied(app_data: &mut AppData, index: usize) {
+    let now = Utc::now();
+    let wallpaper = &mut app_data.history[index];
+    wallpaper.last_shown_at = Some(now.to_rfc3339());
+
+    app_data.state.current_history_index = index;
+    app_data.state.current_wallpaper_id = Some(wallpaper.id.clone());
+
+    #[allow(clippy::cast_possible_wrap)]
+    let next_run = now + chrono::Duration::minutes(app_data.config.interval_minutes as i64);
+    app_data.state.next_run_at = next_run.to_rfc3339();
 }
 
 async fn set_wallpaper_from_history(wallpaper: &Wallpaper) -> Result<()> {
@@ -157,66 +411,112 @@ async fn set_wallpaper_from_history(wallpaper: &Wallpaper) -> Result<()> {
     let path = data_dir.join("wallpapers").join(&wallpaper.filename);
 
     if !path.exists() {
-        anyhow::bail!("Wallpaper file not found: {}", path.display());
+        let Some(pattern) = Pattern::from_id(&wallpaper.id) else {
+            anyhow::bail!("Wallpaper file not found: {}", path.display());
+        };
+        tracing::info!("Re-rendering generated wallpaper {}", wallpaper.id);
+        generator::save(pattern, &path).await?;
     }
 
-    match path.to_str() {
+    let app_data = AppData::load()?;
+    show(&app_data, wallpaper, &path).await
+}
+
+/// Put the wallpaper file at `path` on the desktop, with the overlay drawn on
+/// a copy when enabled, and on the lock screen.
+async fn show(app_data: &AppData, wallpaper: &Wallpaper, path: &Path) -> Result<()> {
+    let desktop = desktop_image(&app_data.config, wallpaper, path).await;
+    match desktop.to_str() {
         Some(p) => wallpaper::set_from_path(p)
             .map_err(|e| anyhow::anyhow!("Failed to set wallpaper: {e}"))?,
         None => return Err(anyhow::anyhow!("Wallpaper path contains invalid UTF-8")),
     }
 
-    let app_data = AppData::load()?;
-    set_lockscreen_from_file(&app_data, &path).await?;
-
-    Ok(())
+    set_lockscreen_from_file(app_data, path).await
 }
 
-async fn fetch_and_set_new(app_data: &mut AppData) -> Result<()> {
-    if app_data.config.unsplash_access_key.is_empty() {
-        anyhow::bail!("Unsplash Access Key is missing. Run 'wallp setup' to configure.");
-    }
-
-    if app_data.config.collections.is_empty() {
-        anyhow::bail!("No collections configured. Run 'wallp setup' to add collections.");
+/// The file to put on the desktop for `wallpaper`. A broken effect or overlay
+/// setting shouldn't stop the wallpaper from changing, so failures fall back
+/// to the plain file.
+async fn desktop_image(config: &Config, wallpaper: &Wallpaper, path: &Path) -> PathBuf {
+    let path = with_effects(&config.effects.desktop, path).await;
+    if !config.overlay.enabled {
+        return path;
     }
+    let composited = match AppData::get_data_dir() {
+        Ok(data_dir) => {
+            overlay::composite(&config.overlay, wallpaper, &path, &data_dir.join("overlay")).await
+        }
+        Err(e) => Err(e),
+    };
+    composited.unwrap_or_else(|e| {
+        tracing::warn!("Failed to draw the overlay: {e:#}");
+        path
+    })
+}
 
-    let client = UnsplashClient::new(&app_data.config.unsplash_access_key);
-    let photo = client.fetch_random(&app_data.config.collections).await?;
+async fn fetch_and_set_new(app_data: &mut AppData, quality: ImageQuality) -> Result<()> {
+    let wallpapers_dir = AppData::get_data_dir()?.join("wallpapers");
 
-    let filename = format!("wallpaper_{}.jpg", photo.id);
-    let data_dir = AppData::get_data_dir()?;
-    let wallpapers_dir = data_dir.join("wallpapers");
-    let file_path = wallpapers_dir.join(&filename);
+    let wanted = wanted_tone(app_data.config.brightness).await;
+    let access_key = AccessKey::default();
+    drop_stale_queue(app_data, &wallpapers_dir);
+    let new_wallpaper = match take_from_queue(app_data, &wallpapers_dir, wanted) {
+        Some(queued) => queued,
+        None => download_new(app_data, quality, &wallpapers_dir, wanted, &access_key).await?,
+    };
+    apply_new(app_data, new_wallpaper, &wallpapers_dir, &access_key).await?;
+    app_data.save()?;
 
-    client.download_image(&photo.urls.full, &file_path).await?;
+    Ok(())
+}
 
-    file_path.to_str().map_or_else(
-        || {
-            Err(anyhow::anyhow!(
-                "Wallpaper file path contains invalid UTF-8"
-            ))
-        },
-        |p| {
-            wallpaper::set_from_path(p).map_err(|e| anyhow::anyhow!("Failed to set wallpaper: {e}"))
-        },
-    )?;
+/// `path` with the effects in `chain` applied, or `path` itself if that fails.
+async fn with_effects(chain: &[Effect], path: &Path) -> PathBuf {
+    let processed = match AppData::get_data_dir() {
+        Ok(data_dir) => effects::processed(chain, path, &data_dir.join("effects")).await,
+        Err(e) => Err(e),
+    };
+    processed.unwrap_or_else(|e| {
+        tracing::warn!("Failed to apply effects: {e:#}");
+        path.to_path_buf()
+    })
+}
 
-    set_lockscreen_from_file(app_data, &file_path).await?;
+/// Put the freshly downloaded `new_wallpaper` on screen and record it in
+/// history (without saving `app_data`).
+async fn apply_new(
+    app_data: &mut AppData,
+    mut new_wallpaper: Wallpaper,
+    wallpapers_dir: &Path,
+    access_key: &AccessKey,
+) -> Result<()> {
+    let file_path = wallpapers_dir.join(&new_wallpaper.filename);
+    show(app_data, &new_wallpaper, &file_path).await?;
+
+    if let Some(location) = &new_wallpaper.download_location
+        && app_data.config.has_access_key()
+    {
+        let client = access_key
+            .get(&app_data.config)
+            .and_then(|key| UnsplashClient::for_config(&app_data.config, key));
+        let tracked = match client {
+            Ok(client) => client.track_download(location).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = tracked {
+            tracing::warn!("Failed to track download of {}: {e}", new_wallpaper.id);
+        }
+    }
 
-    let new_wallpaper = Wallpaper {
-        id: photo.id.clone(),
-        filename,
-        applied_at: Utc::now().to_rfc3339(),
-        title: photo.description.or(photo.alt_description),
-        author: Some(photo.user.name),
-        url: Some(photo.links.html),
-    };
+    new_wallpaper.applied_at = Utc::now().to_rfc3339();
+    let id = new_wallpaper.id.clone();
 
     app_data.history.push(new_wallpaper);
     app_data.state.current_history_index = app_data.history.len() - 1;
-    app_data.state.current_wallpaper_id = Some(photo.id);
+    app_data.state.current_wallpaper_id = Some(id);
     app_data.state.last_run_at = Utc::now().to_rfc3339();
+    app_data.state.offline_rotation = false;
 
     #[allow(clippy::cast_possible_wrap)]
     let next_run = Utc::now() + chrono::Duration::minutes(app_data.config.interval_minutes as i64);
@@ -225,10 +525,578 @@ async fn fetch_and_set_new(app_data: &mut AppData) -> Result<()> {
     if let Err(e) = app_data.cleanup_old_wallpapers() {
         eprintln!("Warning: Failed to clean up old wallpapers: {e}");
     }
+    if let Some(data_dir) = wallpapers_dir.parent() {
+        let filenames: Vec<&str> = app_data
+            .history
+            .iter()
+            .chain(&app_data.queue)
+            .map(|w| w.filename.as_str())
+            .collect();
+        let removed = effects::prune(&data_dir.join("effects"), &filenames)
+            + effects::prune(&data_dir.join("thumbnails"), &filenames);
+        if removed > 0 {
+            tracing::debug!("Removed {removed} cached effect copies and thumbnails");
+        }
+    }
+
+    Ok(())
+}
+
+/// Which sources the queue is filled from, to notice them changing.
+fn queue_sources(config: &Config) -> String {
+    sources::short_hash(&format!(
+        "{:?}|{:?}|{:?}",
+        config.collections, config.query, config.topics
+    ))
+}
+
+/// Empty the queue if it was filled before the collections, query or topics
+/// last changed, so photos from the old sources don't keep turning up.
+/// Returns whether anything changed.
+fn drop_stale_queue(app_data: &mut AppData, wallpapers_dir: &Path) -> bool {
+    let current = queue_sources(&app_data.config);
+    match app_data.state.queue_sources.as_deref() {
+        Some(filled_from) if filled_from == current => return false,
+        // Queues from before this was recorded are taken to be current.
+        None => {
+            app_data.state.queue_sources = Some(current);
+            return true;
+        }
+        Some(_) => {}
+    }
+    let stale = std::mem::take(&mut app_data.queue);
+    if !stale.is_empty() {
+        tracing::info!(
+            "Sources changed, dropping {} prefetched wallpaper(s)",
+            stale.len()
+        );
+    }
+    for wallpaper in &stale {
+        discard(app_data, wallpapers_dir, wallpaper);
+    }
+    app_data.state.queue_sources = Some(current);
+    true
+}
+
+/// Take the oldest prefetched photo of the `wanted` tone whose file is still
+/// on disk. Photos of the other tone stay queued for when the theme changes.
+fn take_from_queue(
+    app_data: &mut AppData,
+    wallpapers_dir: &Path,
+    wanted: Option<Tone>,
+) -> Option<Wallpaper> {
+    app_data.queue.retain(|queued| {
+        let present = wallpapers_dir.join(&queued.filename).exists();
+        if !present {
+            tracing::warn!(
+                "Prefetched wallpaper {} is missing, skipping",
+                queued.filename
+            );
+        }
+        present
+    });
+    let index = app_data.queue.iter().position(|w| w.matches(wanted))?;
+    Some(app_data.queue.remove(index))
+}
+
+/// Fetch a random photo from one of the configured sources and download it
+/// into `wallpapers_dir`.
+async fn download_new(
+    app_data: &mut AppData,
+    quality: ImageQuality,
+    wallpapers_dir: &Path,
+    wanted: Option<Tone>,
+    access_key: &AccessKey,
+) -> Result<Wallpaper> {
+    let source = WallpaperSource::pick(app_data)?;
+    download_from(
+        app_data,
+        &source,
+        quality,
+        wallpapers_dir,
+        wanted,
+        access_key,
+    )
+    .await
+}
+
+/// How many photos to try before settling for a copy of one already seen or
+/// one of the wrong brightness.
+const ATTEMPTS: usize = 3;
+
+/// Fetch a photo from `source` that isn't in history yet and download it into
+/// `wallpapers_dir`. Copies of photos in history or the queue, and photos that
+/// aren't of the `wanted` tone, are deleted and others tried, settling for
+/// the best of them after [`ATTEMPTS`].
+async fn download_from(
+    app_data: &mut AppData,
+    source: &WallpaperSource,
+    quality: ImageQuality,
+    wallpapers_dir: &Path,
+    wanted: Option<Tone>,
+    access_key: &AccessKey,
+) -> Result<Wallpaper> {
+    let client = http::build_client(&app_data.config.network)?;
+
+    // The best photo so far, and whether it's a copy of one already seen.
+    let mut best: Option<(Wallpaper, bool)> = None;
+    let mut tries = 0;
+    'fetch: while tries < ATTEMPTS {
+        // Mismatches are likely when a tone is wanted, so ask for enough
+        // photos up front rather than making a request per try.
+        let count = if wanted.is_some() {
+            ATTEMPTS - tries
+        } else {
+            1
+        };
+        let mut candidates = match source
+            .fetch(&app_data.config, access_key, count, quality)
+            .await
+        {
+            Ok(candidates) if !candidates.is_empty() => candidates,
+            Ok(_) if best.is_some() => break,
+            Ok(_) => anyhow::bail!("No photos returned"),
+            Err(e) if best.is_some() => {
+                tracing::warn!("Failed to fetch another photo: {e}");
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        // Sources that return a whole feed may repeat earlier pictures.
+        candidates.sort_by_key(|c| app_data.history.iter().any(|w| w.id == c.id));
+
+        for candidate in candidates.into_iter().take(ATTEMPTS - tries) {
+            tries += 1;
+            let id = candidate.id.clone();
+            let wallpaper = match download_candidate(&client, candidate, wallpapers_dir).await {
+                Ok(wallpaper) => wallpaper,
+                Err(e) if best.is_some() => {
+                    tracing::warn!("Failed to download photo {id}: {e}");
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let copy_of = app_data
+                .history
+                .iter()
+                .chain(&app_data.queue)
+                .find(|w| w.same_picture(&wallpaper))
+                .map(|w| w.id.clone());
+            let banned = app_data.is_banned(&wallpaper);
+            let matched = wallpaper.matches(wanted);
+            if banned {
+                tracing::info!("Photo {id} is banned, trying another");
+            } else if let Some(original) = &copy_of {
+                tracing::info!("Photo {id} is a copy of {original}, trying another");
+            } else if !matched {
+                tracing::info!(
+                    "Photo {id} doesn't match the {} theme, trying another",
+                    wanted.map_or("", Tone::as_str)
+                );
+            }
+
+            let duplicate = banned || copy_of.is_some();
+            let better = best.as_ref().is_none_or(|(other, other_duplicate)| {
+                if duplicate == *other_duplicate {
+                    closer(&wallpaper, other, wanted)
+                } else {
+                    !duplicate
+                }
+            });
+            if better {
+                if let Some((rejected, _)) = best.replace((wallpaper, duplicate)) {
+                    discard(app_data, wallpapers_dir, &rejected);
+                }
+            } else {
+                discard(app_data, wallpapers_dir, &wallpaper);
+            }
+            if matched && !duplicate {
+                break 'fetch;
+            }
+        }
+    }
+    let (wallpaper, duplicate) = best.context("No photos returned")?;
+    if duplicate {
+        tracing::warn!("Only found photos already seen in {tries} tries, using one again");
+    } else if !wallpaper.matches(wanted)
+        && let Some(wanted) = wanted
+    {
+        tracing::warn!(
+            "No {} photo found in {tries} tries, using the closest",
+            wanted.as_str()
+        );
+    }
+
+    if let Some(name) = source.daily_name() {
+        app_data
+            .state
+            .record_daily_fetch(name, chrono::Local::now().date_naive());
+    }
+    Ok(wallpaper)
+}
+
+/// Whether `a` is nearer than `b` to the `wanted` tone.
+fn closer(a: &Wallpaper, b: &Wallpaper, wanted: Option<Tone>) -> bool {
+    if a.matches(wanted) {
+        return true;
+    }
+    let (a, b) = (a.luminance.unwrap_or(0.5), b.luminance.unwrap_or(0.5));
+    match wanted {
+        Some(Tone::Dark) => a < b,
+        Some(Tone::Light) => a > b,
+        None => true,
+    }
+}
+
+/// Delete a rejected download, unless history or the queue use the same file.
+fn discard(app_data: &AppData, wallpapers_dir: &Path, wallpaper: &Wallpaper) {
+    let in_use = app_data
+        .history
+        .iter()
+        .chain(&app_data.queue)
+        .any(|w| w.filename == wallpaper.filename);
+    if in_use {
+        return;
+    }
+    if let Err(e) = std::fs::remove_file(wallpapers_dir.join(&wallpaper.filename)) {
+        tracing::warn!("Failed to remove {}: {e}", wallpaper.filename);
+    }
+}
+
+/// Download `candidate` into `wallpapers_dir`, returning its (not yet applied)
+/// history record.
+async fn download_candidate(
+    client: &reqwest::Client,
+    candidate: Candidate,
+    wallpapers_dir: &Path,
+) -> Result<Wallpaper> {
+    let filename = format!("wallpaper_{}.{}", candidate.id, candidate.extension());
+    let file_path = wallpapers_dir.join(&filename);
+
+    match (candidate.pattern, &candidate.local_path) {
+        (Some(pattern), _) => generator::save(pattern, &file_path).await?,
+        (None, Some(path)) => http::import_image(path, &file_path).await?,
+        (None, None) => http::download_image(client, &candidate.image_url, &file_path).await?,
+    }
+    let stats = analysis::analyze(&file_path)
+        .await
+        .inspect_err(|e| tracing::warn!("Failed to measure {filename}: {e:#}"))
+        .ok();
+    if let Some(thumbnails_dir) = wallpapers_dir.parent().map(|dir| dir.join("thumbnails"))
+        && let Err(e) = preview::thumbnail(&file_path, &thumbnails_dir).await
+    {
+        tracing::warn!("Failed to make a thumbnail of {filename}: {e:#}");
+    }
+
+    Ok(Wallpaper {
+        id: candidate.id,
+        filename,
+        applied_at: String::new(),
+        title: candidate.title,
+        author: candidate.author,
+        url: candidate.url,
+        author_url: candidate.author_url,
+        download_location: candidate.download_location,
+        source: Some(candidate.source),
+        copyright: candidate.copyright,
+        explanation: candidate.explanation,
+        luminance: stats.map(|s| s.luminance),
+        contrast: stats.map(|s| s.contrast),
+        image_hash: stats.map(|s| s.hash),
+        ..Default::default()
+    })
+}
+
+/// Top the prefetch queue back up to `prefetch_count` photos from a random
+/// source, fetching the metadata for all of them in as few API requests as the
+/// source allows. Returns how many photos were added.
+///
+/// # Errors
+///
+/// Returns an error if the config cannot be loaded or saved, or if the
+/// source's request fails. Individual download failures are only logged.
+pub async fn refill_queue() -> Result<usize> {
+    let mut app_data = AppData::load()?;
+    let wallpapers_dir = AppData::get_data_dir()?.join("wallpapers");
+    if drop_stale_queue(&mut app_data, &wallpapers_dir) {
+        app_data.save()?;
+    }
+    // Photos of the other tone wait for the theme to change back, but don't
+    // count towards the queue meanwhile.
+    let tone = wanted_tone(app_data.config.brightness).await;
+    let queued = app_data.queue.iter().filter(|w| w.matches(tone)).count();
+    let wanted = app_data.config.prefetch_count.saturating_sub(queued);
+    if wanted == 0 {
+        return Ok(0);
+    }
+
+    let source = WallpaperSource::pick(&app_data)?;
+    let candidates = source
+        .fetch(
+            &app_data.config,
+            &AccessKey::default(),
+            wanted,
+            ImageQuality::Full,
+        )
+        .await?;
+    let client = http::build_client(&app_data.config.network)?;
+
+    let mut downloaded = Vec::new();
+    for candidate in candidates {
+        let id = candidate.id.clone();
+        let known = app_data.queue.iter().any(|w| w.id == id)
+            || app_data.history.iter().any(|w| w.id == id)
+            || app_data.banned.iter().any(|b| b.id == id);
+        if known {
+            continue;
+        }
+        let wallpaper = match download_candidate(&client, candidate, &wallpapers_dir).await {
+            Ok(wallpaper) => wallpaper,
+            Err(e) => {
+                tracing::warn!("Failed to prefetch photo {id}: {e}");
+                continue;
+            }
+        };
+        let copy = app_data
+            .history
+            .iter()
+            .chain(&app_data.queue)
+            .chain(&downloaded)
+            .any(|w| w.same_picture(&wallpaper));
+        if copy || app_data.is_banned(&wallpaper) || !wallpaper.matches(tone) {
+            tracing::debug!(
+                "Not prefetching photo {id}, it's a copy, banned or doesn't match the theme"
+            );
+            discard(&app_data, &wallpapers_dir, &wallpaper);
+        } else {
+            downloaded.push(wallpaper);
+        }
+    }
 
+    // Reload so anything applied while we were downloading isn't overwritten.
+    let mut app_data = AppData::load()?;
+    if let Some(name) = source.daily_name() {
+        app_data
+            .state
+            .record_daily_fetch(name, chrono::Local::now().date_naive());
+    }
+    let mut added = 0;
+    for wallpaper in downloaded {
+        if !app_data.queue.iter().any(|w| w.id == wallpaper.id) {
+            app_data.queue.push(wallpaper);
+            added += 1;
+        }
+    }
     app_data.save()?;
 
-    Ok(())
+    Ok(added)
+}
+
+/// Wallpapers in history or the queue that show the same picture.
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    /// The one that stays: the wallpaper on screen, or else a favorite, or
+    /// else the largest image.
+    pub kept: Wallpaper,
+    /// The others, except favorites, which are never removed.
+    pub copies: Vec<Wallpaper>,
+}
+
+/// Find wallpapers in history and the queue that are copies of each other,
+/// first hashing any downloaded before hashes were recorded. With `remove`,
+/// the copies' files are deleted and they're dropped from history and the
+/// queue.
+///
+/// # Errors
+///
+/// Returns an error if the config cannot be loaded or saved.
+pub async fn dedupe(remove: bool) -> Result<Vec<DuplicateGroup>> {
+    let mut app_data = AppData::load()?;
+    let wallpapers_dir = AppData::get_data_dir()?.join("wallpapers");
+
+    let mut measured = false;
+    for wallpaper in app_data.history.iter_mut().chain(&mut app_data.queue) {
+        let path = wallpapers_dir.join(&wallpaper.filename);
+        if wallpaper.image_hash.is_some() || !path.exists() {
+            continue;
+        }
+        match analysis::analyze(&path).await {
+            Ok(stats) => {
+                wallpaper.image_hash = Some(stats.hash);
+                wallpaper.luminance.get_or_insert(stats.luminance);
+                wallpaper.contrast.get_or_insert(stats.contrast);
+                measured = true;
+            }
+            Err(e) => tracing::warn!("Failed to measure {}: {e:#}", wallpaper.filename),
+        }
+    }
+
+    // Entries sharing a file (the same photo shown twice) aren't copies.
+    let entries: Vec<&Wallpaper> = app_data.history.iter().chain(&app_data.queue).collect();
+    let fingerprints: Vec<Option<analysis::Fingerprint>> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            let first = entries[..i]
+                .iter()
+                .all(|other| other.filename != w.filename);
+            w.fingerprint()
+                .filter(|_| first && wallpapers_dir.join(&w.filename).exists())
+        })
+        .collect();
+    let current = app_data.state.current_wallpaper_id.as_deref();
+    let groups: Vec<DuplicateGroup> = analysis::duplicate_groups(&fingerprints)
+        .into_iter()
+        .filter_map(|members| {
+            let members = members.into_iter().map(|i| entries[i].clone()).collect();
+            split_copies(members, current, |w| {
+                pixels(&wallpapers_dir.join(&w.filename))
+            })
+        })
+        .collect();
+
+    if remove && !groups.is_empty() {
+        let doomed: Vec<&str> = groups
+            .iter()
+            .flat_map(|g| &g.copies)
+            .map(|w| w.filename.as_str())
+            .collect();
+        for filename in &doomed {
+            if let Err(e) = std::fs::remove_file(wallpapers_dir.join(filename)) {
+                tracing::warn!("Failed to remove {filename}: {e}");
+            }
+        }
+        let current_id = app_data
+            .history
+            .get(app_data.state.current_history_index)
+            .map(|w| w.id.clone());
+        app_data
+            .history
+            .retain(|w| !doomed.contains(&w.filename.as_str()));
+        app_data
+            .queue
+            .retain(|w| !doomed.contains(&w.filename.as_str()));
+        app_data.state.current_history_index = current_id
+            .and_then(|id| app_data.history.iter().position(|w| w.id == id))
+            .unwrap_or_else(|| app_data.history.len().saturating_sub(1));
+    }
+    if measured || (remove && !groups.is_empty()) {
+        app_data.save()?;
+    }
+
+    Ok(groups)
+}
+
+/// Choose which of `members`, copies of one picture, to keep (see
+/// [`DuplicateGroup`]), sizing images with `pixels`. `None` if all the
+/// others are favorites, leaving nothing to remove.
+fn split_copies(
+    mut members: Vec<Wallpaper>,
+    current: Option<&str>,
+    pixels: impl Fn(&Wallpaper) -> u64,
+) -> Option<DuplicateGroup> {
+    let keep = members
+        .iter()
+        .position(|w| Some(w.id.as_str()) == current)
+        .or_else(|| (0..members.len()).max_by_key(|&i| (members[i].favorite, pixels(&members[i]))))
+        .unwrap_or(0);
+    let kept = members.remove(keep);
+    members.retain(|w| !w.favorite);
+    (!members.is_empty()).then_some(DuplicateGroup {
+        kept,
+        copies: members,
+    })
+}
+
+/// Pixel count of the image at `path`, or 0 if it can't be read.
+fn pixels(path: &Path) -> u64 {
+    image::image_dimensions(path).map_or(0, |(w, h)| u64::from(w) * u64::from(h))
+}
+
+/// How long cached custom collection details are trusted before `wallp settings`
+/// looks them up again.
+const COLLECTION_CHECK_HOURS: i64 = 24;
+
+/// Look up a custom collection on Unsplash and record what was found.
+///
+/// # Errors
+///
+/// Returns an error if the request fails for any reason other than the
+/// collection not existing.
+pub async fn lookup_collection(client: &UnsplashClient, id: &str) -> Result<CustomCollection> {
+    let found = client.fetch_collection(id).await?;
+    Ok(custom_collection_from(id, found, Utc::now()))
+}
+
+fn custom_collection_from(
+    id: &str,
+    found: Option<UnsplashCollection>,
+    now: chrono::DateTime<Utc>,
+) -> CustomCollection {
+    let checked_at = Some(now.to_rfc3339());
+    match found {
+        Some(collection) => CustomCollection {
+            id: id.to_string(),
+            title: collection.title,
+            total_photos: Some(collection.total_photos),
+            cover_photo_url: collection
+                .cover_photo
+                .map(|c| c.urls.regular.unwrap_or(c.urls.full)),
+            unavailable: collection.private,
+            checked_at,
+        },
+        None => CustomCollection {
+            id: id.to_string(),
+            title: format!("Collection {id}"),
+            unavailable: true,
+            checked_at,
+            ..Default::default()
+        },
+    }
+}
+
+/// Re-check custom collections whose cached details are more than a day old.
+/// Entries that were never found keep their existing title.
+///
+/// # Errors
+///
+/// Returns an error if the config cannot be loaded or saved, or if a lookup
+/// fails (e.g. offline); entries checked before the failure are still saved.
+pub async fn refresh_custom_collections(access_key: &AccessKey) -> Result<()> {
+    let mut app_data = AppData::load()?;
+    if !app_data.config.has_access_key() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let max_age = chrono::Duration::hours(COLLECTION_CHECK_HOURS);
+    let client = UnsplashClient::for_config(&app_data.config, access_key.get(&app_data.config)?)?;
+
+    let mut result = Ok(());
+    let mut changed = false;
+    for collection in &mut app_data.config.custom_collections {
+        if !collection.is_stale(now, max_age) {
+            continue;
+        }
+        match lookup_collection(&client, &collection.id).await {
+            Ok(mut fresh) => {
+                if fresh.unavailable && fresh.total_photos.is_none() {
+                    fresh.title = std::mem::take(&mut collection.title);
+                }
+                *collection = fresh;
+                changed = true;
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+
+    if changed {
+        app_data.save()?;
+    }
+    result
 }
 
 #[allow(clippy::missing_errors_doc)]
@@ -293,6 +1161,7 @@ mod tests {
             title: Some("Test Title".to_string()),
             author: Some("Test Author".to_string()),
             url: Some("https://example.com".to_string()),
+            ..Default::default()
         });
 
         app_data.state.current_history_index = 0;
@@ -323,6 +1192,7 @@ mod tests {
                 title: None,
                 author: None,
                 url: None,
+                ..Default::default()
             });
         }
         app_data.state.current_history_index = 2;
@@ -344,6 +1214,7 @@ mod tests {
             title: None,
             author: None,
             url: None,
+            ..Default::default()
         });
 
         app_data.state.current_history_index = 0;
@@ -365,6 +1236,7 @@ mod tests {
                 title: None,
                 author: None,
                 url: None,
+                ..Default::default()
             });
         }
         app_data.state.current_history_index = 1;
@@ -373,4 +1245,282 @@ mod tests {
         assert!(app_data.state.current_history_index < app_data.history.len() - 1);
         Ok(())
     }
+
+    fn wallpaper_shown_at(id: &str, shown_at: &str) -> Wallpaper {
+        Wallpaper {
+            id: id.to_string(),
+            filename: format!("{id}.jpg"),
+            applied_at: shown_at.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pick_offline_candidate_least_recent() {
+        let mut history = vec![
+            wallpaper_shown_at("a", "2024-01-03T00:00:00Z"),
+            wallpaper_shown_at("b", "2024-01-01T00:00:00Z"),
+            wallpaper_shown_at("c", "2024-01-02T00:00:00Z"),
+        ];
+        assert_eq!(
+            pick_offline_candidate(&history, 0, OfflineFallback::LeastRecent, None),
+            Some(1)
+        );
+
+        // Re-showing "b" makes "c" the least recently shown.
+        history[1].last_shown_at = Some("2024-01-04T00:00:00Z".to_string());
+        assert_eq!(
+            pick_offline_candidate(&history, 0, OfflineFallback::LeastRecent, None),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_pick_offline_candidate_skips_current() {
+        let history = vec![
+            wallpaper_shown_at("a", "2024-01-01T00:00:00Z"),
+            wallpaper_shown_at("b", "2024-01-02T00:00:00Z"),
+        ];
+        assert_eq!(
+            pick_offline_candidate(&history, 0, OfflineFallback::LeastRecent, None),
+            Some(1)
+        );
+        for _ in 0..20 {
+            assert_eq!(
+                pick_offline_candidate(&history, 1, OfflineFallback::Random, None),
+                Some(0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_pick_offline_candidate_edge_cases() {
+        let single = vec![wallpaper_shown_at("a", "2024-01-01T00:00:00Z")];
+        assert_eq!(
+            pick_offline_candidate(&single, 0, OfflineFallback::Random, None),
+            Some(0)
+        );
+        assert_eq!(
+            pick_offline_candidate(&single, 0, OfflineFallback::Off, None),
+            None
+        );
+        assert_eq!(
+            pick_offline_candidate(&[], 0, OfflineFallback::LeastRecent, None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_offline_and_local_rotation_prefer_wanted_tone() {
+        let shaded = |id: &str, luminance: f32| Wallpaper {
+            luminance: Some(luminance),
+            ..wallpaper_shown_at(id, "2024-01-01T00:00:00Z")
+        };
+        let history = vec![
+            shaded("a", 0.2),
+            shaded("b", 0.8),
+            shaded("c", 0.1),
+            shaded("d", 0.9),
+        ];
+        let dark = Some(Tone::Dark);
+        for _ in 0..20 {
+            assert_eq!(
+                pick_offline_candidate(&history, 0, OfflineFallback::Random, dark),
+                Some(2)
+            );
+        }
+        assert_eq!(next_local(&history, 0, dark), 2);
+        assert_eq!(next_local(&history, 2, dark), 0);
+        assert_eq!(next_local(&history, 1, None), 2);
+
+        // With nothing of the wanted tone left, any other wallpaper will do.
+        let light = vec![shaded("a", 0.8), shaded("b", 0.9)];
+        assert_eq!(
+            pick_offline_candidate(&light, 0, OfflineFallback::LeastRecent, dark),
+            Some(1)
+        );
+        assert_eq!(next_local(&light, 1, dark), 0);
+    }
+
+    #[tokio::test]
+    async fn test_is_network_error() -> anyhow::Result<()> {
+        let error = anyhow::anyhow!("Unsplash API Error 401 Unauthorized");
+        assert!(!is_network_error(&error));
+
+        // Nothing listens on a port that was just freed.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")?
+            .local_addr()?
+            .port();
+        let refused = reqwest::Client::new()
+            .get(format!("http://127.0.0.1:{port}/"))
+            .send()
+            .await
+            .expect_err("connection should be refused");
+        let error = anyhow::Error::new(refused).context("Failed to fetch a photo");
+        assert!(is_network_error(&error));
+        Ok(())
+    }
+
+    #[test]
+    fn test_take_from_queue_skips_missing_files() -> anyhow::Result<()> {
+        let (temp_dir, mut app_data) = create_test_env()?;
+        let wallpapers_dir = temp_dir.path().join("wallp").join("wallpapers");
+        fs::write(wallpapers_dir.join("present.jpg"), "data")?;
+
+        app_data.queue.push(Wallpaper {
+            id: "missing".to_string(),
+            filename: "missing.jpg".to_string(),
+            ..Default::default()
+        });
+        app_data.queue.push(Wallpaper {
+            id: "present".to_string(),
+            filename: "present.jpg".to_string(),
+            ..Default::default()
+        });
+
+        let taken = take_from_queue(&mut app_data, &wallpapers_dir, None);
+        assert_eq!(taken.map(|w| w.id), Some("present".to_string()));
+        assert!(app_data.queue.is_empty());
+        assert!(take_from_queue(&mut app_data, &wallpapers_dir, None).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_dropped_when_sources_change() -> anyhow::Result<()> {
+        let (temp_dir, mut app_data) = create_test_env()?;
+        let wallpapers_dir = temp_dir.path().join("wallp").join("wallpapers");
+        fs::write(wallpapers_dir.join("queued.jpg"), "data")?;
+        app_data.queue.push(Wallpaper {
+            id: "queued".to_string(),
+            filename: "queued.jpg".to_string(),
+            ..Default::default()
+        });
+
+        // An existing queue is adopted, then kept while the sources stay put.
+        assert!(drop_stale_queue(&mut app_data, &wallpapers_dir));
+        assert!(!drop_stale_queue(&mut app_data, &wallpapers_dir));
+        assert_eq!(app_data.queue.len(), 1);
+
+        app_data.config.topics = vec!["nature".to_string()];
+        assert!(drop_stale_queue(&mut app_data, &wallpapers_dir));
+        assert!(app_data.queue.is_empty());
+        assert!(!wallpapers_dir.join("queued.jpg").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_take_from_queue_keeps_other_tone_queued() -> anyhow::Result<()> {
+        let (temp_dir, mut app_data) = create_test_env()?;
+        let wallpapers_dir = temp_dir.path().join("wallp").join("wallpapers");
+        for (id, luminance) in [("bright", 0.8), ("dim", 0.2)] {
+            fs::write(wallpapers_dir.join(format!("{id}.jpg")), "data")?;
+            app_data.queue.push(Wallpaper {
+                id: id.to_string(),
+                filename: format!("{id}.jpg"),
+                luminance: Some(luminance),
+                ..Default::default()
+            });
+        }
+
+        let taken = take_from_queue(&mut app_data, &wallpapers_dir, Some(Tone::Dark));
+        assert_eq!(taken.map(|w| w.id), Some("dim".to_string()));
+        assert!(take_from_queue(&mut app_data, &wallpapers_dir, Some(Tone::Dark)).is_none());
+        assert_eq!(app_data.queue.len(), 1);
+        let taken = take_from_queue(&mut app_data, &wallpapers_dir, Some(Tone::Light));
+        assert_eq!(taken.map(|w| w.id), Some("bright".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_closer_to_wanted_tone() {
+        let shade = |luminance| Wallpaper {
+            luminance: Some(luminance),
+            ..Default::default()
+        };
+        let (gray, white) = (shade(0.6), shade(0.9));
+        assert!(closer(&gray, &white, Some(Tone::Dark)));
+        assert!(!closer(&white, &gray, Some(Tone::Dark)));
+        assert!(closer(&shade(0.3), &gray, Some(Tone::Dark)));
+        assert!(closer(&Wallpaper::default(), &gray, Some(Tone::Dark)));
+        assert!(closer(&shade(0.4), &shade(0.1), Some(Tone::Light)));
+    }
+
+    #[test]
+    fn test_split_copies_spares_favorites() {
+        let wallpaper = |id: &str, favorite: bool| Wallpaper {
+            id: id.to_string(),
+            filename: format!("wallpaper_{id}.jpg"),
+            favorite,
+            ..Default::default()
+        };
+        let size = |w: &Wallpaper| if w.id == "big" { 4 } else { 1 };
+
+        // The largest goes unless a favorite is among them.
+        let group = split_copies(
+            vec![wallpaper("small", false), wallpaper("big", false)],
+            None,
+            size,
+        );
+        assert_eq!(group.map(|g| g.kept.id), Some("big".to_string()));
+        let group = split_copies(
+            vec![wallpaper("big", false), wallpaper("small", true)],
+            None,
+            size,
+        );
+        assert_eq!(group.map(|g| g.kept.id), Some("small".to_string()));
+
+        // The one on screen stays, and other favorites aren't copies.
+        let group = split_copies(
+            vec![
+                wallpaper("big", false),
+                wallpaper("loved", true),
+                wallpaper("shown", false),
+            ],
+            Some("shown"),
+            size,
+        );
+        let Some(group) = group else {
+            panic!("expected a group");
+        };
+        assert_eq!(group.kept.id, "shown");
+        let copies: Vec<&str> = group.copies.iter().map(|w| w.id.as_str()).collect();
+        assert_eq!(copies, vec!["big"]);
+
+        // Nothing to remove when the rest are favorites.
+        assert!(
+            split_copies(
+                vec![wallpaper("big", true), wallpaper("small", true)],
+                None,
+                size
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_custom_collection_from_api() -> anyhow::Result<()> {
+        let now = Utc::now();
+        let found: UnsplashCollection = serde_json::from_str(
+            r#"{"title": "Fog", "total_photos": 12, "private": false,
+                "cover_photo": {"urls": {"full": "https://example.com/full.jpg",
+                                         "regular": "https://example.com/regular.jpg"}}}"#,
+        )?;
+
+        let collection = custom_collection_from("123", Some(found), now);
+        assert_eq!(collection.id, "123");
+        assert_eq!(collection.title, "Fog");
+        assert_eq!(collection.total_photos, Some(12));
+        assert_eq!(
+            collection.cover_photo_url.as_deref(),
+            Some("https://example.com/regular.jpg")
+        );
+        assert!(!collection.unavailable);
+        assert_eq!(collection.checked_at, Some(now.to_rfc3339()));
+
+        let missing = custom_collection_from("404", None, now);
+        assert!(missing.unavailable);
+        assert_eq!(missing.title, "Collection 404");
+        assert!(missing.problem().is_some());
+        Ok(())
+    }
 }
@@ -0,0 +1,261 @@
+//! Machine-readable output for `--json`.
+//!
+//! Every command prints exactly one JSON object on stdout. Fields are only
+//! ever added, never renamed or removed, so scripts can rely on them. Times
+//! are RFC 3339 strings and wallpaper numbers count back from the most
+//! recent (0), as in `wallp set`.
+
+use crate::config::{AppData, Wallpaper};
+use anyhow::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// How command results are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Format {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// One JSON object per command.
+    Json,
+}
+
+/// Print `value` as pretty JSON on stdout.
+///
+/// # Errors
+///
+/// Returns an error if `value` can't be serialized.
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Printed instead of the result when a command fails.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub error: String,
+}
+
+/// A wallpaper in history.
+#[derive(Debug, Serialize)]
+pub struct WallpaperReport {
+    pub number: usize,
+    pub id: String,
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub source: Option<String>,
+    pub url: Option<String>,
+    pub author_url: Option<String>,
+    pub copyright: Option<String>,
+    pub explanation: Option<String>,
+    pub applied_at: String,
+    pub last_shown_at: String,
+    pub favorite: bool,
+    /// `"dark"` or `"light"`, once measured.
+    pub brightness: Option<&'static str>,
+    pub luminance: Option<f32>,
+    pub contrast: Option<f32>,
+    pub current: bool,
+}
+
+impl WallpaperReport {
+    /// Describe the wallpaper at `position` in `app_data.history`.
+    #[must_use]
+    pub fn new(
+        app_data: &AppData,
+        position: usize,
+        wallpapers_dir: &std::path::Path,
+    ) -> Option<Self> {
+        let w: &Wallpaper = app_data.history.get(position)?;
+        Some(Self {
+            number: app_data.history.len() - 1 - position,
+            id: w.id.clone(),
+            path: wallpapers_dir.join(&w.filename),
+            title: w.title.clone(),
+            author: w.author.clone(),
+            source: w.source.clone(),
+            url: w.url.clone(),
+            author_url: w.author_url.clone(),
+            copyright: w.copyright.clone(),
+            explanation: w.explanation.clone(),
+            applied_at: w.applied_at.clone(),
+            last_shown_at: w.last_shown().to_string(),
+            favorite: w.favorite,
+            brightness: w.tone().map(crate::analysis::Tone::as_str),
+            luminance: w.luminance,
+            contrast: w.contrast,
+            current: position == app_data.state.current_history_index,
+        })
+    }
+
+    /// Describe every wallpaper in history, most recent first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data directory can't be found.
+    pub fn all(app_data: &AppData) -> Result<Vec<Self>> {
+        let wallpapers_dir = AppData::get_data_dir()?.join("wallpapers");
+        Ok((0..app_data.history.len())
+            .rev()
+            .filter_map(|position| Self::new(app_data, position, &wallpapers_dir))
+            .collect())
+    }
+
+    /// Describe the wallpaper on screen, if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data directory can't be found.
+    pub fn current(app_data: &AppData) -> Result<Option<Self>> {
+        let wallpapers_dir = AppData::get_data_dir()?.join("wallpapers");
+        let position = app_data
+            .state
+            .current_history_index
+            .min(app_data.history.len().saturating_sub(1));
+        Ok(Self::new(app_data, position, &wallpapers_dir))
+    }
+}
+
+/// `wallp status`.
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    /// `"running"`, `"paused"` (until `paused_until`) or `"stopped"`.
+    pub state: &'static str,
+    pub paused_until: Option<String>,
+    pub next_run_at: String,
+    pub last_run_at: String,
+    pub prefetched: usize,
+    pub prefetch_count: usize,
+    pub current: Option<WallpaperReport>,
+}
+
+/// `wallp list`.
+#[derive(Debug, Serialize)]
+pub struct ListReport {
+    pub wallpapers: Vec<WallpaperReport>,
+}
+
+/// `wallp info`, and the result of commands that change the wallpaper.
+#[derive(Debug, Serialize)]
+pub struct ActionReport {
+    /// The command that ran, e.g. `"next"`.
+    pub action: &'static str,
+    /// The wallpaper on screen afterwards.
+    pub wallpaper: Option<WallpaperReport>,
+}
+
+/// `wallp pause` and `wallp resume`.
+#[derive(Debug, Serialize)]
+pub struct SchedulerReport {
+    pub action: &'static str,
+    pub running: bool,
+    /// When a timed pause ends.
+    pub paused_until: Option<String>,
+}
+
+/// One entry of `collections` in `wallp settings`.
+#[derive(Debug, Serialize)]
+pub struct CollectionReport {
+    pub id: String,
+    pub description: String,
+    /// Why the collection can't be used at the moment, if it can't.
+    pub problem: Option<&'static str>,
+}
+
+/// `wallp settings`.
+#[derive(Debug, Serialize)]
+pub struct SettingsReport {
+    /// The Unsplash key with all but its last four characters masked.
+    pub api_key: Option<String>,
+    pub api_key_source: String,
+    pub wallhaven_api_key_set: bool,
+    pub collections: Vec<CollectionReport>,
+    pub query: Option<String>,
+    pub topics: Vec<String>,
+    pub username: Option<String>,
+    pub content_filter: &'static str,
+    /// The proxy URL with any password removed.
+    pub proxy: Option<String>,
+    pub no_proxy: Vec<String>,
+    pub ca_bundle: Option<PathBuf>,
+    pub interval_minutes: u64,
+    /// `null` keeps wallpapers forever.
+    pub retention_days: Option<u64>,
+    pub autostart: bool,
+    pub in_path: bool,
+    pub lockscreen: LockscreenReport,
+}
+
+/// `wallp lockscreen`.
+#[derive(Debug, Serialize)]
+pub struct LockscreenReport {
+    pub enabled: bool,
+    /// Empty when the lock screen uses the desktop collections.
+    pub collections: Vec<String>,
+}
+
+/// `wallp dedupe`.
+#[derive(Debug, Serialize)]
+pub struct DedupeReport {
+    pub removed: bool,
+    pub groups: Vec<DuplicateGroupReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroupReport {
+    pub kept: String,
+    pub copies: Vec<String>,
+}
+
+/// `wallp config get`, `set` and `unset`: the setting's value afterwards.
+#[derive(Debug, Serialize)]
+pub struct ConfigValueReport {
+    pub key: String,
+    pub value: serde_json::Value,
+}
+
+/// `wallp config list`, with secrets masked.
+#[derive(Debug, Serialize)]
+pub struct ConfigListReport {
+    pub settings: serde_json::Map<String, serde_json::Value>,
+}
+
+/// `wallp folder` and `wallp config`, which only print the path with `--json`.
+#[derive(Debug, Serialize)]
+pub struct PathReport {
+    pub path: PathBuf,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wallpaper_numbers_count_back_from_newest() {
+        let mut app_data = AppData::default();
+        for id in ["a", "b", "c"] {
+            app_data.history.push(Wallpaper {
+                id: id.to_string(),
+                filename: format!("wallpaper_{id}.jpg"),
+                luminance: Some(0.2),
+                ..Default::default()
+            });
+        }
+        app_data.state.current_history_index = 1;
+
+        let dir = std::path::Path::new("/w");
+        let report = WallpaperReport::new(&app_data, 0, dir).expect("report");
+        assert_eq!(report.number, 2);
+        assert!(!report.current);
+        let report = WallpaperReport::new(&app_data, 1, dir).expect("report");
+        assert_eq!(report.number, 1);
+        assert!(report.current);
+        assert_eq!(report.path, dir.join("wallpaper_b.jpg"));
+
+        let json = serde_json::to_value(&report).expect("json");
+        assert_eq!(json["id"], "b");
+        assert_eq!(json["brightness"], "dark");
+        assert_eq!(json["title"], serde_json::Value::Null);
+    }
+}
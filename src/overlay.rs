@@ -0,0 +1,455 @@
+//! Text drawn onto a copy of the wallpaper: the photo's attribution, today's
+//! date or a quote.
+
+use crate::config::{OverlayConfig, OverlayPosition, OverlayText, Wallpaper};
+use ab_glyph::{Font, FontVec, PxScale, PxScaleFont, ScaleFont};
+use anyhow::{Context, Result};
+use image::{Rgb, RgbImage, Rgba};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Fonts tried, in order, when `overlay.font` is unset.
+const SYSTEM_FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/noto/NotoSans-Regular.ttf",
+    "/usr/share/fonts/noto/NotoSans-Regular.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    r"C:\Windows\Fonts\segoeui.ttf",
+    r"C:\Windows\Fonts\arial.ttf",
+];
+
+/// Lines wider than this share of the image are wrapped.
+const MAX_TEXT_WIDTH: f32 = 0.45;
+
+/// The first of the usual system fonts that is installed.
+#[must_use]
+pub fn system_font() -> Option<PathBuf> {
+    SYSTEM_FONTS
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.is_file())
+}
+
+/// What the overlay says for `wallpaper` on `today`, or `None` when there's
+/// nothing to say (e.g. a photo with neither title nor author).
+///
+/// # Errors
+///
+/// Returns an error if the quotes file is missing, unreadable or empty, or the
+/// date format is invalid.
+pub fn text_for(
+    config: &OverlayConfig,
+    wallpaper: &Wallpaper,
+    today: chrono::NaiveDate,
+) -> Result<Option<String>> {
+    match config.text {
+        OverlayText::Attribution => {
+            let title = wallpaper.title.as_deref().map(str::trim);
+            let author = wallpaper.author.as_deref().map(str::trim);
+            Ok(match (title.filter(|t| !t.is_empty()), author) {
+                (Some(title), Some(author)) if !author.is_empty() => {
+                    Some(format!("{title} by {author}"))
+                }
+                (Some(title), _) => Some(title.to_string()),
+                (None, Some(author)) if !author.is_empty() => Some(format!("Photo by {author}")),
+                (None, _) => None,
+            })
+        }
+        OverlayText::Date => {
+            let mut text = String::new();
+            write!(text, "{}", today.format(&config.date_format))
+                .map_err(|_| anyhow::anyhow!("Invalid date_format '{}'", config.date_format))?;
+            Ok(Some(text))
+        }
+        OverlayText::Quote => {
+            let path = config
+                .quotes_file
+                .as_deref()
+                .context("overlay.quotes_file is not set")?;
+            let quotes = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read quotes from {}", path.display()))?;
+            let quotes = split_quotes(&quotes);
+            if quotes.is_empty() {
+                anyhow::bail!("No quotes in {}", path.display());
+            }
+            // The same photo always gets the same quote.
+            let hash = crate::sources::stable_hash(&wallpaper.id);
+            let index = usize::try_from(hash % quotes.len() as u64).unwrap_or_default();
+            Ok(Some(quotes[index].clone()))
+        }
+    }
+}
+
+/// Quotes separated by blank lines; a quote may span several lines.
+fn split_quotes(text: &str) -> Vec<String> {
+    let mut quotes = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim).chain([""]) {
+        if line.is_empty() {
+            if !current.is_empty() {
+                quotes.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    quotes
+}
+
+/// Draw the configured text onto a copy of the image at `source`, saved in
+/// `dir`, and return the copy's path. Returns `source` itself when there's no
+/// text for this wallpaper. Earlier copies in `dir` are removed.
+///
+/// # Errors
+///
+/// Returns an error if the text can't be worked out, the font or image can't
+/// be loaded, a color is invalid, or the copy can't be saved.
+pub async fn composite(
+    config: &OverlayConfig,
+    wallpaper: &Wallpaper,
+    source: &Path,
+    dir: &Path,
+) -> Result<PathBuf> {
+    let Some(text) = text_for(config, wallpaper, chrono::Local::now().date_naive())? else {
+        return Ok(source.to_path_buf());
+    };
+
+    let stem = source
+        .file_stem()
+        .context("Wallpaper path has no file name")?
+        .to_string_lossy();
+    let dest = dir.join(format!("{stem}-overlay.jpg"));
+
+    let config = config.clone();
+    let source = source.to_path_buf();
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<PathBuf> {
+        let font_path = config
+            .font
+            .clone()
+            .or_else(system_font)
+            .context("No font found for the overlay; set overlay.font")?;
+        let font = FontVec::try_from_vec(
+            std::fs::read(&font_path)
+                .with_context(|| format!("Failed to read font {}", font_path.display()))?,
+        )
+        .map_err(|_| anyhow::anyhow!("{} is not a usable font", font_path.display()))?;
+
+        let color = parse_color(&config.color)?;
+        let scrim = config.scrim.as_deref().map(parse_color).transpose()?;
+        let mut image = image::open(&source)
+            .with_context(|| format!("Failed to open {}", source.display()))?
+            .into_rgb8();
+        draw_text(&mut image, &font, &text, &config, color, scrim);
+
+        std::fs::create_dir_all(&dir).context("Failed to create overlay directory")?;
+        crate::http::write_jpeg(&image, &dest, 92)
+            .context("Failed to save wallpaper with overlay")?;
+
+        for entry in std::fs::read_dir(&dir)?.flatten() {
+            if entry.path() != dest {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+        Ok(dest)
+    })
+    .await
+    .context("Overlay task failed")?
+}
+
+/// Parse `#rrggbb` or `#rrggbbaa`.
+pub(crate) fn parse_color(value: &str) -> Result<Rgba<u8>> {
+    let hex = value.trim().trim_start_matches('#');
+    let invalid = || format!("Invalid color '{value}', expected #rrggbb or #rrggbbaa");
+    if !hex.is_ascii() || !(hex.len() == 6 || hex.len() == 8) {
+        anyhow::bail!(invalid());
+    }
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).with_context(invalid);
+    let alpha = if hex.len() == 8 { byte(6)? } else { 255 };
+    Ok(Rgba([byte(0)?, byte(2)?, byte(4)?, alpha]))
+}
+
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn draw_text(
+    image: &mut RgbImage,
+    font: &FontVec,
+    text: &str,
+    config: &OverlayConfig,
+    color: Rgba<u8>,
+    scrim: Option<Rgba<u8>>,
+) {
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let size = (height * config.font_size / 100.0).max(8.0);
+    let font = font.as_scaled(PxScale::from(size));
+    let measure = |line: &str| line_width(&font, line);
+
+    let lines = wrap(text, width * MAX_TEXT_WIDTH, measure);
+    let line_height = font.height() + font.line_gap();
+    let text_width = lines.iter().map(|l| measure(l)).fold(0.0, f32::max);
+    let text_height = line_height * lines.len() as f32 - font.line_gap();
+
+    let padding = size * 0.5;
+    let box_size = (text_width + 2.0 * padding, text_height + 2.0 * padding);
+    let (left, top) = place(config.position, (width, height), box_size, size * 1.5);
+
+    if let Some(scrim) = scrim {
+        for y in top as u32..((top + box_size.1) as u32).min(image.height()) {
+            for x in left as u32..((left + box_size.0) as u32).min(image.width()) {
+                blend(image.get_pixel_mut(x, y), scrim, 1.0);
+            }
+        }
+    }
+
+    for (i, line) in lines.iter().enumerate() {
+        // Lines line up with the side of the screen the text sits on.
+        let slack = text_width - measure(line);
+        let indent = match config.position {
+            OverlayPosition::TopLeft | OverlayPosition::BottomLeft => 0.0,
+            OverlayPosition::Top | OverlayPosition::Center | OverlayPosition::Bottom => slack / 2.0,
+            OverlayPosition::TopRight | OverlayPosition::BottomRight => slack,
+        };
+        let mut caret = left + padding + indent;
+        let baseline = top + padding + font.ascent() + line_height * i as f32;
+        let mut previous = None;
+        for c in line.chars() {
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += font.kern(previous, id);
+            }
+            previous = Some(id);
+            let glyph = id.with_scale_and_position(font.scale(), ab_glyph::point(caret, baseline));
+            caret += font.h_advance(id);
+
+            let Some(outline) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                let x = bounds.min.x as i64 + i64::from(gx);
+                let y = bounds.min.y as i64 + i64::from(gy);
+                if let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y))
+                    && x < image.width()
+                    && y < image.height()
+                {
+                    blend(image.get_pixel_mut(x, y), color, coverage);
+                }
+            });
+        }
+    }
+}
+
+fn line_width(font: &PxScaleFont<&FontVec>, line: &str) -> f32 {
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in line.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, id);
+        }
+        width += font.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// Break `text` into lines no wider than `max_width`, at spaces where
+/// possible. Existing line breaks are kept.
+fn wrap(text: &str, max_width: f32, measure: impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{line} {word}")
+            };
+            if measure(&candidate) > max_width && !line.is_empty() {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Top-left corner of a `size` box at `position` in an image of `image`
+/// pixels, `margin` pixels from the edges.
+fn place(
+    position: OverlayPosition,
+    image: (f32, f32),
+    size: (f32, f32),
+    margin: f32,
+) -> (f32, f32) {
+    let left = margin;
+    let center = (image.0 - size.0) / 2.0;
+    let right = image.0 - size.0 - margin;
+    let top = margin;
+    let middle = (image.1 - size.1) / 2.0;
+    let bottom = image.1 - size.1 - margin;
+    let (x, y) = match position {
+        OverlayPosition::TopLeft => (left, top),
+        OverlayPosition::Top => (center, top),
+        OverlayPosition::TopRight => (right, top),
+        OverlayPosition::Center => (center, middle),
+        OverlayPosition::BottomLeft => (left, bottom),
+        OverlayPosition::Bottom => (center, bottom),
+        OverlayPosition::BottomRight => (right, bottom),
+    };
+    (x.max(0.0), y.max(0.0))
+}
+
+/// Mix `color` over `pixel` by its alpha times `coverage`.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn blend(pixel: &mut Rgb<u8>, color: Rgba<u8>, coverage: f32) {
+    let alpha = f32::from(color[3]) / 255.0 * coverage.clamp(0.0, 1.0);
+    for (channel, value) in pixel.0.iter_mut().zip(color.0) {
+        let mixed = f32::from(*channel) * (1.0 - alpha) + f32::from(value) * alpha;
+        *channel = mixed.round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    fn wallpaper(title: Option<&str>, author: Option<&str>) -> Wallpaper {
+        Wallpaper {
+            id: "abc123".to_string(),
+            title: title.map(String::from),
+            author: author.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_attribution_text() -> anyhow::Result<()> {
+        let config = OverlayConfig::default();
+        let today = chrono::NaiveDate::from_ymd_opt(2026, 10, 18).unwrap_or_default();
+        let text = |title, author| text_for(&config, &wallpaper(title, author), today);
+
+        assert_eq!(
+            text(Some("Harbour at dawn"), Some("Ana"))?.as_deref(),
+            Some("Harbour at dawn by Ana")
+        );
+        assert_eq!(text(None, Some("Ana"))?.as_deref(), Some("Photo by Ana"));
+        assert_eq!(text(Some("Fog"), Some(" "))?.as_deref(), Some("Fog"));
+        assert_eq!(text(None, None)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_date_and_quote_text() -> anyhow::Result<()> {
+        let today = chrono::NaiveDate::from_ymd_opt(2026, 10, 18).unwrap_or_default();
+        let config = OverlayConfig {
+            text: OverlayText::Date,
+            ..Default::default()
+        };
+        assert_eq!(
+            text_for(&config, &wallpaper(None, None), today)?.as_deref(),
+            Some("Sunday, October 18")
+        );
+        let config = OverlayConfig {
+            date_format: "%Q".to_string(),
+            ..config
+        };
+        assert!(text_for(&config, &wallpaper(None, None), today).is_err());
+
+        let dir = tempfile::TempDir::new()?;
+        let quotes = dir.path().join("quotes.txt");
+        std::fs::write(&quotes, "Stay hungry.\n— Jobs\n\n\n  Less is more.  \n")?;
+        let config = OverlayConfig {
+            text: OverlayText::Quote,
+            quotes_file: Some(quotes),
+            ..Default::default()
+        };
+        let quote = text_for(&config, &wallpaper(None, None), today)?;
+        assert!(matches!(
+            quote.as_deref(),
+            Some("Stay hungry.\n— Jobs" | "Less is more.")
+        ));
+        assert_eq!(quote, text_for(&config, &wallpaper(None, None), today)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(
+            parse_color("#ffffff").ok(),
+            Some(Rgba([255, 255, 255, 255]))
+        );
+        assert_eq!(parse_color("00000080").ok(), Some(Rgba([0, 0, 0, 128])));
+        assert!(parse_color("#fff").is_err());
+        assert!(parse_color("#gg0000").is_err());
+        assert!(parse_color("#ééé").is_err());
+    }
+
+    #[test]
+    fn test_wrap() {
+        // One unit per character.
+        let measure = |s: &str| s.chars().map(|_| 1.0).sum();
+        assert_eq!(
+            wrap("the quick brown fox", 10.0, measure),
+            ["the quick", "brown fox"]
+        );
+        assert_eq!(wrap("a\nb", 10.0, measure), ["a", "b"]);
+        assert_eq!(wrap("unbreakable", 4.0, measure), ["unbreakable"]);
+    }
+
+    #[test]
+    fn test_place() {
+        let image = (1000.0, 500.0);
+        let size = (200.0, 50.0);
+        assert_eq!(
+            place(OverlayPosition::BottomRight, image, size, 10.0),
+            (790.0, 440.0)
+        );
+        assert_eq!(
+            place(OverlayPosition::Center, image, size, 10.0),
+            (400.0, 225.0)
+        );
+        assert_eq!(
+            place(OverlayPosition::TopLeft, (100.0, 20.0), size, 10.0),
+            (10.0, 10.0)
+        );
+    }
+
+    #[test]
+    fn test_draw_text_changes_only_its_corner() -> anyhow::Result<()> {
+        let Some(font_path) = system_font() else {
+            return Ok(());
+        };
+        let font = FontVec::try_from_vec(std::fs::read(font_path)?)?;
+        let mut image = RgbImage::from_pixel(400, 200, Rgb([40, 80, 120]));
+        let config = OverlayConfig {
+            font_size: 8.0,
+            ..Default::default()
+        };
+        draw_text(
+            &mut image,
+            &font,
+            "Harbour by Ana",
+            &config,
+            Rgba([255, 255, 255, 255]),
+            Some(Rgba([0, 0, 0, 128])),
+        );
+
+        assert_eq!(*image.get_pixel(5, 5), Rgb([40, 80, 120]));
+        let (width, height) = image.dimensions();
+        let corner = image.view(width / 2, height / 2, width / 2, height / 2);
+        assert!(corner.pixels().any(|(_, _, p)| p == Rgb([255, 255, 255])));
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+//! External sources over a subprocess protocol.
+//!
+//! For every fetch wallp starts the plugin's `command`, writes one JSON request
+//! to its stdin and closes it:
+//!
+//! ```json
+//! {"version": 1, "count": 3, "screen": {"width": 2560, "height": 1440},
+//!  "orientation": "landscape", "quality": "full", "options": {}}
+//! ```
+//!
+//! `screen` and `orientation` are `null` when the screen size is unknown, and
+//! `options` is the plugin's `options` from the config. The plugin answers
+//! with one JSON object on stdout, either `{"error": "message"}` or:
+//!
+//! ```json
+//! {"photos": [{"id": "abc", "title": "...", "author": "...", "author_url": "...",
+//!              "url": "https://...", "image_url": "https://.../abc.jpg"}]}
+//! ```
+//!
+//! Each photo needs an `id` and either an `image_url` to download or an
+//! absolute `path` to a local file; the other fields are optional. Anything
+//! written to stderr goes to wallp's log. A plugin that runs past its
+//! `timeout_secs` is killed.
+
+use crate::config::PluginSource;
+use crate::unsplash::ImageQuality;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+/// Version of the request format, bumped on incompatible changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct PluginRequest<'a> {
+    pub version: u32,
+    pub count: usize,
+    pub screen: Option<Screen>,
+    pub orientation: Option<Orientation>,
+    pub quality: ImageQuality,
+    pub options: &'a serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Screen {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    Landscape,
+    Portrait,
+}
+
+impl<'a> PluginRequest<'a> {
+    /// A request for `count` photos for a screen of `screen` pixels.
+    #[must_use]
+    pub fn new(
+        plugin: &'a PluginSource,
+        count: usize,
+        screen: Option<(u32, u32)>,
+        quality: ImageQuality,
+    ) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            count,
+            screen: screen.map(|(width, height)| Screen { width, height }),
+            orientation: screen.map(|(width, height)| {
+                if height > width {
+                    Orientation::Portrait
+                } else {
+                    Orientation::Landscape
+                }
+            }),
+            quality,
+            options: &plugin.options,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginResponse {
+    #[serde(default)]
+    photos: Vec<PluginPhoto>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+pub struct PluginPhoto {
+    pub id: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub author_url: Option<String>,
+    /// Page to view the photo on.
+    pub url: Option<String>,
+    pub image_url: Option<String>,
+    pub path: Option<PathBuf>,
+}
+
+impl PluginPhoto {
+    /// Why wallp can't use this photo, if it can't.
+    fn problem(&self) -> Option<&'static str> {
+        if self.id.trim().is_empty() {
+            return Some("has no id");
+        }
+        match (&self.image_url, &self.path) {
+            (Some(_), Some(_)) => Some("has both an image_url and a path"),
+            (None, None) => Some("has neither an image_url nor a path"),
+            (None, Some(path)) if !path.is_absolute() => Some("has a relative path"),
+            _ => None,
+        }
+    }
+}
+
+/// Run `plugin` with `request` and return the photos it offers.
+///
+/// # Errors
+///
+/// Returns an error if the plugin can't be started, times out, exits with a
+/// failure status, prints something other than a valid response, reports an
+/// error, or offers no usable photos.
+pub async fn run(plugin: &PluginSource, request: &PluginRequest<'_>) -> Result<Vec<PluginPhoto>> {
+    let name = &plugin.name;
+    let mut child = tokio::process::Command::new(&plugin.command)
+        .args(&plugin.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| {
+            format!(
+                "Failed to start plugin '{name}' ({})",
+                plugin.command.display()
+            )
+        })?;
+
+    let mut input = serde_json::to_vec(request)?;
+    input.push(b'\n');
+    let stdin = child.stdin.take();
+    let mut stdout = child.stdout.take().context("Plugin stdout is not piped")?;
+    let stderr = child.stderr.take().context("Plugin stderr is not piped")?;
+    let stderr = tokio::spawn(log_stderr(name.clone(), stderr));
+
+    // Writing the request counts towards the timeout too: a plugin that
+    // neither reads it nor exits would otherwise block here forever.
+    let exchange = async {
+        if let Some(mut stdin) = stdin {
+            // A plugin that doesn't read its request closes the pipe early.
+            if let Err(e) = stdin.write_all(&input).await {
+                tracing::debug!("Plugin '{name}' did not read its request: {e}");
+            }
+        }
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).await?;
+        Ok::<_, std::io::Error>((child.wait().await?, output))
+    };
+    let timeout = Duration::from_secs(plugin.timeout_secs);
+    let Ok(result) = tokio::time::timeout(timeout, exchange).await else {
+        // Whatever it wrote to stderr has been logged already.
+        let _ = child.kill().await;
+        anyhow::bail!("Plugin '{name}' timed out after {}s", plugin.timeout_secs);
+    };
+    let (status, stdout) = result.with_context(|| format!("Failed to run plugin '{name}'"))?;
+    let last_line = stderr.await.unwrap_or_default();
+
+    if !status.success() {
+        anyhow::bail!(
+            "Plugin '{name}' exited with {status}{}",
+            last_line.map(|l| format!(": {l}")).unwrap_or_default()
+        );
+    }
+
+    let response: PluginResponse = serde_json::from_slice(&stdout)
+        .with_context(|| format!("Plugin '{name}' returned an invalid response"))?;
+    if let Some(error) = response.error {
+        anyhow::bail!("Plugin '{name}' reported an error: {error}");
+    }
+
+    let photos: Vec<PluginPhoto> = response
+        .photos
+        .into_iter()
+        .filter(|photo| match photo.problem() {
+            Some(problem) => {
+                tracing::warn!("Plugin '{name}' photo '{}' {problem}, skipping", photo.id);
+                false
+            }
+            None => true,
+        })
+        .collect();
+    if photos.is_empty() {
+        anyhow::bail!("Plugin '{name}' returned no usable photos");
+    }
+    Ok(photos)
+}
+
+/// Log each line the plugin writes to stderr as it arrives, so a plugin that
+/// hangs still leaves a trace. Returns the last line, for error messages.
+async fn log_stderr(name: String, stderr: tokio::process::ChildStderr) -> Option<String> {
+    let mut reader = tokio::io::BufReader::new(stderr);
+    let mut line = Vec::new();
+    let mut last = None;
+    while reader
+        .read_until(b'\n', &mut line)
+        .await
+        .is_ok_and(|read| read > 0)
+    {
+        let text = String::from_utf8_lossy(&line).trim().to_string();
+        if !text.is_empty() {
+            tracing::info!("Plugin '{name}': {text}");
+            last = Some(text);
+        }
+        line.clear();
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_serialization() -> anyhow::Result<()> {
+        let plugin = PluginSource {
+            name: "internal".to_string(),
+            options: serde_json::json!({"album": "offsite-2026"}),
+            ..Default::default()
+        };
+
+        let request = PluginRequest::new(&plugin, 2, Some((1080, 1920)), ImageQuality::Full);
+        assert_eq!(
+            serde_json::to_value(&request)?,
+            serde_json::json!({
+                "version": 1,
+                "count": 2,
+                "screen": {"width": 1080, "height": 1920},
+                "orientation": "portrait",
+                "quality": "full",
+                "options": {"album": "offsite-2026"}
+            })
+        );
+
+        let request = PluginRequest::new(&plugin, 1, None, ImageQuality::Reduced);
+        let value = serde_json::to_value(&request)?;
+        assert!(value["screen"].is_null() && value["orientation"].is_null());
+        Ok(())
+    }
+
+    #[test]
+    fn test_photo_problems() {
+        let photo = PluginPhoto {
+            id: "a".to_string(),
+            image_url: Some("https://example.com/a.jpg".to_string()),
+            ..Default::default()
+        };
+        assert!(photo.problem().is_none());
+
+        let both = PluginPhoto {
+            path: Some(PathBuf::from("/tmp/a.jpg")),
+            ..photo.clone()
+        };
+        assert!(both.problem().is_some());
+
+        let relative = PluginPhoto {
+            image_url: None,
+            path: Some(PathBuf::from("a.jpg")),
+            ..photo.clone()
+        };
+        assert_eq!(relative.problem(), Some("has a relative path"));
+
+        let no_id = PluginPhoto {
+            id: " ".to_string(),
+            ..photo
+        };
+        assert_eq!(no_id.problem(), Some("has no id"));
+    }
+}
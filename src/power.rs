@@ -0,0 +1,156 @@
+use crate::config::{Config, ConstrainedFetch};
+use std::fs;
+use std::path::Path;
+
+/// Where Linux exposes batteries and AC adapters.
+pub const POWER_SUPPLY_ROOT: &str = "/sys/class/power_supply";
+
+/// Decide how the scheduler should fetch right now, given the battery and
+/// metered-connection policies in `config`.
+///
+/// Each condition is only probed when its policy is something other than
+/// [`ConstrainedFetch::Fetch`]; when both apply, the stricter policy wins.
+#[must_use]
+pub fn current_policy(config: &Config) -> ConstrainedFetch {
+    let mut policy = ConstrainedFetch::Fetch;
+
+    if config.on_battery != ConstrainedFetch::Fetch && on_battery(Path::new(POWER_SUPPLY_ROOT)) {
+        tracing::info!("Running on battery, applying {:?}", config.on_battery);
+        policy = policy.max(config.on_battery);
+    }
+
+    if config.on_metered != ConstrainedFetch::Fetch && is_metered() {
+        tracing::info!("Connection is metered, applying {:?}", config.on_metered);
+        policy = policy.max(config.on_metered);
+    }
+
+    policy
+}
+
+/// Whether the machine is running on battery power.
+///
+/// Reads the power supplies under `root` (normally [`POWER_SUPPLY_ROOT`]): any
+/// online AC/USB supply means we're plugged in, otherwise a discharging
+/// battery means we're not. Always `false` on platforms without sysfs.
+#[must_use]
+pub fn on_battery(root: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(root) else {
+        return false;
+    };
+
+    let mut discharging = false;
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        let read = |name: &str| {
+            fs::read_to_string(dir.join(name))
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        if read("type") == "Battery" {
+            discharging |= read("status") == "Discharging";
+        } else if read("online") == "1" {
+            return false;
+        }
+    }
+
+    discharging
+}
+
+/// Whether `NetworkManager` reports the primary connection as metered.
+///
+/// Returns `false` if `NetworkManager` isn't available.
+#[must_use]
+pub fn is_metered() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("busctl")
+            .args([
+                "--system",
+                "get-property",
+                "org.freedesktop.NetworkManager",
+                "/org/freedesktop/NetworkManager",
+                "org.freedesktop.NetworkManager",
+                "Metered",
+            ])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .is_some_and(|o| parse_nm_metered(&String::from_utf8_lossy(&o.stdout)))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
+
+/// Parse `busctl` output for `NetworkManager`'s `NMMetered` enum (e.g. `u 1`).
+/// `1` (yes) and `3` (guess-yes) count as metered.
+fn parse_nm_metered(output: &str) -> bool {
+    matches!(output.split_whitespace().nth(1), Some("1" | "3"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn add_supply(root: &Path, name: &str, files: &[(&str, &str)]) -> anyhow::Result<()> {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir)?;
+        for (file, content) in files {
+            fs::write(dir.join(file), format!("{content}\n"))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_on_battery_discharging() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        add_supply(temp_dir.path(), "AC", &[("type", "Mains"), ("online", "0")])?;
+        add_supply(
+            temp_dir.path(),
+            "BAT0",
+            &[("type", "Battery"), ("status", "Discharging")],
+        )?;
+        assert!(on_battery(temp_dir.path()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_on_battery_plugged_in() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        add_supply(temp_dir.path(), "AC", &[("type", "Mains"), ("online", "1")])?;
+        add_supply(
+            temp_dir.path(),
+            "BAT0",
+            &[("type", "Battery"), ("status", "Charging")],
+        )?;
+        assert!(!on_battery(temp_dir.path()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_on_battery_desktop_without_supplies() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        assert!(!on_battery(temp_dir.path()));
+        assert!(!on_battery(&temp_dir.path().join("missing")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_nm_metered() {
+        assert!(parse_nm_metered("u 1\n"));
+        assert!(parse_nm_metered("u 3"));
+        assert!(!parse_nm_metered("u 2"));
+        assert!(!parse_nm_metered("u 4"));
+        assert!(!parse_nm_metered(""));
+    }
+
+    #[test]
+    fn test_stricter_policy_wins() {
+        assert!(ConstrainedFetch::Skip > ConstrainedFetch::RotateLocal);
+        assert!(ConstrainedFetch::RotateLocal > ConstrainedFetch::LowQuality);
+        assert!(ConstrainedFetch::LowQuality > ConstrainedFetch::Fetch);
+    }
+}
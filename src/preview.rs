@@ -0,0 +1,397 @@
+//! Small thumbnails of downloaded wallpapers, and drawing images inline in
+//! the terminal with the kitty graphics protocol, sixel or Unicode half blocks.
+
+use crate::config::PreviewProtocol;
+use anyhow::{Context, Result};
+use base64::Engine;
+use image::{DynamicImage, RgbImage};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Width of cached thumbnails, in pixels.
+pub const THUMBNAIL_WIDTH: u32 = 320;
+
+/// Columns used for the thumbnails in `wallp list --thumbs`.
+pub const LIST_COLUMNS: u16 = 24;
+
+/// Cell width assumed when the terminal doesn't report its size in pixels.
+const DEFAULT_CELL_WIDTH: u32 = 10;
+
+/// The cached thumbnail of the wallpaper at `source`, made now if there isn't
+/// one yet.
+///
+/// # Errors
+///
+/// Returns an error if the image can't be read or the thumbnail can't be saved.
+pub async fn thumbnail(source: &Path, cache_dir: &Path) -> Result<PathBuf> {
+    let stem = source
+        .file_stem()
+        .context("Wallpaper path has no file name")?
+        .to_string_lossy();
+    // Named like the effects cache so `effects::prune` cleans up after both.
+    let dest = cache_dir.join(format!("{stem}-thumb.jpg"));
+    if dest.exists() {
+        return Ok(dest);
+    }
+
+    let source = source.to_path_buf();
+    let cache_dir = cache_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<PathBuf> {
+        let image =
+            image::open(&source).with_context(|| format!("Failed to open {}", source.display()))?;
+        let small = image
+            .thumbnail(THUMBNAIL_WIDTH, THUMBNAIL_WIDTH)
+            .into_rgb8();
+
+        std::fs::create_dir_all(&cache_dir).context("Failed to create thumbnail cache")?;
+        crate::http::write_jpeg(&small, &dest, 85).context("Failed to save thumbnail")?;
+        Ok(dest)
+    })
+    .await
+    .context("Thumbnail task failed")?
+}
+
+/// How images are drawn in the terminal, once `auto` has been resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Sixel,
+    Blocks,
+}
+
+impl Protocol {
+    /// The protocol configured, or else the best one the terminal is known to
+    /// support.
+    #[must_use]
+    pub fn resolve(configured: PreviewProtocol) -> Self {
+        match configured {
+            PreviewProtocol::Auto => Self::detect(|name| std::env::var(name).ok()),
+            PreviewProtocol::Kitty => Self::Kitty,
+            PreviewProtocol::Sixel => Self::Sixel,
+            PreviewProtocol::Blocks => Self::Blocks,
+        }
+    }
+
+    /// Guess from the environment, as there's no reliable way to ask the
+    /// terminal without waiting on a reply.
+    fn detect(var: impl Fn(&str) -> Option<String>) -> Self {
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        // Escape sequences don't make it through multiplexers unwrapped.
+        if var("TMUX").is_some() || term.starts_with("screen") {
+            return Self::Blocks;
+        }
+        if var("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+            || matches!(program.as_str(), "WezTerm" | "ghostty")
+        {
+            return Self::Kitty;
+        }
+        if term.contains("sixel")
+            || term.starts_with("foot")
+            || term.starts_with("mlterm")
+            || term == "yaft-256color"
+            || matches!(program.as_str(), "iTerm.app" | "mintty" | "contour")
+        {
+            return Self::Sixel;
+        }
+        Self::Blocks
+    }
+}
+
+/// The terminal's width in columns and the width of a cell in pixels.
+#[must_use]
+pub fn terminal_size() -> (u16, u32) {
+    let columns = crossterm::terminal::size().map_or(80, |(columns, _)| columns);
+    let cell_width = crossterm::terminal::window_size()
+        .ok()
+        .filter(|size| size.width > 0 && size.columns > 0)
+        .map_or(DEFAULT_CELL_WIDTH, |size| {
+            u32::from(size.width / size.columns).max(1)
+        });
+    (columns, cell_width)
+}
+
+/// Escape sequences that draw the image at `path` `columns` wide, followed by
+/// a newline.
+///
+/// # Errors
+///
+/// Returns an error if the image can't be read.
+pub fn render(path: &Path, columns: u16, protocol: Protocol) -> Result<String> {
+    let image = image::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let columns = columns.max(1);
+    Ok(match protocol {
+        Protocol::Kitty => kitty(&image, columns)?,
+        Protocol::Sixel => {
+            let (_, cell_width) = terminal_size();
+            let width = u32::from(columns) * cell_width;
+            sixel(
+                &image
+                    .resize(width, width, image::imageops::FilterType::Triangle)
+                    .into_rgb8(),
+            )
+        }
+        Protocol::Blocks => blocks(&image, columns),
+    })
+}
+
+/// Kitty graphics protocol: the image as a base64 PNG, sent in chunks and
+/// scaled by the terminal to `columns` cells.
+fn kitty(image: &DynamicImage, columns: u16) -> Result<String> {
+    let mut png = Vec::new();
+    image
+        .to_rgb8()
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .context("Failed to encode preview")?;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(png);
+
+    let mut out = String::new();
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(4096).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            let _ = write!(
+                out,
+                "\x1b_Gf=100,a=T,q=2,c={columns},m={more};{chunk}\x1b\\"
+            );
+        } else {
+            let _ = write!(out, "\x1b_Gm={more};{chunk}\x1b\\");
+        }
+    }
+    out.push('\n');
+    Ok(out)
+}
+
+/// Sixel, with colors reduced to a 6x6x6 cube.
+fn sixel(image: &RgbImage) -> String {
+    let (width, height) = image.dimensions();
+    let register = |x: u32, y: u32| -> usize {
+        let [r, g, b] = image
+            .get_pixel(x, y)
+            .0
+            .map(|c| (usize::from(c) * 5 + 127) / 255);
+        r * 36 + g * 6 + b
+    };
+
+    let mut out = format!("\x1bPq\"1;1;{width};{height}");
+    let used: BTreeSet<usize> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| register(x, y))
+        .collect();
+    for &color in &used {
+        let [r, g, b] = [color / 36, color / 6 % 6, color % 6].map(|c| c * 20);
+        let _ = write!(out, "#{color};2;{r};{g};{b}");
+    }
+
+    for top in (0..height).step_by(6) {
+        let rows = (height - top).min(6);
+        let band: BTreeSet<usize> = (0..width)
+            .flat_map(|x| (top..top + rows).map(move |y| (x, y)))
+            .map(|(x, y)| register(x, y))
+            .collect();
+        for color in band {
+            let _ = write!(out, "#{color}");
+            let sixels = (0..width).map(|x| {
+                let bits = (0..rows)
+                    .filter(|&row| register(x, top + row) == color)
+                    .fold(0u8, |bits, row| bits | 1 << row);
+                char::from(63 + bits)
+            });
+            push_runs(&mut out, sixels);
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\\n");
+    out
+}
+
+/// Append `chars`, run-length encoding repeats as sixel `!<count><char>`.
+fn push_runs(out: &mut String, chars: impl Iterator<Item = char>) {
+    let mut run: Option<(char, usize)> = None;
+    let flush = |out: &mut String, (c, count): (char, usize)| {
+        if count > 3 {
+            let _ = write!(out, "!{count}{c}");
+        } else {
+            out.extend(std::iter::repeat_n(c, count));
+        }
+    };
+    for c in chars {
+        run = match run {
+            Some((last, count)) if last == c => Some((c, count + 1)),
+            Some(previous) => {
+                flush(out, previous);
+                Some((c, 1))
+            }
+            None => Some((c, 1)),
+        };
+    }
+    if let Some(run) = run {
+        flush(out, run);
+    }
+}
+
+/// `image` scaled to fit `columns` cells across and, if given, `rows` down
+/// when drawn with half blocks, i.e. at one pixel per column and two per row.
+#[must_use]
+pub fn half_block_pixels(image: &DynamicImage, columns: u16, rows: Option<u16>) -> RgbImage {
+    let (image_width, image_height) = (
+        u64::from(image.width().max(1)),
+        u64::from(image.height().max(1)),
+    );
+    let mut width = u64::from(columns.max(1));
+    let mut height = (width * image_height / image_width).max(1);
+    if let Some(rows) = rows {
+        let max_height = u64::from(rows.max(1)) * 2;
+        if height > max_height {
+            height = max_height;
+            width = (height * image_width / image_height).max(1);
+        }
+    }
+    let to_u32 = |n: u64| u32::try_from(n).unwrap_or(u32::MAX);
+    image
+        .resize_exact(
+            to_u32(width),
+            to_u32(height),
+            image::imageops::FilterType::Triangle,
+        )
+        .into_rgb8()
+}
+
+/// Upper half blocks colored with 24-bit ANSI escapes: two pixels per cell.
+fn blocks(image: &DynamicImage, columns: u16) -> String {
+    let image = half_block_pixels(image, columns, None);
+    let (width, height) = image.dimensions();
+
+    let mut out = String::new();
+    for y in (0..height).step_by(2) {
+        for x in 0..width {
+            let [r, g, b] = image.get_pixel(x, y).0;
+            let _ = write!(out, "\x1b[38;2;{r};{g};{b}m");
+            if y + 1 < height {
+                let [r, g, b] = image.get_pixel(x, y + 1).0;
+                let _ = write!(out, "\x1b[48;2;{r};{g};{b}m");
+            }
+            out.push('▀');
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    /// Red on the left, blue on the right.
+    fn halves(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, _| {
+            if x < width / 2 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 0, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn test_detect() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| (*value).to_string())
+            }
+        };
+        assert_eq!(
+            Protocol::detect(env(&[("TERM", "xterm-kitty")])),
+            Protocol::Kitty
+        );
+        assert_eq!(
+            Protocol::detect(env(&[("TERM_PROGRAM", "WezTerm")])),
+            Protocol::Kitty
+        );
+        assert_eq!(Protocol::detect(env(&[("TERM", "foot")])), Protocol::Sixel);
+        assert_eq!(
+            Protocol::detect(env(&[("TERM", "xterm-kitty"), ("TMUX", "/tmp/tmux")])),
+            Protocol::Blocks
+        );
+        assert_eq!(
+            Protocol::detect(env(&[("TERM", "xterm-256color")])),
+            Protocol::Blocks
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let out = blocks(&DynamicImage::ImageRgb8(halves(8, 4)), 8);
+        // 8x4 pixels make two rows of eight cells.
+        assert_eq!(out.lines().count(), 2);
+        assert_eq!(out.matches('▀').count(), 16);
+        let first = out.lines().next().unwrap_or_default();
+        assert!(first.starts_with("\x1b[38;2;255;0;0m\x1b[48;2;255;0;0m▀"));
+        assert!(first.ends_with("\x1b[38;2;0;0;255m\x1b[48;2;0;0;255m▀\x1b[0m"));
+    }
+
+    #[test]
+    fn test_half_block_pixels_fit() {
+        let wide = DynamicImage::ImageRgb8(halves(400, 100));
+        assert_eq!(half_block_pixels(&wide, 40, None).dimensions(), (40, 10));
+        assert_eq!(
+            half_block_pixels(&wide, 40, Some(20)).dimensions(),
+            (40, 10)
+        );
+        assert_eq!(half_block_pixels(&wide, 40, Some(2)).dimensions(), (16, 4));
+    }
+
+    #[test]
+    fn test_sixel() {
+        let out = sixel(&halves(8, 8));
+        assert!(out.starts_with("\x1bPq\"1;1;8;8#5;2;0;0;100#180;2;100;0;0#5"));
+        assert!(out.ends_with("\x1b\\\n"));
+        // Two bands, the second only two pixels high.
+        assert_eq!(out.matches('-').count(), 2);
+        assert!(out.contains("#180!4~!4?$"));
+        assert!(out.contains("#180!4B!4?$"));
+    }
+
+    #[test]
+    fn test_push_runs() {
+        let mut out = String::new();
+        push_runs(&mut out, "aaabbbbbc".chars());
+        assert_eq!(out, "aaa!5bc");
+    }
+
+    #[test]
+    fn test_kitty_chunks() -> anyhow::Result<()> {
+        let mut rng = fastrand::Rng::with_seed(7);
+        let noise = RgbImage::from_fn(64, 64, |_, _| Rgb([rng.u8(..), rng.u8(..), rng.u8(..)]));
+        let out = kitty(&DynamicImage::ImageRgb8(noise), 20)?;
+        assert!(out.starts_with("\x1b_Gf=100,a=T,q=2,c=20,m=1;iVBOR"));
+        assert!(out.ends_with("\x1b\\\n"));
+        assert!(out.matches("\x1b_Gm=1;").count() > 1);
+        assert_eq!(out.matches("\x1b_Gm=0;").count(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_is_cached() -> anyhow::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let source = dir.path().join("wallpaper_abc.jpg");
+        halves(1280, 640).save(&source)?;
+        let cache = dir.path().join("thumbnails");
+
+        let thumb = thumbnail(&source, &cache).await?;
+        assert_eq!(thumb, cache.join("wallpaper_abc-thumb.jpg"));
+        assert_eq!(image::image_dimensions(&thumb)?, (THUMBNAIL_WIDTH, 160));
+        std::fs::write(&thumb, b"cached")?;
+        assert_eq!(thumbnail(&source, &cache).await?, thumb);
+        assert_eq!(std::fs::read(&thumb)?, b"cached");
+        Ok(())
+    }
+}
@@ -1,28 +1,134 @@
-use crate::config::AppData;
+use crate::config::{AppData, CatchUpPolicy, ConstrainedFetch, OfflineFallback};
 use crate::manager;
-use chrono::Utc;
+use crate::power;
+use crate::sources;
+use crate::unsplash::ImageQuality;
+use chrono::{DateTime, TimeDelta, Utc};
 use std::time::Duration;
 
+/// A wall-clock gap between two ticks larger than this means the machine was
+/// suspended (the tick interval itself is one minute).
+const WAKE_GAP_THRESHOLD_MINUTES: i64 = 3;
+
+/// How late a run may be before it counts as missed rather than merely due.
+const MISSED_RUN_GRACE_MINUTES: i64 = 2;
+
+/// How long to wait before retrying a prefetch that failed or found nothing
+/// new, to stay well inside Unsplash's rate limit.
+const PREFETCH_RETRY_MINUTES: i64 = 15;
+
+/// What the scheduler should do on a given tick.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Wait,
+    Run,
+    Reschedule(DateTime<Utc>),
+}
+
 pub async fn start_background_task() {
     let mut interval = tokio::time::interval(Duration::from_mins(1));
+    let mut last_tick: Option<DateTime<Utc>> = None;
+    let mut prefetch_retry_at: Option<DateTime<Utc>> = None;
 
     loop {
         interval.tick().await;
 
-        if let Err(e) = check_and_run().await {
+        // Monotonic timers stop while suspended, so compare wall-clock time.
+        let now = Utc::now();
+        let woke = last_tick
+            .is_some_and(|t| now - t > chrono::Duration::minutes(WAKE_GAP_THRESHOLD_MINUTES));
+        if woke {
+            tracing::info!("Detected wake from sleep");
+        }
+        last_tick = Some(now);
+
+        if let Err(e) = check_and_run(woke).await {
             tracing::warn!("Scheduler error: {e}");
         }
+
+        if prefetch_retry_at.is_none_or(|t| Utc::now() >= t) {
+            prefetch_retry_at = None;
+            let back_off = match refill_if_needed().await {
+                Ok(still_short) => still_short,
+                Err(e) => {
+                    tracing::warn!("Prefetch error: {e}");
+                    true
+                }
+            };
+            if back_off {
+                prefetch_retry_at =
+                    Some(Utc::now() + chrono::Duration::minutes(PREFETCH_RETRY_MINUTES));
+            }
+        }
     }
 }
 
-async fn check_and_run() -> anyhow::Result<()> {
+/// Top up the prefetch queue in the background so `new`/`next` can apply a
+/// wallpaper without waiting on the network. Returns `true` when nothing
+/// could be added although the queue is short, e.g. because every photo the
+/// source offered was already seen, so the caller should wait before asking
+/// again.
+async fn refill_if_needed() -> anyhow::Result<bool> {
     let app_data = AppData::load()?;
 
+    if !app_data.state.is_running
+        || !sources::has_new_today(&app_data)
+        || app_data.queue.len() >= app_data.config.prefetch_count
+    {
+        return Ok(false);
+    }
+
+    if power::current_policy(&app_data.config) != ConstrainedFetch::Fetch {
+        return Ok(false);
+    }
+
+    let added = manager::refill_queue().await?;
+    if added == 0 {
+        tracing::info!(
+            "Found nothing new to prefetch, trying again in {PREFETCH_RETRY_MINUTES} minutes"
+        );
+    } else {
+        tracing::info!("Prefetched {added} wallpaper(s)");
+    }
+    Ok(added == 0)
+}
+
+/// Pause the scheduler, indefinitely when `until` is `None`.
+///
+/// # Errors
+///
+/// Returns an error if the state cannot be loaded or saved.
+pub fn pause(until: Option<DateTime<Utc>>) -> anyhow::Result<()> {
+    let mut app_data = AppData::load()?;
+    app_data.state.pause(until);
+    app_data.save()
+}
+
+/// Resume a paused scheduler.
+///
+/// # Errors
+///
+/// Returns an error if the state cannot be loaded or saved.
+pub fn resume() -> anyhow::Result<()> {
+    let mut app_data = AppData::load()?;
+    app_data.state.resume();
+    app_data.save()
+}
+
+async fn check_and_run(woke: bool) -> anyhow::Result<()> {
+    let mut app_data = AppData::load()?;
+
     if !app_data.state.is_running {
-        return Ok(());
+        if !app_data.state.pause_expired(Utc::now()) {
+            return Ok(());
+        }
+        tracing::info!("Timed pause ended, resuming scheduler");
+        app_data.state.resume();
+        app_data.save()?;
     }
 
-    if app_data.config.unsplash_access_key.is_empty() {
+    // Also waits out the rest of the day once daily sources are all fetched.
+    if !sources::has_new_today(&app_data) {
         return Ok(());
     }
 
@@ -31,8 +137,8 @@ async fn check_and_run() -> anyhow::Result<()> {
         Err(e) => {
             tracing::warn!("Invalid next_run_at in config: {e}. Resetting schedule.");
             if let Ok(mut app_data) = AppData::load() {
-                let next_run = Utc::now()
-                    + chrono::Duration::minutes(app_data.config.interval_minutes.cast_signed());
+                let now = Utc::now();
+                let next_run = minutes_after(now, app_data.config.interval_minutes).unwrap_or(now);
                 app_data.state.next_run_at = next_run.to_rfc3339();
                 let _ = app_data.save();
             }
@@ -40,23 +146,134 @@ async fn check_and_run() -> anyhow::Result<()> {
         }
     };
 
-    if Utc::now() >= next_run
-        && let Err(e) = manager::next().await
-    {
-        tracing::warn!(
-            "Scheduler error: {e}. Applying 15-minute backoff to prevent rate-limiting."
-        );
-        if let Ok(mut backoff_app_data) = AppData::load() {
-            #[allow(clippy::cast_possible_wrap)]
-            let backoff_time = Utc::now() + chrono::Duration::minutes(15);
-            backoff_app_data.state.next_run_at = backoff_time.to_rfc3339();
-            let _ = backoff_app_data.save();
+    match plan(
+        Utc::now(),
+        next_run,
+        app_data.config.interval_minutes,
+        woke,
+        app_data.config.catch_up_policy,
+    ) {
+        Action::Wait => {}
+        Action::Reschedule(at) => {
+            tracing::info!("Catch-up policy moved next run to {}", at.to_rfc3339());
+            app_data.state.next_run_at = at.to_rfc3339();
+            app_data.save()?;
+        }
+        Action::Run => {
+            let result = match power::current_policy(&app_data.config) {
+                ConstrainedFetch::Fetch => run_online(&app_data, ImageQuality::Full).await,
+                ConstrainedFetch::LowQuality => run_online(&app_data, ImageQuality::Reduced).await,
+                ConstrainedFetch::RotateLocal => manager::rotate_local().await,
+                ConstrainedFetch::Skip => {
+                    tracing::info!("Skipping scheduled run due to power/network policy");
+                    let now = Utc::now();
+                    let next_run =
+                        minutes_after(now, app_data.config.interval_minutes).unwrap_or(now);
+                    app_data.state.next_run_at = next_run.to_rfc3339();
+                    app_data.save()
+                }
+            };
+
+            if let Err(e) = result {
+                tracing::warn!(
+                    "Scheduler error: {e}. Applying 15-minute backoff to prevent rate-limiting."
+                );
+                if let Ok(mut backoff_app_data) = AppData::load() {
+                    #[allow(clippy::cast_possible_wrap)]
+                    let backoff_time = Utc::now() + chrono::Duration::minutes(15);
+                    backoff_app_data.state.next_run_at = backoff_time.to_rfc3339();
+                    let _ = backoff_app_data.save();
+                }
+            }
         }
     }
 
     Ok(())
 }
 
+/// Advance to the next wallpaper, falling back to one already downloaded when
+/// the network turns out to be unreachable. There's no probe beforehand: only
+/// the real request goes through the proxy and to the source actually picked.
+async fn run_online(app_data: &AppData, quality: ImageQuality) -> anyhow::Result<()> {
+    let fallback = app_data.config.offline_fallback;
+
+    // After an offline rotation the history index points somewhere in the
+    // middle, so fetch a fresh photo instead of walking forward from there.
+    let result = if app_data.state.offline_rotation {
+        manager::new_with_quality(quality).await
+    } else {
+        manager::next_with_quality(quality).await
+    };
+
+    match result {
+        Err(e) if fallback != OfflineFallback::Off && manager::is_network_error(&e) => {
+            tracing::warn!("Fetch failed: {e}. Falling back to downloaded wallpapers.");
+            manager::rotate_offline(fallback).await
+        }
+        other => other,
+    }
+}
+
+/// Decide what to do on this tick, applying the catch-up policy when the
+/// scheduled run was missed (or the machine just woke up).
+fn plan(
+    now: DateTime<Utc>,
+    next_run: DateTime<Utc>,
+    interval_minutes: u64,
+    woke: bool,
+    policy: CatchUpPolicy,
+) -> Action {
+    if now < next_run {
+        // Don't let a run that happens to fall right after wake-up fire
+        // before the configured delay has passed.
+        if woke
+            && let CatchUpPolicy::DelayAfterWake(minutes) = policy
+            && let Some(earliest) = minutes_after(now, minutes)
+            && next_run < earliest
+        {
+            return Action::Reschedule(earliest);
+        }
+        return Action::Wait;
+    }
+
+    let missed = woke || now - next_run > chrono::Duration::minutes(MISSED_RUN_GRACE_MINUTES);
+    if !missed {
+        return Action::Run;
+    }
+
+    match policy {
+        CatchUpPolicy::RunOnce | CatchUpPolicy::DelayAfterWake(0) => Action::Run,
+        // A time past the end of the calendar never comes.
+        CatchUpPolicy::DelayAfterWake(minutes) => {
+            minutes_after(now, minutes).map_or(Action::Wait, Action::Reschedule)
+        }
+        CatchUpPolicy::Skip => {
+            // The first slot on the original schedule that is still ahead.
+            let Some(interval) = i64::try_from(interval_minutes.max(1))
+                .ok()
+                .and_then(TimeDelta::try_minutes)
+                .map(|interval| interval.num_milliseconds())
+            else {
+                return Action::Wait;
+            };
+            let missed = (now - next_run).num_milliseconds() / interval + 1;
+            missed
+                .checked_mul(interval)
+                .and_then(TimeDelta::try_milliseconds)
+                .and_then(|late| next_run.checked_add_signed(late))
+                .map_or(Action::Wait, Action::Reschedule)
+        }
+    }
+}
+
+/// `minutes` after `at`, or `None` if that's past the end of the calendar.
+fn minutes_after(at: DateTime<Utc>, minutes: u64) -> Option<DateTime<Utc>> {
+    i64::try_from(minutes)
+        .ok()
+        .and_then(TimeDelta::try_minutes)
+        .and_then(|delta| at.checked_add_signed(delta))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,4 +317,105 @@ mod tests {
             assert!((diff - interval).abs() <= 1); // Allow 1 min tolerance
         }
     }
+
+    #[test]
+    fn test_plan_runs_when_just_due() {
+        let now = Utc::now();
+        let next = now - ChronoDuration::seconds(30);
+        for policy in [
+            CatchUpPolicy::Skip,
+            CatchUpPolicy::RunOnce,
+            CatchUpPolicy::DelayAfterWake(5),
+        ] {
+            assert_eq!(plan(now, next, 60, false, policy), Action::Run);
+        }
+    }
+
+    #[test]
+    fn test_plan_waits_before_next_run() {
+        let now = Utc::now();
+        let next = now + ChronoDuration::minutes(30);
+        assert_eq!(
+            plan(now, next, 60, false, CatchUpPolicy::RunOnce),
+            Action::Wait
+        );
+    }
+
+    #[test]
+    fn test_plan_run_once_after_missed_runs() {
+        let now = Utc::now();
+        let next = now - ChronoDuration::days(2);
+        assert_eq!(
+            plan(now, next, 60, false, CatchUpPolicy::RunOnce),
+            Action::Run
+        );
+    }
+
+    #[test]
+    fn test_plan_skip_moves_to_next_slot() {
+        let now = Utc::now();
+        let next = now - ChronoDuration::minutes(150);
+        let Action::Reschedule(at) = plan(now, next, 60, false, CatchUpPolicy::Skip) else {
+            panic!("expected reschedule");
+        };
+        assert!(at > now);
+        assert_eq!(at, next + ChronoDuration::minutes(180));
+
+        // Exactly on a slot moves on to the following one.
+        let next = now - ChronoDuration::minutes(120);
+        assert_eq!(
+            plan(now, next, 60, false, CatchUpPolicy::Skip),
+            Action::Reschedule(now + ChronoDuration::minutes(60))
+        );
+
+        // Years of missed runs are skipped in one step.
+        let next = now - ChronoDuration::days(3650) - ChronoDuration::seconds(90);
+        let Action::Reschedule(at) = plan(now, next, 30, false, CatchUpPolicy::Skip) else {
+            panic!("expected reschedule");
+        };
+        assert_eq!(
+            at,
+            now + ChronoDuration::minutes(28) + ChronoDuration::seconds(30)
+        );
+
+        // An interval past the end of the calendar doesn't panic.
+        assert_eq!(
+            plan(now, next, u64::MAX, false, CatchUpPolicy::Skip),
+            Action::Wait
+        );
+        assert_eq!(
+            plan(now, next, u64::MAX / 2, false, CatchUpPolicy::Skip),
+            Action::Wait
+        );
+    }
+
+    #[test]
+    fn test_plan_delay_after_wake() {
+        let now = Utc::now();
+        let next = now - ChronoDuration::seconds(30);
+        assert_eq!(
+            plan(now, next, 60, true, CatchUpPolicy::DelayAfterWake(5)),
+            Action::Reschedule(now + ChronoDuration::minutes(5))
+        );
+        // A run due shortly after wake-up is pushed back as well.
+        let soon = now + ChronoDuration::minutes(1);
+        assert_eq!(
+            plan(now, soon, 60, true, CatchUpPolicy::DelayAfterWake(5)),
+            Action::Reschedule(now + ChronoDuration::minutes(5))
+        );
+        // A delay past the end of the calendar doesn't panic.
+        assert_eq!(
+            plan(now, next, 60, true, CatchUpPolicy::DelayAfterWake(u64::MAX)),
+            Action::Wait
+        );
+        assert_eq!(
+            plan(now, soon, 60, true, CatchUpPolicy::DelayAfterWake(u64::MAX)),
+            Action::Wait
+        );
+        // Without a wake-up the delay does not apply to an on-time run.
+        assert_eq!(
+            plan(now, next, 60, false, CatchUpPolicy::DelayAfterWake(5)),
+            Action::Run
+        );
+    }
 }
@@ -0,0 +1,49 @@
+/// Size of the primary screen in pixels, if it can be determined.
+#[must_use]
+pub fn size() -> Option<(u32, u32)> {
+    #[cfg(target_os = "windows")]
+    {
+        use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN};
+
+        // SAFETY: GetSystemMetrics has no preconditions.
+        let (width, height) =
+            unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) };
+        let width = u32::try_from(width).ok().filter(|w| *w > 0)?;
+        let height = u32::try_from(height).ok().filter(|h| *h > 0)?;
+        Some((width, height))
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let output = std::process::Command::new("xrandr")
+            .arg("--current")
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_xrandr(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// The `current W x H` size from `xrandr --current`.
+#[cfg(not(target_os = "windows"))]
+fn parse_xrandr(output: &str) -> Option<(u32, u32)> {
+    let current = output.split("current ").nth(1)?;
+    let mut parts = current.split(',').next()?.split('x');
+    let width = parts.next()?.trim().parse().ok()?;
+    let height = parts.next()?.trim().parse().ok()?;
+    Some((width, height))
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_xrandr() {
+        let output = "Screen 0: minimum 320 x 200, current 2560 x 1440, maximum 16384 x 16384\n\
+                      eDP-1 connected primary 2560x1440+0+0 (normal left inverted right x axis y axis)\n";
+        assert_eq!(parse_xrandr(output), Some((2560, 1440)));
+        assert_eq!(parse_xrandr("Can't open display"), None);
+    }
+}
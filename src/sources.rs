@@ -0,0 +1,731 @@
+use crate::apod::{ApodClient, ApodEntry};
+use crate::bing::{BingClient, BingImage};
+use crate::config::{AppData, Config, FeedSource, JsonSource, PluginSource, WallhavenSearch};
+use crate::credentials::AccessKey;
+use crate::feed::{self, FeedItem};
+use crate::generator::{self, GeneratorStyle, Pattern};
+use crate::http;
+use crate::json_source::{self, JsonItem};
+use crate::plugin::{self, PluginPhoto, PluginRequest};
+use crate::screen;
+use crate::unsplash::{ImageQuality, PhotoFilter, UnsplashClient, UnsplashPhoto, with_utm};
+use crate::wallhaven::{WallhavenClient, WallhavenWallpaper};
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Prefix marking a `collections` entry as a saved Wallhaven search.
+pub const WALLHAVEN_PREFIX: &str = "wallhaven:";
+
+/// Prefix marking a `collections` entry as an RSS/Atom feed.
+pub const FEED_PREFIX: &str = "feed:";
+
+/// Prefix marking a `collections` entry as a JSON source.
+pub const JSON_PREFIX: &str = "json:";
+
+/// Prefix marking a `collections` entry as a plugin.
+pub const PLUGIN_PREFIX: &str = "plugin:";
+
+/// Prefix marking a `collections` entry as a generated style, e.g.
+/// `generated:mesh`.
+pub const GENERATED_PREFIX: &str = "generated:";
+
+/// `collections` entry for Bing's image of the day.
+pub const BING_ENTRY: &str = "daily:bing";
+
+/// `collections` entry for NASA's Astronomy Picture of the Day.
+pub const APOD_ENTRY: &str = "daily:apod";
+
+/// A photo picked by a source, not yet downloaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Candidate {
+    /// Unique across sources; non-Unsplash IDs carry the source name.
+    pub id: String,
+    pub source: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub author_url: Option<String>,
+    /// Page to view the photo on.
+    pub url: Option<String>,
+    pub image_url: String,
+    pub download_location: Option<String>,
+    pub copyright: Option<String>,
+    pub explanation: Option<String>,
+    /// A file on disk to use instead of downloading `image_url`.
+    pub local_path: Option<PathBuf>,
+    /// A pattern to render instead of downloading `image_url`.
+    pub pattern: Option<Pattern>,
+}
+
+impl Candidate {
+    fn from_unsplash(photo: UnsplashPhoto, quality: ImageQuality) -> Self {
+        Self {
+            image_url: photo.urls.for_quality(quality).to_string(),
+            source: "unsplash".to_string(),
+            title: photo.description.or(photo.alt_description),
+            author: Some(photo.user.name),
+            author_url: photo.user.links.map(|links| with_utm(&links.html)),
+            url: Some(with_utm(&photo.links.html)),
+            download_location: photo.links.download_location,
+            id: photo.id,
+            ..Default::default()
+        }
+    }
+
+    fn from_wallhaven(wallpaper: WallhavenWallpaper) -> Self {
+        Self {
+            id: format!("wallhaven-{}", wallpaper.id),
+            source: "wallhaven".to_string(),
+            title: wallpaper.title(),
+            author_url: wallpaper.uploader_url(),
+            author: wallpaper.uploader.map(|u| u.username),
+            url: Some(wallpaper.url),
+            image_url: wallpaper.path,
+            ..Default::default()
+        }
+    }
+
+    fn from_bing(image: BingImage, base_url: &str, quality: ImageQuality) -> Self {
+        Self {
+            id: format!("bing-{}", image.startdate),
+            source: "bing".to_string(),
+            image_url: image.image_url(base_url, quality),
+            author: image.photographer(),
+            title: image.title,
+            url: image.copyrightlink,
+            copyright: image.copyright,
+            ..Default::default()
+        }
+    }
+
+    fn from_apod(entry: ApodEntry, quality: ImageQuality) -> Self {
+        Self {
+            id: format!("apod-{}", entry.date),
+            source: "apod".to_string(),
+            image_url: entry.image_url(quality).to_string(),
+            author: entry.credit(),
+            copyright: entry.credit().map(|credit| format!("© {credit}")),
+            url: entry.page_url(),
+            title: Some(entry.title),
+            explanation: entry.explanation,
+            ..Default::default()
+        }
+    }
+
+    fn from_feed(item: FeedItem, feed: &FeedSource) -> Self {
+        let key = item.id.as_deref().unwrap_or(&item.image_url);
+        Self {
+            id: format!("feed-{}-{}", feed.name, short_hash(key)),
+            source: format!("feed:{}", feed.name),
+            title: item.title,
+            author: item.author,
+            url: item.link,
+            image_url: item.image_url,
+            ..Default::default()
+        }
+    }
+
+    fn from_json(item: JsonItem, source: &JsonSource) -> Self {
+        let key = item.id.as_deref().unwrap_or(&item.image_url);
+        Self {
+            id: format!("json-{}-{}", source.name, short_hash(key)),
+            source: format!("json:{}", source.name),
+            title: item.title,
+            author: item.author,
+            url: item.link,
+            image_url: item.image_url,
+            ..Default::default()
+        }
+    }
+
+    fn from_plugin(photo: PluginPhoto, plugin: &PluginSource) -> Self {
+        // IDs end up in file names, and both parts come from outside.
+        let file_name_safe = |text: &str| -> String {
+            text.chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect()
+        };
+        Self {
+            id: format!(
+                "plugin-{}-{}",
+                file_name_safe(&plugin.name),
+                file_name_safe(&photo.id)
+            ),
+            source: format!("plugin:{}", plugin.name),
+            title: photo.title,
+            author: photo.author,
+            author_url: photo.author_url,
+            url: photo.url,
+            image_url: photo.image_url.unwrap_or_default(),
+            local_path: photo.path,
+            ..Default::default()
+        }
+    }
+
+    /// A candidate for rendering `pattern`.
+    #[must_use]
+    pub fn generated(pattern: Pattern) -> Self {
+        Self {
+            id: pattern.id(),
+            source: "generated".to_string(),
+            title: Some(format!(
+                "{} ({}x{})",
+                pattern.style.label(),
+                pattern.width,
+                pattern.height
+            )),
+            pattern: Some(pattern),
+            ..Default::default()
+        }
+    }
+
+    /// File extension for the image, from its path or URL.
+    #[must_use]
+    pub fn extension(&self) -> &str {
+        let path = match &self.local_path {
+            Some(path) => path.to_str().unwrap_or_default(),
+            None => self.image_url.split(['?', '#']).next().unwrap_or_default(),
+        };
+        match path.rsplit_once('.').map(|(_, ext)| ext) {
+            Some(ext @ ("jpg" | "jpeg" | "png" | "webp")) => ext,
+            _ => "jpg",
+        }
+    }
+}
+
+/// Somewhere wallpapers come from.
+#[derive(Debug, Clone)]
+pub enum WallpaperSource {
+    Unsplash(PhotoFilter),
+    Wallhaven(WallhavenSearch),
+    Bing,
+    Apod,
+    Feed(FeedSource),
+    Json(JsonSource),
+    Plugin(PluginSource),
+    Generated(GeneratorStyle),
+}
+
+impl WallpaperSource {
+    /// Every source `config` selects that can be fetched from right now.
+    /// Wallhaven entries naming a search that doesn't exist are skipped.
+    #[must_use]
+    pub fn configured(config: &Config) -> Vec<Self> {
+        let mut sources = Vec::new();
+
+        let filter = PhotoFilter::from(config);
+        if filter.has_source() && config.has_access_key() {
+            sources.push(Self::Unsplash(filter));
+        }
+
+        for entry in &config.collections {
+            if let Some(name) = entry.strip_prefix(WALLHAVEN_PREFIX) {
+                if let Some(search) = config.wallhaven_search(name) {
+                    sources.push(Self::Wallhaven(search.clone()));
+                } else {
+                    tracing::warn!("No Wallhaven search named '{name}', skipping");
+                }
+            } else if let Some(name) = entry.strip_prefix(FEED_PREFIX) {
+                if let Some(feed) = config.feed(name) {
+                    sources.push(Self::Feed(feed.clone()));
+                } else {
+                    tracing::warn!("No feed named '{name}', skipping");
+                }
+            } else if let Some(name) = entry.strip_prefix(JSON_PREFIX) {
+                if let Some(source) = config.json_source(name) {
+                    sources.push(Self::Json(source.clone()));
+                } else {
+                    tracing::warn!("No JSON source named '{name}', skipping");
+                }
+            } else if let Some(name) = entry.strip_prefix(PLUGIN_PREFIX) {
+                if let Some(plugin) = config.plugin(name) {
+                    sources.push(Self::Plugin(plugin.clone()));
+                } else {
+                    tracing::warn!("No plugin named '{name}', skipping");
+                }
+            } else if let Some(style) = entry.strip_prefix(GENERATED_PREFIX) {
+                match style.parse() {
+                    Ok(style) => sources.push(Self::Generated(style)),
+                    Err(e) => tracing::warn!("{e}, skipping"),
+                }
+            } else if entry == BING_ENTRY {
+                sources.push(Self::Bing);
+            } else if entry == APOD_ENTRY {
+                sources.push(Self::Apod);
+            }
+        }
+
+        sources
+    }
+
+    /// The configured sources with something new to offer: daily sources drop
+    /// out once their image has been fetched on `today`.
+    #[must_use]
+    pub fn available(app_data: &AppData, today: chrono::NaiveDate) -> Vec<Self> {
+        Self::configured(&app_data.config)
+            .into_iter()
+            .filter(|source| {
+                source
+                    .daily_name()
+                    .is_none_or(|name| !app_data.state.fetched_today(name, today))
+            })
+            .collect()
+    }
+
+    /// A random source from `app_data`'s config.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if nothing is configured, only Unsplash is and its
+    /// access key is missing, or only daily sources are and today's images
+    /// have already been fetched.
+    pub fn pick(app_data: &AppData) -> Result<Self> {
+        let config = &app_data.config;
+        let mut sources = Self::available(app_data, chrono::Local::now().date_naive());
+        if sources.is_empty() {
+            if is_configured(config) {
+                anyhow::bail!(
+                    "Today's image of the day has already been fetched. Try again tomorrow."
+                );
+            }
+            if PhotoFilter::from(config).has_source() {
+                anyhow::bail!("Unsplash Access Key is missing. Run 'wallp setup' to configure.");
+            }
+            anyhow::bail!(
+                "No collections, topics or search query configured. Run 'wallp setup' to add some."
+            );
+        }
+        Ok(sources.swap_remove(fastrand::usize(..sources.len())))
+    }
+
+    /// The name daily sources are tracked under in `State::daily_fetched`, or
+    /// `None` for sources with a fresh photo on every fetch.
+    #[must_use]
+    pub fn daily_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Unsplash(_)
+            | Self::Wallhaven(_)
+            | Self::Feed(_)
+            | Self::Json(_)
+            | Self::Plugin(_)
+            | Self::Generated(_) => None,
+            Self::Bing => Some("bing"),
+            Self::Apod => Some("apod"),
+        }
+    }
+
+    /// Fetch up to `count` candidates in as few requests as the source allows.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the source's API request fails or returns nothing.
+    pub async fn fetch(
+        &self,
+        config: &Config,
+        access_key: &AccessKey,
+        count: usize,
+        quality: ImageQuality,
+    ) -> Result<Vec<Candidate>> {
+        match self {
+            Self::Unsplash(filter) => {
+                let client = UnsplashClient::for_config(config, access_key.get(config)?)?;
+                let photos = client.fetch_random_batch(filter, count).await?;
+                Ok(photos
+                    .into_iter()
+                    .map(|photo| Candidate::from_unsplash(photo, quality))
+                    .collect())
+            }
+            Self::Wallhaven(search) => {
+                let client = WallhavenClient::for_config(config)?;
+                let mut candidates = Vec::new();
+                for result in client.search(search).await?.into_iter().take(count.max(1)) {
+                    // Search results leave out the uploader and tags.
+                    let wallpaper = match client.wallpaper(&result.id).await {
+                        Ok(details) => details,
+                        Err(e) => {
+                            tracing::warn!(
+                                "Failed to fetch Wallhaven details for {}: {e}",
+                                result.id
+                            );
+                            result
+                        }
+                    };
+                    candidates.push(Candidate::from_wallhaven(wallpaper));
+                }
+                Ok(candidates)
+            }
+            // One image a day, whatever `count` asks for.
+            Self::Bing => {
+                let client = BingClient::for_config(config)?;
+                let image = client.image_of_the_day().await?;
+                Ok(vec![Candidate::from_bing(
+                    image,
+                    client.base_url(),
+                    quality,
+                )])
+            }
+            Self::Apod => {
+                let client = ApodClient::for_config(config)?;
+                let entry = client
+                    .latest_image(chrono::Local::now().date_naive())
+                    .await?;
+                Ok(vec![Candidate::from_apod(entry, quality)])
+            }
+            // Feeds are fetched whole, so every item is returned in random
+            // order; callers skip the ones already downloaded.
+            Self::Feed(source) => {
+                let client = http::build_client(&config.network)?;
+                let mut candidates: Vec<Candidate> = feed::fetch(&client, source)
+                    .await?
+                    .into_iter()
+                    .map(|item| Candidate::from_feed(item, source))
+                    .collect();
+                fastrand::shuffle(&mut candidates);
+                Ok(candidates)
+            }
+            Self::Json(source) => {
+                let client = http::build_client(&config.network)?;
+                let mut candidates: Vec<Candidate> = json_source::fetch(&client, source)
+                    .await?
+                    .into_iter()
+                    .map(|item| Candidate::from_json(item, source))
+                    .collect();
+                fastrand::shuffle(&mut candidates);
+                Ok(candidates)
+            }
+            Self::Plugin(source) => {
+                let screen = tokio::task::spawn_blocking(screen::size)
+                    .await
+                    .unwrap_or_default();
+                let request = PluginRequest::new(source, count.max(1), screen, quality);
+                Ok(plugin::run(source, &request)
+                    .await?
+                    .into_iter()
+                    .map(|photo| Candidate::from_plugin(photo, source))
+                    .collect())
+            }
+            Self::Generated(style) => {
+                let screen = tokio::task::spawn_blocking(screen::size)
+                    .await
+                    .unwrap_or_default()
+                    .unwrap_or(generator::DEFAULT_SIZE);
+                Ok((0..count.max(1))
+                    .map(|_| Candidate::generated(Pattern::random(*style, screen)))
+                    .collect())
+            }
+        }
+    }
+}
+
+/// Whether `config` has any source that can be fetched from.
+#[must_use]
+pub fn is_configured(config: &Config) -> bool {
+    !WallpaperSource::configured(config).is_empty()
+}
+
+/// Whether any configured source has something new today, i.e. it isn't only
+/// daily sources whose images were already fetched.
+#[must_use]
+pub fn has_new_today(app_data: &AppData) -> bool {
+    !WallpaperSource::available(app_data, chrono::Local::now().date_naive()).is_empty()
+}
+
+/// How `wallp settings` describes a non-Unsplash `collections` entry.
+#[must_use]
+pub fn describe_entry(config: &Config, entry: &str) -> Option<String> {
+    if let Some(name) = entry.strip_prefix(WALLHAVEN_PREFIX) {
+        return Some(match config.wallhaven_search(name) {
+            Some(search) => format!("Wallhaven: {}", search.summary()),
+            None => "Wallhaven: search not found".to_string(),
+        });
+    }
+    if let Some(name) = entry.strip_prefix(FEED_PREFIX) {
+        return Some(match config.feed(name) {
+            Some(feed) => format!("Feed: {}", feed.url),
+            None => "Feed: not found".to_string(),
+        });
+    }
+    if let Some(name) = entry.strip_prefix(JSON_PREFIX) {
+        return Some(match config.json_source(name) {
+            Some(source) => format!("JSON: {}", source.url),
+            None => "JSON source: not found".to_string(),
+        });
+    }
+    if let Some(name) = entry.strip_prefix(PLUGIN_PREFIX) {
+        return Some(match config.plugin(name) {
+            Some(plugin) => format!("Plugin: {}", plugin.command.display()),
+            None => "Plugin: not found".to_string(),
+        });
+    }
+    if let Some(style) = entry.strip_prefix(GENERATED_PREFIX) {
+        return Some(match style.parse::<GeneratorStyle>() {
+            Ok(style) => format!("Generated: {} (offline)", style.label().to_lowercase()),
+            Err(_) => "Generated: unknown style".to_string(),
+        });
+    }
+    match entry {
+        BING_ENTRY => Some(format!("Bing image of the day ({})", config.bing.market)),
+        APOD_ENTRY => Some("NASA Astronomy Picture of the Day".to_string()),
+        _ => None,
+    }
+}
+
+/// Every non-Unsplash `collections` entry the setup wizard can offer, with
+/// its description.
+#[must_use]
+pub fn selectable_entries(config: &Config) -> Vec<(String, String)> {
+    let mut entries = vec![BING_ENTRY.to_string(), APOD_ENTRY.to_string()];
+    entries.extend(
+        GeneratorStyle::ALL
+            .iter()
+            .map(|style| format!("{GENERATED_PREFIX}{style}")),
+    );
+    entries.extend(
+        config
+            .wallhaven
+            .searches
+            .iter()
+            .map(|s| format!("{WALLHAVEN_PREFIX}{}", s.name)),
+    );
+    entries.extend(
+        config
+            .feeds
+            .iter()
+            .map(|f| format!("{FEED_PREFIX}{}", f.name)),
+    );
+    entries.extend(
+        config
+            .json_sources
+            .iter()
+            .map(|s| format!("{JSON_PREFIX}{}", s.name)),
+    );
+    entries.extend(
+        config
+            .plugins
+            .iter()
+            .map(|p| format!("{PLUGIN_PREFIX}{}", p.name)),
+    );
+    entries
+        .into_iter()
+        .filter_map(|entry| Some((describe_entry(config, &entry)?, entry)))
+        .map(|(desc, entry)| (entry, desc))
+        .collect()
+}
+
+/// A short, stable hex digest (FNV-1a) for building IDs out of URLs.
+pub(crate) fn short_hash(value: &str) -> String {
+    format!("{:016x}", stable_hash(value))
+}
+
+/// The FNV-1a hash of `value`, which unlike `std`'s hasher stays the same
+/// across runs and releases.
+pub(crate) fn stable_hash(value: &str) -> u64 {
+    value.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wallhaven_config() -> Config {
+        let mut config = Config {
+            collections: vec!["wallhaven:anime".to_string(), "wallhaven:gone".to_string()],
+            ..Config::default()
+        };
+        config.wallhaven.searches.push(WallhavenSearch {
+            name: "anime".to_string(),
+            ..Default::default()
+        });
+        config
+    }
+
+    #[test]
+    fn test_configured_sources() {
+        let mut config = wallhaven_config();
+        let sources = WallpaperSource::configured(&config);
+        assert_eq!(sources.len(), 1);
+        assert!(matches!(&sources[0], WallpaperSource::Wallhaven(s) if s.name == "anime"));
+
+        config.unsplash_access_key = "key".to_string();
+        config.collections.push("123".to_string());
+        let sources = WallpaperSource::configured(&config);
+        assert_eq!(sources.len(), 2);
+        assert!(matches!(&sources[0], WallpaperSource::Unsplash(f) if f.collections == ["123"]));
+    }
+
+    fn app_data_with(config: Config) -> AppData {
+        AppData {
+            config,
+            ..AppData::default()
+        }
+    }
+
+    #[test]
+    fn test_pick_reports_missing_key_or_sources() {
+        let app_data = AppData::default();
+        let error = WallpaperSource::pick(&app_data).expect_err("no key");
+        assert!(error.to_string().contains("Access Key is missing"));
+
+        let app_data = app_data_with(Config {
+            collections: Vec::new(),
+            ..Config::default()
+        });
+        let error = WallpaperSource::pick(&app_data).expect_err("no sources");
+        assert!(error.to_string().contains("No collections"));
+
+        assert!(WallpaperSource::pick(&app_data_with(wallhaven_config())).is_ok());
+        assert!(is_configured(&wallhaven_config()));
+        assert!(!is_configured(&Config::default()));
+    }
+
+    #[test]
+    fn test_daily_sources_are_fetched_once_a_day() {
+        let today = chrono::NaiveDate::from_ymd_opt(2026, 10, 18).expect("valid date");
+        let mut app_data = app_data_with(Config {
+            collections: vec![BING_ENTRY.to_string(), APOD_ENTRY.to_string()],
+            ..Config::default()
+        });
+        assert_eq!(WallpaperSource::available(&app_data, today).len(), 2);
+
+        app_data.state.record_daily_fetch("bing", today);
+        let available = WallpaperSource::available(&app_data, today);
+        assert_eq!(available.len(), 1);
+        assert!(matches!(available[0], WallpaperSource::Apod));
+
+        app_data.state.record_daily_fetch("apod", today);
+        assert!(WallpaperSource::available(&app_data, today).is_empty());
+        let tomorrow = today.succ_opt().expect("valid date");
+        assert_eq!(WallpaperSource::available(&app_data, tomorrow).len(), 2);
+    }
+
+    #[test]
+    fn test_describe_entry() {
+        let config = wallhaven_config();
+        assert_eq!(
+            describe_entry(&config, "wallhaven:anime").as_deref(),
+            Some("Wallhaven: any wallpaper")
+        );
+        assert_eq!(
+            describe_entry(&config, BING_ENTRY).as_deref(),
+            Some("Bing image of the day (en-US)")
+        );
+        assert!(describe_entry(&config, "894").is_none());
+    }
+
+    #[test]
+    fn test_feed_and_json_sources() {
+        let config = Config {
+            collections: vec!["feed:blog".to_string(), "json:api".to_string()],
+            feeds: vec![FeedSource {
+                name: "blog".to_string(),
+                url: "https://blog.example.com/rss".to_string(),
+            }],
+            json_sources: vec![JsonSource {
+                name: "api".to_string(),
+                url: "https://api.example.com/photos".to_string(),
+                image: "url".to_string(),
+                ..Default::default()
+            }],
+            ..Config::default()
+        };
+        let sources = WallpaperSource::configured(&config);
+        assert!(matches!(&sources[0], WallpaperSource::Feed(f) if f.name == "blog"));
+        assert!(matches!(&sources[1], WallpaperSource::Json(s) if s.name == "api"));
+
+        let entries = selectable_entries(&config);
+        assert!(entries.contains(&(
+            "feed:blog".to_string(),
+            "Feed: https://blog.example.com/rss".to_string()
+        )));
+        assert_eq!(entries.len(), 4 + GeneratorStyle::ALL.len());
+    }
+
+    #[test]
+    fn test_generated_sources_work_offline() {
+        let config = Config {
+            unsplash_access_key: String::new(),
+            collections: vec!["generated:mesh".to_string(), "generated:plaid".to_string()],
+            ..Config::default()
+        };
+        let sources = WallpaperSource::configured(&config);
+        assert_eq!(sources.len(), 1);
+        assert!(matches!(
+            sources[0],
+            WallpaperSource::Generated(GeneratorStyle::Mesh)
+        ));
+        assert_eq!(
+            describe_entry(&config, "generated:mesh").as_deref(),
+            Some("Generated: mesh gradient (offline)")
+        );
+    }
+
+    #[test]
+    fn test_feed_candidate_ids_are_stable() {
+        let feed = FeedSource {
+            name: "blog".to_string(),
+            ..Default::default()
+        };
+        let item = FeedItem {
+            id: Some("post-1".to_string()),
+            image_url: "https://cdn.example.com/a.jpg".to_string(),
+            ..Default::default()
+        };
+        let first = Candidate::from_feed(item.clone(), &feed);
+        assert_eq!(first.id, Candidate::from_feed(item, &feed).id);
+        assert!(first.id.starts_with("feed-blog-"));
+        assert_eq!(first.source, "feed:blog");
+        assert_ne!(short_hash("a"), short_hash("b"));
+    }
+
+    #[test]
+    fn test_candidate_extension() {
+        let mut candidate = Candidate {
+            image_url: "https://w.wallhaven.cc/full/94/wallhaven-94x38z.png".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(candidate.extension(), "png");
+
+        candidate.image_url = "https://images.unsplash.com/photo-123?ixid=abc&fm=jpg".to_string();
+        assert_eq!(candidate.extension(), "jpg");
+
+        candidate.local_path = Some(PathBuf::from("/srv/photos/offsite.webp"));
+        assert_eq!(candidate.extension(), "webp");
+    }
+
+    #[test]
+    fn test_plugin_candidate_id_is_file_name_safe() {
+        let plugin = PluginSource {
+            name: "internal".to_string(),
+            ..Default::default()
+        };
+        let photo = PluginPhoto {
+            id: "albums/42:7".to_string(),
+            path: Some(PathBuf::from("/srv/photos/7.jpg")),
+            ..Default::default()
+        };
+        let candidate = Candidate::from_plugin(photo, &plugin);
+        assert_eq!(candidate.id, "plugin-internal-albums_42_7");
+        assert_eq!(candidate.source, "plugin:internal");
+        assert!(candidate.image_url.is_empty());
+
+        let plugin = PluginSource {
+            name: "../../evil".to_string(),
+            ..Default::default()
+        };
+        let photo = PluginPhoto {
+            id: "1".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Candidate::from_plugin(photo, &plugin).id,
+            "plugin-______evil-1"
+        );
+    }
+}
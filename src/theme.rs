@@ -0,0 +1,100 @@
+use crate::analysis::Tone;
+
+/// Whether the desktop prefers a dark or light color scheme, if it says.
+#[must_use]
+pub fn system_tone() -> Option<Tone> {
+    #[cfg(target_os = "windows")]
+    {
+        use winreg::RegKey;
+        use winreg::enums::HKEY_CURRENT_USER;
+
+        let personalize = RegKey::predef(HKEY_CURRENT_USER)
+            .open_subkey(r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize")
+            .ok()?;
+        let light: u32 = personalize.get_value("AppsUseLightTheme").ok()?;
+        Some(if light == 0 { Tone::Dark } else { Tone::Light })
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // The key only exists in dark mode.
+        let output = std::process::Command::new("defaults")
+            .args(["read", "-g", "AppleInterfaceStyle"])
+            .output()
+            .ok()?;
+        let dark =
+            output.status.success() && String::from_utf8_lossy(&output.stdout).contains("Dark");
+        Some(if dark { Tone::Dark } else { Tone::Light })
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        portal_tone().or_else(gsettings_tone)
+    }
+}
+
+/// `org.freedesktop.appearance color-scheme` from the XDG desktop portal.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn portal_tone() -> Option<Tone> {
+    let output = std::process::Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.portal.Desktop",
+            "--object-path",
+            "/org/freedesktop/portal/desktop",
+            "--method",
+            "org.freedesktop.portal.Settings.Read",
+            "org.freedesktop.appearance",
+            "color-scheme",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_portal_reply(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The portal answers e.g. `(<<uint32 1>>,)`: 1 prefers dark, 2 prefers light
+/// and 0 has no preference.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn parse_portal_reply(reply: &str) -> Option<Tone> {
+    let value = reply.split("uint32").nth(1)?;
+    let digits: String = value
+        .trim_start()
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    match digits.parse::<u32>().ok()? {
+        1 => Some(Tone::Dark),
+        2 => Some(Tone::Light),
+        _ => None,
+    }
+}
+
+/// GNOME's own setting, for desktops without the portal.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn gsettings_tone() -> Option<Tone> {
+    let output = std::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.interface", "color-scheme"])
+        .output()
+        .ok()?;
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "'prefer-dark'" => Some(Tone::Dark),
+        "'prefer-light'" => Some(Tone::Light),
+        _ => None,
+    }
+}
+
+#[cfg(all(test, not(any(target_os = "windows", target_os = "macos"))))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_portal_reply() {
+        assert_eq!(parse_portal_reply("(<<uint32 1>>,)\n"), Some(Tone::Dark));
+        assert_eq!(parse_portal_reply("(<<uint32 2>>,)"), Some(Tone::Light));
+        assert_eq!(parse_portal_reply("(<<uint32 0>>,)"), None);
+        assert_eq!(parse_portal_reply("Error: GDBus.Error"), None);
+    }
+}
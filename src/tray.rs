@@ -4,16 +4,21 @@ use crate::scheduler;
 use anyhow::Context;
 use notify_rust::Notification;
 use std::process::ExitCode;
+use tao::event::{Event, StartCause};
 use tao::event_loop::{ControlFlow, EventLoop};
 use tray_icon::menu::MenuEvent;
 use tray_icon::{
     TrayIconBuilder,
-    menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
 };
 
 /// Interval between watchdog checks when restarting the scheduler after a crash.
 const WATCHDOG_RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
 
+/// How often the tray re-reads the pause state so a timed pause that ended
+/// (or was changed from the CLI) is reflected in the "Pause Scheduler" checkbox.
+const PAUSE_STATE_REFRESH: std::time::Duration = std::time::Duration::from_secs(30);
+
 #[allow(clippy::too_many_lines)]
 #[must_use]
 pub fn run() -> ExitCode {
@@ -92,6 +97,16 @@ pub fn run() -> ExitCode {
 
     let item_autostart = CheckMenuItem::new("Run at Startup", autostart_enabled, true, None);
     let item_pause = CheckMenuItem::new("Pause Scheduler", !is_running, true, None);
+    let item_pause_hour = MenuItem::new("Pause for 1 Hour", true, None);
+    let item_pause_tomorrow = MenuItem::new("Pause Until Tomorrow", true, None);
+    let pause_submenu =
+        match Submenu::with_items("Pause For", true, &[&item_pause_hour, &item_pause_tomorrow]) {
+            Ok(m) => m,
+            Err(e) => {
+                tracing::error!("Failed to create pause submenu: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
     let item_new = MenuItem::new("New Wallpaper", true, None);
     let item_next = MenuItem::new("Next", true, None);
     let item_prev = MenuItem::new("Previous", true, None);
@@ -108,6 +123,7 @@ pub fn run() -> ExitCode {
         &item_info,
         &PredefinedMenuItem::separator(),
         &item_pause,
+        &pause_submenu,
         &PredefinedMenuItem::separator(),
         &item_folder,
         &item_config,
@@ -145,10 +161,21 @@ pub fn run() -> ExitCode {
 
     // Event Loop (runs forever until exit)
     let exit_code = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        event_loop.run(move |_event, _, control_flow| {
-            *control_flow = ControlFlow::Wait;
+        event_loop.run(move |event, _, control_flow| {
+            *control_flow =
+                ControlFlow::WaitUntil(std::time::Instant::now() + PAUSE_STATE_REFRESH);
 
             if let Err(e) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                if matches!(
+                    event,
+                    Event::NewEvents(StartCause::ResumeTimeReached { .. })
+                ) && let Ok(app_data) = AppData::load()
+                {
+                    let paused = !app_data.state.is_running
+                        && !app_data.state.pause_expired(chrono::Utc::now());
+                    item_pause.set_checked(paused);
+                }
+
                 if let Ok(event) = MenuEvent::receiver().try_recv() {
                     if event.id == item_quit.id() {
                         tracing::info!("Quit requested, exiting");
@@ -240,12 +267,30 @@ pub fn run() -> ExitCode {
                         }
                     } else if event.id == item_pause.id() {
                         let is_paused = item_pause.is_checked();
-                        if let Ok(mut app_data) = AppData::load() {
-                            app_data.state.is_running = !is_paused;
-                            if let Err(e) = app_data.save() {
-                                tracing::error!("Failed to save scheduler state: {e}");
-                                item_pause.set_checked(is_paused);
-                            }
+                        let result = if is_paused {
+                            scheduler::pause(None)
+                        } else {
+                            scheduler::resume()
+                        };
+                        if let Err(e) = result {
+                            tracing::error!("Failed to save scheduler state: {e}");
+                            item_pause.set_checked(!is_paused);
+                        }
+                    } else if event.id == item_pause_hour.id()
+                        || event.id == item_pause_tomorrow.id()
+                    {
+                        let until = if event.id == item_pause_hour.id() {
+                            Some(chrono::Utc::now() + chrono::Duration::hours(1))
+                        } else {
+                            start_of_tomorrow()
+                        };
+                        // Pausing indefinitely instead would never resume on its own.
+                        let result = until
+                            .context("Could not work out when tomorrow starts")
+                            .and_then(|until| scheduler::pause(Some(until)));
+                        match result {
+                            Ok(()) => item_pause.set_checked(true),
+                            Err(e) => tracing::error!("Failed to pause the scheduler: {e}"),
                         }
                     } else if event.id == item_autostart.id() {
                         let is_enabled = item_autostart.is_checked();
@@ -263,9 +308,8 @@ pub fn run() -> ExitCode {
                                 .summary("Wallp Error")
                                 .body(&format!("Failed to toggle autostart: {e}"))
                                 .show();
-                        } else if is_enabled && let Ok(mut app_data) = AppData::load() {
-                            app_data.state.is_running = true;
-                            let _ = app_data.save();
+                        } else if is_enabled && scheduler::resume().is_ok() {
+                            item_pause.set_checked(false);
                         }
                     }
                 }
@@ -321,6 +365,17 @@ fn check_autostart_status() -> bool {
     auto.is_enabled().unwrap_or(false)
 }
 
+/// Local midnight at the start of tomorrow, as UTC.
+fn start_of_tomorrow() -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::Local::now()
+        .date_naive()
+        .succ_opt()?
+        .and_hms_opt(0, 0, 0)?
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .map(|t| t.with_timezone(&chrono::Utc))
+}
+
 fn spawn_oneshot<F, Fut>(f: F)
 where
     F: FnOnce() -> Fut + Send + 'static,
@@ -1,7 +1,7 @@
+use crate::config::{Config, ContentFilter, DEFAULT_API_URL, NetworkConfig};
+use crate::http;
 use anyhow::{Context, Result};
-use serde::Deserialize;
-use std::path::PathBuf;
-use tokio::io::AsyncWriteExt;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize)]
 pub struct UnsplashPhoto {
@@ -16,55 +16,205 @@ pub struct UnsplashPhoto {
 #[derive(Debug, Deserialize)]
 pub struct UnsplashUrls {
     pub full: String,
+    /// 1080px-wide variant, used when bandwidth is constrained.
+    pub regular: Option<String>,
+}
+
+/// Which size of a photo to download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageQuality {
+    Full,
+    Reduced,
+}
+
+impl UnsplashUrls {
+    /// The download URL for `quality`, falling back to the full-size image.
+    #[must_use]
+    pub fn for_quality(&self, quality: ImageQuality) -> &str {
+        match quality {
+            ImageQuality::Full => &self.full,
+            ImageQuality::Reduced => self.regular.as_deref().unwrap_or(&self.full),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UnsplashUser {
     pub name: String,
+    pub links: Option<UnsplashUserLinks>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnsplashUserLinks {
+    pub html: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UnsplashLinks {
     pub html: String,
+    /// Endpoint the API guidelines require us to hit whenever a photo is used.
+    pub download_location: Option<String>,
+}
+
+/// Tag an Unsplash link with the referral parameters required by the API
+/// attribution guidelines. URLs that can't be parsed are returned unchanged.
+#[must_use]
+pub fn with_utm(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return url.to_string();
+    };
+    let already_tagged = parsed.query_pairs().any(|(key, _)| key == "utm_source");
+    if !already_tagged {
+        parsed
+            .query_pairs_mut()
+            .append_pair("utm_source", "wallp")
+            .append_pair("utm_medium", "referral");
+    }
+    parsed.into()
 }
 
+/// A collection as returned by `/collections/:id`.
+#[derive(Debug, Deserialize)]
+pub struct UnsplashCollection {
+    pub title: String,
+    pub total_photos: u64,
+    #[serde(default)]
+    pub private: bool,
+    pub cover_photo: Option<UnsplashCoverPhoto>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnsplashCoverPhoto {
+    pub urls: UnsplashUrls,
+}
+
+/// Which photos `/photos/random` may return.
+#[derive(Debug, Clone, Default)]
+pub struct PhotoFilter {
+    pub collections: Vec<String>,
+    pub topics: Vec<String>,
+    pub query: Option<String>,
+    pub username: Option<String>,
+    pub content_filter: ContentFilter,
+}
+
+impl From<&Config> for PhotoFilter {
+    fn from(config: &Config) -> Self {
+        let non_empty = |s: &Option<String>| {
+            s.as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+        };
+        Self {
+            // Entries like `wallhaven:anime` belong to other sources.
+            collections: config
+                .collections
+                .iter()
+                .filter(|c| !c.contains(':'))
+                .cloned()
+                .collect(),
+            topics: config.topics.clone(),
+            query: non_empty(&config.query),
+            username: non_empty(&config.username),
+            content_filter: config.content_filter,
+        }
+    }
+}
+
+impl PhotoFilter {
+    /// Whether there's anything to pick photos from.
+    #[must_use]
+    pub fn has_source(&self) -> bool {
+        !self.collections.is_empty()
+            || !self.topics.is_empty()
+            || self.query.is_some()
+            || self.username.is_some()
+    }
+
+    /// Query parameters for `/photos/random`. The API rejects `query` combined
+    /// with `collections` or `topics`, so a search query wins over both.
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if let Some(query) = &self.query {
+            params.push(("query", query.clone()));
+        } else {
+            if !self.collections.is_empty() {
+                params.push(("collections", self.collections.join(",")));
+            }
+            if !self.topics.is_empty() {
+                params.push(("topics", self.topics.join(",")));
+            }
+        }
+        if let Some(username) = &self.username {
+            params.push(("username", username.clone()));
+        }
+        params.push(("content_filter", self.content_filter.as_str().to_string()));
+        params
+    }
+}
+
+/// Largest `count` accepted by `/photos/random`.
+const MAX_RANDOM_COUNT: usize = 30;
+
 pub struct UnsplashClient {
     client: reqwest::Client,
     access_key: String,
+    base_url: String,
 }
 
 impl UnsplashClient {
-    #[must_use]
-    #[allow(clippy::missing_panics_doc)]
-    pub fn new(access_key: &str) -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .expect("Failed to build HTTP client");
-        Self {
-            client,
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client can't be built from `network`.
+    pub fn new(access_key: &str, network: &NetworkConfig) -> Result<Self> {
+        Ok(Self {
+            client: http::build_client(network)?,
             access_key: access_key.trim().to_string(),
-        }
+            base_url: DEFAULT_API_URL.to_string(),
+        })
+    }
+
+    /// Send API requests to `base_url` instead of the public Unsplash API.
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// A client using `access_key` (see [`crate::credentials::AccessKey`])
+    /// and the API URL and network settings from `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client can't be built.
+    pub fn for_config(config: &Config, access_key: &str) -> Result<Self> {
+        Ok(Self::new(access_key, &config.network)?.with_base_url(&config.unsplash_api_url()))
     }
 
+    /// Fetch up to `count` random photos (capped at the API's limit of 30) in
+    /// a single request, which costs one unit of rate limit regardless of count.
     ///
     /// # Errors
     ///
     /// Returns an error if the network request fails, if the response is not a valid JSON or missing elements,
     /// or if the API returns an error status code.
-    pub async fn fetch_random(&self, collections: &[String]) -> Result<UnsplashPhoto> {
-        let url = "https://api.unsplash.com/photos/random";
-        let collection_str = collections.join(",");
+    pub async fn fetch_random_batch(
+        &self,
+        filter: &PhotoFilter,
+        count: usize,
+    ) -> Result<Vec<UnsplashPhoto>> {
+        let url = format!("{}/photos/random", self.base_url);
+        let mut params = filter.params();
+        params.push(("orientation", "landscape".to_string()));
+        params.push(("count", count.clamp(1, MAX_RANDOM_COUNT).to_string()));
 
         let response = self
             .client
-            .get(url)
+            .get(&url)
             .header("Authorization", format!("Client-ID {}", self.access_key))
-            .query(&[
-                ("collections", collection_str.as_str()),
-                ("orientation", "landscape"),
-                ("count", "1"),
-            ])
+            .query(&params)
             .send()
             .await
             .context("Failed to send Unsplash request")?;
@@ -80,53 +230,66 @@ impl UnsplashClient {
             .await
             .context("Failed to parse Unsplash response")?;
 
-        photos.into_iter().next().context("No photos returned")
+        if photos.is_empty() {
+            anyhow::bail!("No photos returned");
+        }
+
+        Ok(photos)
     }
 
+    /// Tell Unsplash a photo has been used, as its API guidelines require.
     ///
     /// # Errors
     ///
-    /// Returns an error if the image fails to download or fails to save to the filesystem.
-    pub async fn download_image(&self, url: &str, path: &PathBuf) -> Result<()> {
+    /// Returns an error if the network request fails or the API returns an error status code.
+    pub async fn track_download(&self, download_location: &str) -> Result<()> {
         let response = self
             .client
-            .get(url)
+            .get(download_location)
+            .header("Authorization", format!("Client-ID {}", self.access_key))
             .send()
             .await
-            .context("Failed to download image")?;
+            .context("Failed to send download tracking request")?;
 
-        if !response.status().is_success() {
-            anyhow::bail!("Failed to download image: status {}", response.status());
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("Unsplash download tracking failed with status {status}");
         }
+        Ok(())
+    }
 
-        let bytes = response
-            .bytes()
+    /// Look up a collection by ID. Returns `None` if Unsplash doesn't know it,
+    /// which is also what private collections look like to an app key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network request fails, if the response is not a valid JSON,
+    /// or if the API returns an error status code other than 404.
+    pub async fn fetch_collection(&self, id: &str) -> Result<Option<UnsplashCollection>> {
+        let url = format!("{}/collections/{id}", self.base_url);
+
+        let response = self
+            .client
+            .get(&url)
+            .header("Authorization", format!("Client-ID {}", self.access_key))
+            .send()
             .await
-            .context("Failed to get image bytes")?;
+            .context("Failed to send Unsplash request")?;
 
-        if bytes.len() < 1024 {
-            anyhow::bail!(
-                "Downloaded file too small ({} bytes), likely invalid",
-                bytes.len()
-            );
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
         }
-
-        // Ensure directory exists
-        if let Some(parent) = path.parent() {
-            tokio::fs::create_dir_all(parent)
-                .await
-                .context("Failed to create wallpaper directory")?;
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Unsplash API Error {status}: {text}");
         }
 
-        let mut file = tokio::fs::File::create(path)
-            .await
-            .context("Failed to create image file")?;
-
-        file.write_all(&bytes)
+        let collection = response
+            .json()
             .await
-            .context("Failed to write image to file")?;
-
-        Ok(())
+            .context("Failed to parse Unsplash collection")?;
+        Ok(Some(collection))
     }
 }
 
@@ -135,15 +298,27 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_unsplash_client_new() {
-        let client = UnsplashClient::new("test_key");
+    fn test_unsplash_client_new() -> anyhow::Result<()> {
+        let client = UnsplashClient::new("test_key", &NetworkConfig::default())?;
         assert_eq!(client.access_key, "test_key");
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsplash_client_base_url() -> anyhow::Result<()> {
+        let client = UnsplashClient::new("test_key", &NetworkConfig::default())?;
+        assert_eq!(client.base_url, DEFAULT_API_URL);
+
+        let client = client.with_base_url("http://127.0.0.1:8080/");
+        assert_eq!(client.base_url, "http://127.0.0.1:8080");
+        Ok(())
     }
 
     #[test]
-    fn test_unsplash_client_new_trims_whitespace() {
-        let client = UnsplashClient::new("  test_key  ");
+    fn test_unsplash_client_new_trims_whitespace() -> anyhow::Result<()> {
+        let client = UnsplashClient::new("  test_key  ", &NetworkConfig::default())?;
         assert_eq!(client.access_key, "test_key");
+        Ok(())
     }
 
     #[test]
@@ -195,6 +370,155 @@ mod tests {
         assert_eq!(photo.id, "abc123");
         assert_eq!(photo.description, None);
         assert_eq!(photo.alt_description, None);
+        assert_eq!(photo.urls.regular, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsplash_collection_deserialization() -> anyhow::Result<()> {
+        let json = r#"{
+            "id": "1053828",
+            "title": "Tabliss Official",
+            "total_photos": 120,
+            "private": false,
+            "cover_photo": {
+                "id": "xyz",
+                "urls": {
+                    "full": "https://example.com/full.jpg",
+                    "regular": "https://example.com/regular.jpg"
+                }
+            }
+        }"#;
+
+        let collection: UnsplashCollection = serde_json::from_str(json)?;
+        assert_eq!(collection.title, "Tabliss Official");
+        assert_eq!(collection.total_photos, 120);
+        assert!(!collection.private);
+        assert_eq!(
+            collection.cover_photo.map(|c| c.urls.full),
+            Some("https://example.com/full.jpg".to_string())
+        );
+
+        let empty: UnsplashCollection =
+            serde_json::from_str(r#"{"title": "Empty", "total_photos": 0, "cover_photo": null}"#)?;
+        assert_eq!(empty.total_photos, 0);
+        assert!(empty.cover_photo.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsplash_photo_attribution_links() -> anyhow::Result<()> {
+        let json = r#"{
+            "id": "abc123",
+            "urls": { "full": "https://example.com/full.jpg" },
+            "user": {
+                "name": "John Doe",
+                "links": { "html": "https://unsplash.com/@johndoe" }
+            },
+            "links": {
+                "html": "https://unsplash.com/photos/abc123",
+                "download_location": "https://api.unsplash.com/photos/abc123/download?ixid=xyz"
+            }
+        }"#;
+
+        let photo: UnsplashPhoto = serde_json::from_str(json)?;
+        assert_eq!(
+            photo.links.download_location.as_deref(),
+            Some("https://api.unsplash.com/photos/abc123/download?ixid=xyz")
+        );
+        assert_eq!(
+            photo.user.links.map(|l| l.html),
+            Some("https://unsplash.com/@johndoe".to_string())
+        );
         Ok(())
     }
+
+    #[test]
+    fn test_with_utm() {
+        assert_eq!(
+            with_utm("https://unsplash.com/@johndoe"),
+            "https://unsplash.com/@johndoe?utm_source=wallp&utm_medium=referral"
+        );
+        assert_eq!(
+            with_utm("https://unsplash.com/photos/abc123?foo=bar"),
+            "https://unsplash.com/photos/abc123?foo=bar&utm_source=wallp&utm_medium=referral"
+        );
+        let tagged = with_utm("https://unsplash.com/photos/abc123");
+        assert_eq!(with_utm(&tagged), tagged);
+        assert_eq!(with_utm("not a url"), "not a url");
+    }
+
+    #[test]
+    fn test_urls_for_quality() {
+        let urls = UnsplashUrls {
+            full: "https://example.com/full.jpg".to_string(),
+            regular: Some("https://example.com/regular.jpg".to_string()),
+        };
+        assert_eq!(urls.for_quality(ImageQuality::Full), urls.full);
+        assert_eq!(
+            urls.for_quality(ImageQuality::Reduced),
+            "https://example.com/regular.jpg"
+        );
+
+        let full_only = UnsplashUrls {
+            full: "https://example.com/full.jpg".to_string(),
+            regular: None,
+        };
+        assert_eq!(full_only.for_quality(ImageQuality::Reduced), full_only.full);
+    }
+
+    #[test]
+    fn test_photo_filter_collections_and_topics() {
+        let filter = PhotoFilter {
+            collections: vec!["123".to_string(), "456".to_string()],
+            topics: vec!["wallpapers".to_string()],
+            ..Default::default()
+        };
+        let params = filter.params();
+        assert!(params.contains(&("collections", "123,456".to_string())));
+        assert!(params.contains(&("topics", "wallpapers".to_string())));
+        assert!(params.contains(&("content_filter", "low".to_string())));
+        assert!(!params.iter().any(|(k, _)| *k == "query"));
+    }
+
+    #[test]
+    fn test_photo_filter_query_overrides_collections() {
+        let filter = PhotoFilter {
+            collections: vec!["123".to_string()],
+            topics: vec!["nature".to_string()],
+            query: Some("mountains fog".to_string()),
+            username: Some("someone".to_string()),
+            content_filter: ContentFilter::High,
+        };
+        let params = filter.params();
+        assert!(params.contains(&("query", "mountains fog".to_string())));
+        assert!(params.contains(&("username", "someone".to_string())));
+        assert!(params.contains(&("content_filter", "high".to_string())));
+        assert!(
+            !params
+                .iter()
+                .any(|(k, _)| *k == "collections" || *k == "topics")
+        );
+    }
+
+    #[test]
+    fn test_photo_filter_from_config_ignores_blank_strings() {
+        let config = Config {
+            collections: Vec::new(),
+            query: Some("  ".to_string()),
+            username: Some(String::new()),
+            ..Default::default()
+        };
+        let filter = PhotoFilter::from(&config);
+        assert!(filter.query.is_none());
+        assert!(filter.username.is_none());
+        assert!(!filter.has_source());
+
+        let config = Config {
+            collections: Vec::new(),
+            topics: vec!["wallpapers".to_string()],
+            ..Default::default()
+        };
+        assert!(PhotoFilter::from(&config).has_source());
+    }
 }
@@ -0,0 +1,264 @@
+use crate::config::{Config, WallhavenCategory, WallhavenPurity, WallhavenSearch};
+use crate::http;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    data: Vec<WallhavenWallpaper>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WallpaperResponse {
+    data: WallhavenWallpaper,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WallhavenWallpaper {
+    pub id: String,
+    /// Page on wallhaven.cc.
+    pub url: String,
+    /// Full-size image.
+    pub path: String,
+    /// Only included when fetching a single wallpaper.
+    pub uploader: Option<WallhavenUploader>,
+    #[serde(default)]
+    pub tags: Vec<WallhavenTag>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WallhavenUploader {
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WallhavenTag {
+    pub name: String,
+}
+
+impl WallhavenWallpaper {
+    /// A readable title from the first few tags.
+    #[must_use]
+    pub fn title(&self) -> Option<String> {
+        let names: Vec<&str> = self.tags.iter().take(3).map(|t| t.name.as_str()).collect();
+        (!names.is_empty()).then(|| names.join(", "))
+    }
+
+    /// The uploader's profile page.
+    #[must_use]
+    pub fn uploader_url(&self) -> Option<String> {
+        self.uploader
+            .as_ref()
+            .map(|u| format!("https://wallhaven.cc/user/{}", u.username))
+    }
+}
+
+/// Query parameters for `/search`, always sorted randomly.
+#[must_use]
+pub fn search_params(search: &WallhavenSearch) -> Vec<(&'static str, String)> {
+    let mut params = vec![("sorting", "random".to_string())];
+
+    let query = search.tags.join(" ");
+    if !query.trim().is_empty() {
+        params.push(("q", query.trim().to_string()));
+    }
+
+    let categories = if search.categories.is_empty() {
+        "111".to_string()
+    } else {
+        [
+            WallhavenCategory::General,
+            WallhavenCategory::Anime,
+            WallhavenCategory::People,
+        ]
+        .iter()
+        .map(|c| {
+            if search.categories.contains(c) {
+                '1'
+            } else {
+                '0'
+            }
+        })
+        .collect()
+    };
+    params.push(("categories", categories));
+
+    let purity = if search.purity.is_empty() {
+        "100".to_string()
+    } else {
+        [
+            WallhavenPurity::Sfw,
+            WallhavenPurity::Sketchy,
+            WallhavenPurity::Nsfw,
+        ]
+        .iter()
+        .map(|p| if search.purity.contains(p) { '1' } else { '0' })
+        .collect()
+    };
+    params.push(("purity", purity));
+
+    if let Some(resolution) = search
+        .min_resolution
+        .as_deref()
+        .filter(|r| !r.trim().is_empty())
+    {
+        params.push(("atleast", resolution.trim().to_string()));
+    }
+    if !search.ratios.is_empty() {
+        params.push(("ratios", search.ratios.join(",")));
+    }
+
+    params
+}
+
+pub struct WallhavenClient {
+    client: reqwest::Client,
+    api_key: Option<String>,
+    base_url: String,
+}
+
+impl WallhavenClient {
+    /// A client using the API key, API URL and network settings from `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client can't be built.
+    pub fn for_config(config: &Config) -> Result<Self> {
+        Ok(Self {
+            client: http::build_client(&config.network)?,
+            api_key: config
+                .wallhaven
+                .api_key
+                .as_deref()
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .map(String::from),
+            base_url: config.wallhaven_api_url(),
+        })
+    }
+
+    /// Random wallpapers matching `search` (up to one page, 24 results).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network request fails, the API returns an error status
+    /// (e.g. 401 when asking for NSFW without an API key), or nothing matches.
+    pub async fn search(&self, search: &WallhavenSearch) -> Result<Vec<WallhavenWallpaper>> {
+        let response: SearchResponse = self
+            .get("search", &search_params(search))
+            .await
+            .context("Failed to parse Wallhaven search results")?;
+
+        if response.data.is_empty() {
+            anyhow::bail!("No Wallhaven wallpapers match search '{}'", search.name);
+        }
+        Ok(response.data)
+    }
+
+    /// Full details of one wallpaper, including its uploader and tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network request fails or the API returns an error status.
+    pub async fn wallpaper(&self, id: &str) -> Result<WallhavenWallpaper> {
+        let response: WallpaperResponse = self
+            .get(&format!("w/{id}"), &[])
+            .await
+            .context("Failed to parse Wallhaven wallpaper")?;
+        Ok(response.data)
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> Result<T> {
+        let mut request = self
+            .client
+            .get(format!("{}/{endpoint}", self.base_url))
+            .query(params);
+        if let Some(key) = &self.api_key {
+            request = request.header("X-API-Key", key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .context("Failed to send Wallhaven request")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Wallhaven API Error {status}: {text}");
+        }
+
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_params_defaults() {
+        let search = WallhavenSearch {
+            name: "any".to_string(),
+            ..Default::default()
+        };
+        let params = search_params(&search);
+        assert_eq!(
+            params,
+            vec![
+                ("sorting", "random".to_string()),
+                ("categories", "111".to_string()),
+                ("purity", "100".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_params_filters() {
+        let search = WallhavenSearch {
+            name: "anime".to_string(),
+            tags: vec!["landscape".to_string(), "+night".to_string()],
+            categories: vec![WallhavenCategory::Anime],
+            purity: vec![WallhavenPurity::Sfw, WallhavenPurity::Sketchy],
+            min_resolution: Some("2560x1440".to_string()),
+            ratios: vec!["16x9".to_string(), "21x9".to_string()],
+        };
+        let params = search_params(&search);
+        assert!(params.contains(&("q", "landscape +night".to_string())));
+        assert!(params.contains(&("categories", "010".to_string())));
+        assert!(params.contains(&("purity", "110".to_string())));
+        assert!(params.contains(&("atleast", "2560x1440".to_string())));
+        assert!(params.contains(&("ratios", "16x9,21x9".to_string())));
+    }
+
+    #[test]
+    fn test_wallpaper_deserialization() -> anyhow::Result<()> {
+        let json = r#"{"data": {
+            "id": "94x38z",
+            "url": "https://wallhaven.cc/w/94x38z",
+            "path": "https://w.wallhaven.cc/full/94/wallhaven-94x38z.jpg",
+            "resolution": "3840x2160",
+            "uploader": {"username": "artist", "group": "User"},
+            "tags": [{"id": 1, "name": "anime"}, {"id": 2, "name": "night"}]
+        }}"#;
+
+        let wallpaper = serde_json::from_str::<WallpaperResponse>(json)?.data;
+        assert_eq!(wallpaper.id, "94x38z");
+        assert_eq!(wallpaper.title().as_deref(), Some("anime, night"));
+        assert_eq!(
+            wallpaper.uploader_url().as_deref(),
+            Some("https://wallhaven.cc/user/artist")
+        );
+
+        let search: SearchResponse = serde_json::from_str(
+            r#"{"data": [{"id": "abc", "url": "u", "path": "p"}], "meta": {}}"#,
+        )?;
+        assert!(search.data[0].uploader.is_none());
+        assert!(search.data[0].title().is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+#![cfg(target_os = "linux")]
+//! Command output for scripts (`--json`) and the `config` and `settings`
+//! commands.
+
+mod support;
+
+use predicates::prelude::*;
+use predicates::str::contains;
+use wallp::config::{CustomCollection, Wallpaper};
+
+fn json_output(assert: &assert_cmd::assert::Assert) -> anyhow::Result<serde_json::Value> {
+    Ok(serde_json::from_slice(&assert.get_output().stdout)?)
+}
+
+#[test]
+fn test_json_output() -> anyhow::Result<()> {
+    let (_mock, home) = support::start()?;
+
+    let new = json_output(&home.wallp().args(["new", "--json"]).assert().success())?;
+    assert_eq!(new["action"], "new");
+    assert_eq!(new["wallpaper"]["id"], "mock1");
+    home.wallp().arg("new").assert().success();
+
+    let list = json_output(
+        &home
+            .wallp()
+            .args(["list", "--format", "json"])
+            .assert()
+            .success(),
+    )?;
+    let numbers: Vec<_> = list["wallpapers"]
+        .as_array()
+        .expect("wallpapers")
+        .iter()
+        .map(|w| {
+            (
+                w["number"].as_u64(),
+                w["id"].as_str(),
+                w["current"].as_bool(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        numbers,
+        vec![
+            (Some(0), Some("mock2"), Some(true)),
+            (Some(1), Some("mock1"), Some(false))
+        ]
+    );
+
+    let set = json_output(&home.wallp().args(["--json", "set", "1"]).assert().success())?;
+    assert_eq!(set["wallpaper"]["id"], "mock1");
+    let info = json_output(&home.wallp().args(["info", "--json"]).assert().success())?;
+    assert_eq!(info["wallpaper"]["author"], "Mock Photographer");
+
+    let pause = json_output(
+        &home
+            .wallp()
+            .args(["pause", "2h", "--json"])
+            .assert()
+            .success(),
+    )?;
+    assert_eq!(pause["running"], false);
+    let status = json_output(&home.wallp().args(["status", "--json"]).assert().success())?;
+    assert_eq!(status["state"], "paused");
+    assert_eq!(status["current"]["number"], 1);
+
+    let lockscreen = json_output(
+        &home
+            .wallp()
+            .args(["lockscreen", "on", "--json"])
+            .assert()
+            .success(),
+    )?;
+    assert_eq!(lockscreen["enabled"], true);
+    let settings = json_output(&home.wallp().args(["settings", "--json"]).assert().success())?;
+    assert_eq!(settings["lockscreen"]["enabled"], true);
+
+    // Nothing to prompt for, so asking for a choice fails instead.
+    let error = json_output(&home.wallp().args(["set", "--json"]).assert().failure())?;
+    assert!(
+        error["error"]
+            .as_str()
+            .is_some_and(|e| e.contains("number"))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_config_get_set_unset() -> anyhow::Result<()> {
+    let (_mock, home) = support::start()?;
+
+    home.wallp()
+        .args(["config", "set", "interval_minutes", "12h"])
+        .assert()
+        .success()
+        .stdout(contains("interval_minutes = 720"));
+    home.wallp()
+        .args(["config", "set", "topics", "nature, travel"])
+        .assert()
+        .success();
+    home.wallp()
+        .args(["config", "set", "overlay.enabled", "on"])
+        .assert()
+        .success();
+    home.wallp()
+        .args(["config", "set", "wallhaven.api_key", "wallhaven-secret"])
+        .assert()
+        .success()
+        .stdout("✅ wallhaven.api_key = ****cret\n");
+
+    let config = home.app_data()?.config;
+    assert_eq!(config.interval_minutes, 720);
+    assert_eq!(config.topics, vec!["nature", "travel"]);
+    assert!(config.overlay.enabled);
+
+    home.wallp()
+        .args(["config", "get", "wallhaven.api_key"])
+        .assert()
+        .success()
+        .stdout("****cret\n");
+    home.wallp()
+        .args(["config", "get", "wallhaven.api_key", "--reveal"])
+        .assert()
+        .success()
+        .stdout("wallhaven-secret\n");
+    home.wallp()
+        .args(["config", "get", "wallhaven"])
+        .assert()
+        .success()
+        .stdout(contains("****cret").and(contains("wallhaven-secret").not()));
+    home.wallp()
+        .args(["config", "list"])
+        .assert()
+        .success()
+        .stdout(
+            contains("topics = [\"nature\",\"travel\"]")
+                .and(contains("wallhaven.api_key = \"****cret\""))
+                .and(contains("wallhaven-secret").not()),
+        );
+
+    home.wallp()
+        .args(["config", "unset", "topics", "--json"])
+        .assert()
+        .success()
+        .stdout(contains("\"value\": []"));
+    assert!(home.app_data()?.config.topics.is_empty());
+
+    home.wallp()
+        .args(["config", "set", "interval_minutes", "5m"])
+        .assert()
+        .failure()
+        .stderr(contains("at least 30 minutes"));
+    home.wallp()
+        .args(["config", "set", "content_filter", "extreme"])
+        .assert()
+        .failure()
+        .stderr(contains("Invalid value 'extreme' for content_filter"));
+    home.wallp()
+        .args(["config", "get", "no_such_setting"])
+        .assert()
+        .failure()
+        .stderr(contains("Unknown setting"));
+    assert_eq!(home.app_data()?.config.interval_minutes, 720);
+    Ok(())
+}
+
+#[test]
+fn test_settings_validates_custom_collections() -> anyhow::Result<()> {
+    let (mock, home) = support::start_with(|data| {
+        data.config.collections = vec!["777".to_string(), "404404".to_string()];
+        data.config.custom_collections = ["777", "404404"]
+            .into_iter()
+            .map(|id| CustomCollection {
+                id: id.to_string(),
+                title: format!("Collection {id}"),
+                ..Default::default()
+            })
+            .collect();
+    })?;
+    mock.hide_collection("404404");
+
+    home.wallp()
+        .arg("settings")
+        .assert()
+        .success()
+        .stdout(contains("Mock Collection 777, 42 photos"))
+        .stdout(contains(
+            "(404404) no longer exists or has been made private",
+        ));
+
+    let custom = home.app_data()?.config.custom_collections;
+    assert_eq!(custom[0].total_photos, Some(42));
+    assert!(custom[1].unavailable);
+    assert!(custom.iter().all(|c| c.checked_at.is_some()));
+    Ok(())
+}
+
+#[test]
+fn test_set_reports_a_missing_file_in_its_error() -> anyhow::Result<()> {
+    let (_mock, home) = support::start_with(|data| {
+        data.history.push(Wallpaper {
+            id: "gone".to_string(),
+            filename: "wallpaper_gone.jpg".to_string(),
+            ..Default::default()
+        });
+    })?;
+
+    let assert = home.wallp().args(["set", "0"]).assert().failure();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+    assert_eq!(stderr.lines().count(), 1, "no separate warning: {stderr}");
+    assert!(stderr.contains("Removed it from history"));
+    assert!(home.app_data()?.history.is_empty());
+    Ok(())
+}
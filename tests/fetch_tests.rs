@@ -0,0 +1,360 @@
+#![cfg(target_os = "linux")]
+//! Fetching, downloading and applying wallpapers from the mock Unsplash API.
+
+mod support;
+
+use predicates::str::contains;
+use std::fs;
+use support::{MockUnsplash, TestHome};
+use wallp::config::{API_URL_ENV, AccessKeySource, Brightness, Wallpaper};
+
+#[test]
+fn test_new_downloads_and_records_history() -> anyhow::Result<()> {
+    let (mock, home) = support::start()?;
+
+    home.wallp().arg("new").assert().success();
+
+    let app_data = home.app_data()?;
+    assert_eq!(app_data.history.len(), 1);
+    let wallpaper = &app_data.history[0];
+    assert_eq!(wallpaper.id, "mock1");
+    assert_eq!(wallpaper.author.as_deref(), Some("Mock Photographer"));
+    assert!(!wallpaper.applied_at.is_empty());
+    assert!(
+        wallpaper
+            .url
+            .as_deref()
+            .is_some_and(|u| u.contains("utm_source=wallp"))
+    );
+    assert_eq!(
+        app_data.state.current_wallpaper_id.as_deref(),
+        Some("mock1")
+    );
+    assert_eq!(home.downloaded_files()?, vec!["wallpaper_mock1.jpg"]);
+
+    let requests = mock.requests();
+    assert!(
+        requests
+            .iter()
+            .any(|r| r.starts_with("GET /photos/random?") && r.contains("collections="))
+    );
+    assert!(
+        requests
+            .iter()
+            .any(|r| r.starts_with("GET /images/mock1.jpg"))
+    );
+    assert!(
+        requests
+            .iter()
+            .any(|r| r.starts_with("GET /photos/mock1/download"))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_next_fetches_new_photo_at_end_of_history() -> anyhow::Result<()> {
+    let (_mock, home) = support::start()?;
+
+    home.wallp().arg("new").assert().success();
+    home.wallp().arg("next").assert().success();
+
+    let app_data = home.app_data()?;
+    let ids: Vec<&str> = app_data.history.iter().map(|w| w.id.as_str()).collect();
+    assert_eq!(ids, vec!["mock1", "mock2"]);
+    assert_eq!(app_data.state.current_history_index, 1);
+    Ok(())
+}
+
+#[test]
+fn test_prefetched_photos_are_applied_first() -> anyhow::Result<()> {
+    let (mock, home) = support::start_with(|data| {
+        data.config.prefetch_count = 2;
+        data.queue = vec![Wallpaper {
+            id: "queued".to_string(),
+            filename: "wallpaper_queued.jpg".to_string(),
+            ..Default::default()
+        }];
+    })?;
+    fs::create_dir_all(home.wallpapers_dir())?;
+    fs::rename(
+        home.write_jpeg("queued.jpg")?,
+        home.wallpapers_dir().join("wallpaper_queued.jpg"),
+    )?;
+
+    // The queued photo is applied without asking the API, and refilling the
+    // queue is left to the scheduler so the command returns straight away.
+    home.wallp().arg("new").assert().success();
+    let app_data = home.app_data()?;
+    assert_eq!(
+        app_data.history.last().map(|w| w.id.as_str()),
+        Some("queued")
+    );
+    assert!(app_data.queue.is_empty());
+    assert!(
+        !mock
+            .requests()
+            .iter()
+            .any(|r| r.starts_with("GET /photos/random"))
+    );
+
+    home.wallp().arg("new").assert().success();
+    assert_eq!(
+        home.app_data()?.history.last().map(|w| w.id.as_str()),
+        Some("mock1")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_brightness_rejects_mismatched_photos() -> anyhow::Result<()> {
+    let (mock, home) = support::start_with(|data| data.config.brightness = Brightness::Dark)?;
+    mock.shade_images(&["mock3"]);
+
+    home.wallp().arg("new").assert().success();
+    let app_data = home.app_data()?;
+    let wallpaper = app_data.history.last().expect("a wallpaper was applied");
+    assert_eq!(wallpaper.id, "mock3");
+    assert!(wallpaper.luminance.is_some_and(|l| l < 0.2));
+    assert_eq!(home.downloaded_files()?, vec!["wallpaper_mock3.jpg"]);
+
+    // Following a light system theme, the first photo is fine.
+    let mut app_data = home.app_data()?;
+    app_data.config.brightness = Brightness::System;
+    home.write_app_data(&app_data)?;
+    home.stub("gdbus", "echo '(<<uint32 2>>,)'")?;
+    home.wallp().arg("new").assert().success();
+    let app_data = home.app_data()?;
+    assert_eq!(
+        app_data.history.last().map(|w| w.id.as_str()),
+        Some("mock4")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_copies_are_skipped_and_deduped() -> anyhow::Result<()> {
+    let (mock, home) = support::start()?;
+    mock.copy_image("mock2", "mock1");
+
+    home.wallp().arg("new").assert().success();
+    home.wallp().arg("new").assert().success();
+    let app_data = home.app_data()?;
+    let ids: Vec<&str> = app_data.history.iter().map(|w| w.id.as_str()).collect();
+    assert_eq!(ids, vec!["mock1", "mock3"]);
+    assert!(app_data.history.iter().all(|w| w.image_hash.is_some()));
+    assert_eq!(
+        home.downloaded_files()?,
+        vec!["wallpaper_mock1.jpg", "wallpaper_mock3.jpg"]
+    );
+
+    // A smaller copy from before hashes were recorded.
+    fs::write(
+        home.wallpapers_dir().join("wallpaper_old.jpg"),
+        support::half_size_jpeg("mock1.jpg")?,
+    )?;
+    let mut app_data = home.app_data()?;
+    app_data.history.insert(
+        0,
+        Wallpaper {
+            id: "old".to_string(),
+            filename: "wallpaper_old.jpg".to_string(),
+            ..Default::default()
+        },
+    );
+    app_data.state.current_history_index = 2;
+    home.write_app_data(&app_data)?;
+
+    home.wallp()
+        .arg("dedupe")
+        .assert()
+        .success()
+        .stdout(contains(
+            "wallpaper_mock1.jpg (kept)\n  ≈ wallpaper_old.jpg",
+        ));
+    assert!(home.wallpapers_dir().join("wallpaper_old.jpg").exists());
+
+    home.wallp().args(["dedupe", "--remove"]).assert().success();
+    let app_data = home.app_data()?;
+    let ids: Vec<&str> = app_data.history.iter().map(|w| w.id.as_str()).collect();
+    assert_eq!(ids, vec!["mock1", "mock3"]);
+    assert_eq!(app_data.state.current_history_index, 1);
+    assert!(!home.wallpapers_dir().join("wallpaper_old.jpg").exists());
+    Ok(())
+}
+
+#[test]
+fn test_retention_cleans_up_old_wallpapers() -> anyhow::Result<()> {
+    let (_mock, home) = support::start_with(|data| data.config.retention_days = Some(0))?;
+
+    home.wallp().arg("new").assert().success();
+    home.wallp().arg("new").assert().success();
+
+    let app_data = home.app_data()?;
+    assert_eq!(app_data.history.len(), 1);
+    assert_eq!(app_data.history[0].id, "mock2");
+    assert_eq!(home.downloaded_files()?, vec!["wallpaper_mock2.jpg"]);
+    Ok(())
+}
+
+#[test]
+fn test_api_error_leaves_history_untouched() -> anyhow::Result<()> {
+    let (mock, home) = support::start()?;
+    mock.fail_random(401);
+
+    home.wallp()
+        .arg("new")
+        .assert()
+        .failure()
+        .stderr(contains("401"));
+
+    assert!(home.app_data()?.history.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_truncated_image_is_rejected() -> anyhow::Result<()> {
+    let (mock, home) = support::start()?;
+    mock.serve_tiny_images();
+
+    home.wallp()
+        .arg("new")
+        .assert()
+        .failure()
+        .stderr(contains("too small"));
+
+    assert!(home.app_data()?.history.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_interrupted_download_is_resumed() -> anyhow::Result<()> {
+    let (mock, home) = support::start()?;
+    mock.interrupt_next_image();
+
+    home.wallp().arg("new").assert().success();
+
+    assert_eq!(mock.ranges().len(), 1);
+    assert_ne!(mock.ranges()[0], "bytes=0-");
+    assert_eq!(home.downloaded_files()?, vec!["wallpaper_mock1.jpg"]);
+    let bytes = fs::read(home.wallpapers_dir().join("wallpaper_mock1.jpg"))?;
+    assert!(image::load_from_memory(&bytes).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_stale_partial_download_is_not_resumed() -> anyhow::Result<()> {
+    let (mock, home) = support::start()?;
+    // Left behind by a run that was killed while fetching another image.
+    fs::create_dir_all(home.wallpapers_dir())?;
+    fs::write(
+        home.wallpapers_dir().join("wallpaper_mock1.jpg.part"),
+        vec![0xAB; 4096],
+    )?;
+
+    home.wallp().arg("new").assert().success();
+
+    assert!(mock.ranges().is_empty());
+    assert_eq!(home.downloaded_files()?, vec!["wallpaper_mock1.jpg"]);
+    let bytes = fs::read(home.wallpapers_dir().join("wallpaper_mock1.jpg"))?;
+    assert!(image::load_from_memory(&bytes).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_corrupt_image_is_rejected_without_leftovers() -> anyhow::Result<()> {
+    let (mock, home) = support::start()?;
+    mock.serve_corrupt_images();
+
+    home.wallp()
+        .arg("new")
+        .assert()
+        .failure()
+        .stderr(contains("not a valid image"));
+
+    assert!(home.app_data()?.history.is_empty());
+    assert!(home.downloaded_files()?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_requests_go_through_configured_proxy() -> anyhow::Result<()> {
+    let mock = MockUnsplash::start()?;
+    let home = TestHome::new(&mock, |data| {
+        data.config.network.proxy = Some(mock.url.clone());
+    })?;
+
+    home.wallp()
+        .env(API_URL_ENV, "http://unsplash.invalid")
+        .arg("new")
+        .assert()
+        .success();
+
+    assert!(
+        mock.requests()
+            .iter()
+            .any(|r| r.starts_with("GET http://unsplash.invalid/photos/random?"))
+    );
+    assert_eq!(home.app_data()?.history.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_invalid_proxy_is_reported_instead_of_panicking() -> anyhow::Result<()> {
+    let (_mock, home) = support::start_with(|data| {
+        data.config.network.proxy = Some("not a url".to_string());
+    })?;
+
+    home.wallp()
+        .arg("new")
+        .assert()
+        .failure()
+        .stderr(contains("Invalid proxy URL"));
+    Ok(())
+}
+
+#[test]
+fn test_access_key_read_from_environment() -> anyhow::Result<()> {
+    let (_mock, home) = support::start_with(|data| {
+        data.config.unsplash_access_key.clear();
+        data.config.access_key_source = AccessKeySource::Env("WALLP_TEST_KEY".to_string());
+    })?;
+
+    home.wallp()
+        .env("WALLP_TEST_KEY", "env_access_key")
+        .arg("settings")
+        .assert()
+        .success()
+        .stdout(contains("API Key: ****_key"))
+        .stdout(contains(
+            "API Key Source: environment variable WALLP_TEST_KEY",
+        ));
+
+    home.wallp()
+        .env("WALLP_TEST_KEY", "env_access_key")
+        .arg("new")
+        .assert()
+        .success();
+    assert_eq!(home.app_data()?.history.len(), 1);
+
+    home.wallp()
+        .env_remove("WALLP_TEST_KEY")
+        .arg("new")
+        .assert()
+        .failure()
+        .stderr(contains("WALLP_TEST_KEY is not set"));
+    Ok(())
+}
+
+#[test]
+fn test_missing_access_key_is_reported() -> anyhow::Result<()> {
+    let (mock, home) = support::start_with(|data| data.config.unsplash_access_key.clear())?;
+
+    home.wallp()
+        .arg("new")
+        .assert()
+        .failure()
+        .stderr(contains("Access Key is missing"));
+
+    assert!(mock.requests().is_empty());
+    Ok(())
+}
@@ -0,0 +1,36 @@
+#!/bin/sh
+# A wallp plugin for the integration tests. `options.mode` makes it misbehave;
+# otherwise it offers one photo from `options.image_url` or `options.path`.
+
+request=$(cat)
+echo "request: $request" >&2
+
+option() {
+    printf '%s' "$request" | sed -n "s/.*\"$1\":\"\([^\"]*\)\".*/\1/p"
+}
+width=$(printf '%s' "$request" | sed -n 's/.*"width":\([0-9]*\).*/\1/p')
+
+case "$(option mode)" in
+sleep)
+    exec sleep 30
+    ;;
+fail)
+    echo "album not found" >&2
+    exit 3
+    ;;
+error)
+    echo '{"error": "quota exceeded"}'
+    exit 0
+    ;;
+esac
+
+if [ -n "$(option path)" ]; then
+    image="\"path\": \"$(option path)\""
+else
+    image="\"image_url\": \"$(option image_url)\""
+fi
+
+cat <<JSON
+{"photos": [{"id": "album/1", "title": "Offsite ${width:-unknown}",
+             "author": "Plugin Photographer", $image}]}
+JSON
@@ -0,0 +1,93 @@
+#![cfg(target_os = "linux")]
+//! Drawing on wallpapers and previewing them: thumbnails, overlays and effects.
+
+mod support;
+
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use wallp::config::{Effect, PreviewProtocol};
+
+#[test]
+fn test_thumbnails_and_terminal_preview() -> anyhow::Result<()> {
+    let (_mock, home) = support::start_with(|data| {
+        data.config.preview_protocol = PreviewProtocol::Blocks;
+    })?;
+
+    home.wallp().arg("new").assert().success();
+    let thumbnails = home.wallpapers_dir().with_file_name("thumbnails");
+    assert!(thumbnails.join("wallpaper_mock1-thumb.jpg").exists());
+
+    home.wallp()
+        .arg("preview")
+        .assert()
+        .success()
+        .stdout(contains("▀").and(contains("Mock photo mock1 by Mock Photographer")));
+    home.wallp()
+        .args(["list", "--thumbs"])
+        .assert()
+        .success()
+        .stdout(contains("\x1b[38;2;").and(contains("0: Mock photo mock1")));
+    home.wallp().args(["preview", "3"]).assert().failure();
+    Ok(())
+}
+
+#[test]
+fn test_overlay_is_drawn_on_a_copy() -> anyhow::Result<()> {
+    if wallp::overlay::system_font().is_none() {
+        return Ok(());
+    }
+    let (_mock, home) = support::start_with(|data| {
+        data.config.overlay.enabled = true;
+    })?;
+    let log = home.path().join("dconf.log");
+    home.stub("dconf", &format!("echo \"$@\" >> '{}'", log.display()))?;
+
+    home.wallp().arg("new").assert().success();
+
+    let wallpaper = &home.app_data()?.history[0];
+    let original = home.wallpapers_dir().join(&wallpaper.filename);
+    let stem = original.file_stem().unwrap_or_default().to_string_lossy();
+    let copy = home
+        .path()
+        .join(format!(".local/share/wallp/overlay/{stem}-overlay.jpg"));
+    assert!(copy.exists());
+    assert_ne!(fs::read(&copy)?, fs::read(&original)?);
+    assert!(fs::read_to_string(&log)?.contains(&*copy.to_string_lossy()));
+    Ok(())
+}
+
+#[test]
+fn test_desktop_effects_are_cached_across_history() -> anyhow::Result<()> {
+    let (_mock, home) = support::start_with(|data| {
+        data.config.effects.desktop = vec![Effect::Blur(4.0), Effect::Brightness(0.7)];
+    })?;
+    let log = home.path().join("dconf.log");
+    home.stub("dconf", &format!("echo \"$@\" >> '{}'", log.display()))?;
+
+    home.wallp().arg("new").assert().success();
+    home.wallp().arg("new").assert().success();
+    let effects_dir = home.path().join(".local/share/wallp/effects");
+    let cached = |dir: &std::path::Path| -> anyhow::Result<Vec<(String, std::time::SystemTime)>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)?.flatten() {
+            let modified = entry.metadata()?.modified()?;
+            files.push((entry.file_name().to_string_lossy().into_owned(), modified));
+        }
+        files.sort();
+        Ok(files)
+    };
+    let before = cached(&effects_dir)?;
+    assert_eq!(before.len(), 2);
+
+    home.wallp().arg("prev").assert().success();
+    home.wallp().arg("next").assert().success();
+
+    // Going back and forth reuses the processed copies.
+    assert_eq!(cached(&effects_dir)?, before);
+    let log = fs::read_to_string(&log)?;
+    assert!(!log.is_empty() && log.lines().all(|line| line.contains("/wallp/effects/")));
+    // The downloads themselves are untouched.
+    assert_eq!(home.downloaded_files()?.len(), 2);
+    Ok(())
+}
@@ -0,0 +1,347 @@
+#![cfg(target_os = "linux")]
+//! Sources other than Unsplash: Wallhaven, APOD, Bing, feeds, JSON endpoints,
+//! plugins and generated patterns.
+
+mod support;
+
+use predicates::str::contains;
+use std::fs;
+use support::{MockUnsplash, TestHome};
+use wallp::config::{FeedSource, JsonSource, PluginSource, WallhavenPurity, WallhavenSearch};
+
+const SAMPLE_PLUGIN: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/plugins/sample-plugin.sh"
+);
+
+fn plugin_home(
+    mock: &MockUnsplash,
+    timeout_secs: u64,
+    options: serde_json::Value,
+) -> anyhow::Result<TestHome> {
+    TestHome::new(mock, |data| {
+        data.config.unsplash_access_key.clear();
+        data.config.collections = vec!["plugin:offsite".to_string()];
+        data.config.plugins.push(PluginSource {
+            name: "offsite".to_string(),
+            command: SAMPLE_PLUGIN.into(),
+            timeout_secs,
+            options,
+            ..Default::default()
+        });
+    })
+}
+
+#[test]
+fn test_wallhaven_search_is_used_as_a_source() -> anyhow::Result<()> {
+    let (mock, home) = support::start_with(|data| {
+        data.config.unsplash_access_key.clear();
+        data.config.collections = vec!["wallhaven:anime".to_string()];
+        data.config.wallhaven.api_key = Some("wallhaven_key".to_string());
+        data.config.wallhaven.searches.push(WallhavenSearch {
+            name: "anime".to_string(),
+            tags: vec!["anime".to_string(), "night".to_string()],
+            purity: vec![WallhavenPurity::Sfw, WallhavenPurity::Sketchy],
+            min_resolution: Some("2560x1440".to_string()),
+            ..Default::default()
+        });
+    })?;
+
+    home.wallp().arg("new").assert().success();
+
+    let app_data = home.app_data()?;
+    let wallpaper = &app_data.history[0];
+    assert_eq!(wallpaper.id, "wallhaven-wh1");
+    assert_eq!(wallpaper.source.as_deref(), Some("wallhaven"));
+    assert_eq!(wallpaper.title.as_deref(), Some("anime, night sky"));
+    assert_eq!(wallpaper.author.as_deref(), Some("mock_uploader"));
+    assert_eq!(
+        wallpaper.author_url.as_deref(),
+        Some("https://wallhaven.cc/user/mock_uploader")
+    );
+    assert_eq!(
+        home.downloaded_files()?,
+        vec!["wallpaper_wallhaven-wh1.jpg"]
+    );
+
+    let requests = mock.requests();
+    let search = requests
+        .iter()
+        .find(|r| r.starts_with("GET /api/v1/search?"))
+        .expect("search request");
+    assert!(search.contains("q=anime+night"));
+    assert!(search.contains("purity=110"));
+    assert!(search.contains("atleast=2560x1440"));
+    assert!(requests.iter().any(|r| r.starts_with("GET /api/v1/w/wh1 ")));
+    assert!(!requests.iter().any(|r| r.starts_with("GET /photos/")));
+    assert_eq!(mock.wallhaven_api_keys(), vec!["wallhaven_key"; 2]);
+
+    home.wallp()
+        .arg("info")
+        .assert()
+        .success()
+        .stdout(contains("Source: wallhaven"));
+    home.wallp()
+        .arg("settings")
+        .assert()
+        .success()
+        .stdout(contains(
+            "Wallhaven: anime night, at least 2560x1440 (wallhaven:anime)",
+        ));
+    Ok(())
+}
+
+#[test]
+fn test_apod_skips_videos_and_is_fetched_once_a_day() -> anyhow::Result<()> {
+    let (mock, home) = support::start_with(|data| {
+        data.config.unsplash_access_key.clear();
+        data.config.collections = vec!["daily:apod".to_string()];
+    })?;
+
+    home.wallp().arg("new").assert().success();
+
+    let app_data = home.app_data()?;
+    let wallpaper = &app_data.history[0];
+    assert_eq!(wallpaper.id, "apod-2026-10-17");
+    assert_eq!(wallpaper.title.as_deref(), Some("The Orion Nebula"));
+    assert_eq!(wallpaper.author.as_deref(), Some("Mock Astronomer"));
+    assert_eq!(wallpaper.copyright.as_deref(), Some("© Mock Astronomer"));
+    assert!(
+        wallpaper
+            .explanation
+            .as_deref()
+            .is_some_and(|e| e.contains("stellar nursery"))
+    );
+    assert_eq!(
+        wallpaper.url.as_deref(),
+        Some("https://apod.nasa.gov/apod/ap261017.html")
+    );
+    assert!(app_data.state.daily_fetched.contains_key("apod"));
+    let requests = mock.requests();
+    assert!(requests.iter().any(|r| r.starts_with("GET /apod?")
+        && r.contains("api_key=DEMO_KEY")
+        && r.contains("start_date=")));
+    assert!(
+        requests
+            .iter()
+            .any(|r| r.starts_with("GET /images/apod-orion.jpg"))
+    );
+
+    home.wallp()
+        .arg("new")
+        .assert()
+        .failure()
+        .stderr(contains("already been fetched"));
+    assert_eq!(home.app_data()?.history.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_bing_image_of_the_day() -> anyhow::Result<()> {
+    let (mock, home) = support::start_with(|data| {
+        data.config.unsplash_access_key.clear();
+        data.config.collections = vec!["daily:bing".to_string()];
+        data.config.bing.market = "de-DE".to_string();
+    })?;
+
+    home.wallp().arg("new").assert().success();
+
+    let wallpaper = &home.app_data()?.history[0];
+    assert_eq!(wallpaper.id, "bing-20261018");
+    assert_eq!(wallpaper.source.as_deref(), Some("bing"));
+    assert_eq!(
+        wallpaper.author.as_deref(),
+        Some("Mock Photographer/Getty Images")
+    );
+    let requests = mock.requests();
+    assert!(
+        requests
+            .iter()
+            .any(|r| r.starts_with("GET /HPImageArchive.aspx?") && r.contains("mkt=de-DE"))
+    );
+    assert!(
+        requests
+            .iter()
+            .any(|r| r.starts_with("GET /images/bing-lake_UHD.jpg"))
+    );
+
+    home.wallp()
+        .arg("info")
+        .assert()
+        .success()
+        .stdout(contains("Copyright: Lake Bled, Slovenia"));
+    Ok(())
+}
+
+#[test]
+fn test_feed_items_are_used_without_repeats() -> anyhow::Result<()> {
+    let mock = MockUnsplash::start()?;
+    let home = TestHome::new(&mock, |data| {
+        data.config.unsplash_access_key.clear();
+        data.config.collections = vec!["feed:blog".to_string()];
+        data.config.feeds.push(FeedSource {
+            name: "blog".to_string(),
+            url: format!("{}/feeds/photos.rss", mock.url),
+        });
+    })?;
+
+    home.wallp().arg("new").assert().success();
+    home.wallp().arg("new").assert().success();
+
+    let app_data = home.app_data()?;
+    let mut titles: Vec<&str> = app_data
+        .history
+        .iter()
+        .filter_map(|w| w.title.as_deref())
+        .collect();
+    titles.sort_unstable();
+    assert_eq!(titles, vec!["Fog", "Harbour at dawn"]);
+    assert!(
+        app_data
+            .history
+            .iter()
+            .all(|w| w.id.starts_with("feed-blog-") && w.source.as_deref() == Some("feed:blog"))
+    );
+    let harbour = app_data
+        .history
+        .iter()
+        .find(|w| w.title.as_deref() == Some("Harbour at dawn"))
+        .expect("harbour was applied");
+    assert_eq!(harbour.author.as_deref(), Some("Feed Photographer"));
+    // The relative `<img>` in the second item resolves against the feed URL.
+    assert!(
+        mock.requests()
+            .iter()
+            .any(|r| r.starts_with("GET /images/feed-fog.jpg"))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_json_source_selectors() -> anyhow::Result<()> {
+    let mock = MockUnsplash::start()?;
+    let home = TestHome::new(&mock, |data| {
+        data.config.unsplash_access_key.clear();
+        data.config.collections = vec!["json:photos".to_string()];
+        data.config.json_sources.push(JsonSource {
+            name: "photos".to_string(),
+            url: format!("{}/feeds/photos.json", mock.url),
+            items: Some("$.photos[*]".to_string()),
+            image: "src".to_string(),
+            id: Some("id".to_string()),
+            title: Some("caption".to_string()),
+            author: Some("credit.name".to_string()),
+            ..Default::default()
+        });
+    })?;
+
+    home.wallp().arg("new").assert().success();
+
+    let wallpaper = &home.app_data()?.history[0];
+    assert_eq!(wallpaper.title.as_deref(), Some("Dunes"));
+    assert_eq!(wallpaper.author.as_deref(), Some("Json Photographer"));
+    assert_eq!(wallpaper.source.as_deref(), Some("json:photos"));
+    assert!(
+        mock.requests()
+            .iter()
+            .any(|r| r.starts_with("GET /images/json-dunes.jpg"))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_plugin_photo_is_downloaded() -> anyhow::Result<()> {
+    let mock = MockUnsplash::start()?;
+    let image_url = format!("{}/images/plugin-offsite.jpg", mock.url);
+    let home = plugin_home(&mock, 30, serde_json::json!({"image_url": image_url}))?;
+    home.stub(
+        "xrandr",
+        "echo 'Screen 0: minimum 8 x 8, current 2560 x 1440, maximum 32767 x 32767'",
+    )?;
+
+    home.wallp().arg("new").assert().success();
+
+    let wallpaper = &home.app_data()?.history[0];
+    assert_eq!(wallpaper.id, "plugin-offsite-album_1");
+    // The plugin saw the screen size in its request.
+    assert_eq!(wallpaper.title.as_deref(), Some("Offsite 2560"));
+    assert_eq!(wallpaper.author.as_deref(), Some("Plugin Photographer"));
+    assert_eq!(wallpaper.source.as_deref(), Some("plugin:offsite"));
+    assert!(
+        mock.requests()
+            .iter()
+            .any(|r| r.starts_with("GET /images/plugin-offsite.jpg"))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_plugin_local_file_is_imported() -> anyhow::Result<()> {
+    let mock = MockUnsplash::start()?;
+    let dir = tempfile::TempDir::new()?;
+    let source = dir.path().join("offsite.jpg");
+    let home = plugin_home(&mock, 30, serde_json::json!({"path": source}))?;
+    fs::copy(home.write_jpeg("offsite.jpg")?, &source)?;
+
+    home.wallp().arg("new").assert().success();
+
+    assert_eq!(
+        home.downloaded_files()?,
+        vec!["wallpaper_plugin-offsite-album_1.jpg"]
+    );
+    // Without xrandr output the screen size is unknown.
+    assert_eq!(
+        home.app_data()?.history[0].title.as_deref(),
+        Some("Offsite unknown")
+    );
+    assert!(source.exists(), "the plugin's file is copied, not moved");
+    Ok(())
+}
+
+#[test]
+fn test_plugin_failures_are_reported() -> anyhow::Result<()> {
+    let mock = MockUnsplash::start()?;
+    for (timeout_secs, mode, message) in [
+        (1, "sleep", "timed out after 1s"),
+        (30, "fail", "album not found"),
+        (30, "error", "reported an error: quota exceeded"),
+    ] {
+        let home = plugin_home(&mock, timeout_secs, serde_json::json!({"mode": mode}))?;
+        home.wallp()
+            .arg("new")
+            .assert()
+            .failure()
+            .stderr(contains(message));
+        assert!(home.app_data()?.history.is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_generated_wallpaper_is_re_rendered_from_history() -> anyhow::Result<()> {
+    let (mock, home) = support::start_with(|data| {
+        data.config.unsplash_access_key.clear();
+        data.config.collections = vec!["generated:tiles".to_string()];
+    })?;
+    home.stub(
+        "xrandr",
+        "echo 'Screen 0: minimum 8 x 8, current 640 x 360'",
+    )?;
+
+    home.wallp().arg("new").assert().success();
+    home.wallp().arg("new").assert().success();
+
+    let first = home.app_data()?.history[0].clone();
+    assert!(first.id.starts_with("generated-tiles-") && first.id.ends_with("-640x360"));
+    assert_eq!(first.source.as_deref(), Some("generated"));
+    let path = home.wallpapers_dir().join(&first.filename);
+    assert_eq!(image::image_dimensions(&path)?, (640, 360));
+    let original = fs::read(&path)?;
+
+    fs::remove_file(&path)?;
+    home.wallp().arg("prev").assert().success();
+
+    assert_eq!(fs::read(&path)?, original);
+    assert!(mock.requests().is_empty(), "generated sources stay offline");
+    Ok(())
+}
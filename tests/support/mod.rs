@@ -0,0 +1,521 @@
+//! Test support: an in-process mock of the Unsplash API and an isolated home
+//! directory for running the `wallp` binary against it. Each test file uses
+//! only some of it.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tempfile::TempDir;
+use wallp::config::{
+    API_URL_ENV, APOD_API_URL_ENV, AppData, BING_API_URL_ENV, WALLHAVEN_API_URL_ENV,
+};
+
+const RANDOM_PHOTO_FIXTURE: &str = include_str!("../fixtures/random_photo.json");
+const COLLECTION_FIXTURE: &str = include_str!("../fixtures/collection.json");
+const WALLHAVEN_RESULT_FIXTURE: &str = include_str!("../fixtures/wallhaven_search_result.json");
+const WALLHAVEN_WALLPAPER_FIXTURE: &str = include_str!("../fixtures/wallhaven_wallpaper.json");
+const BING_FIXTURE: &str = include_str!("../fixtures/bing_archive.json");
+const APOD_FIXTURE: &str = include_str!("../fixtures/apod.json");
+const FEED_FIXTURE: &str = include_str!("../fixtures/feed.rss");
+const JSON_SOURCE_FIXTURE: &str = include_str!("../fixtures/photos.json");
+
+#[derive(Default)]
+struct MockState {
+    requests: Vec<String>,
+    next_photo: usize,
+    random_status: Option<u16>,
+    tiny_images: bool,
+    corrupt_images: bool,
+    interrupt_next_image: bool,
+    ranges: Vec<String>,
+    missing_collections: Vec<String>,
+    wallhaven_api_keys: Vec<String>,
+    dark_images: Option<Vec<String>>,
+    image_copies: HashMap<String, String>,
+}
+
+/// A minimal HTTP server that answers the Unsplash endpoints wallp uses (and
+/// Wallhaven's under `/api/v1`, Bing's, APOD's, and an RSS feed and JSON
+/// endpoint under `/feeds`) with fixture JSON and generated JPEGs. Runs on a
+/// background thread for the lifetime of the test process.
+pub struct MockUnsplash {
+    pub url: String,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockUnsplash {
+    pub fn start() -> anyhow::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let base = url.clone();
+        let server_state = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = handle(stream, &base, &server_state) {
+                    eprintln!("mock unsplash: {e}");
+                }
+            }
+        });
+
+        Ok(Self { url, state })
+    }
+
+    /// Make `/photos/random` respond with `status` and an error body.
+    pub fn fail_random(&self, status: u16) {
+        self.lock().random_status = Some(status);
+    }
+
+    /// Serve truncated images, as a broken CDN response would.
+    pub fn serve_tiny_images(&self) {
+        self.lock().tiny_images = true;
+    }
+
+    /// Serve bytes that aren't an image.
+    pub fn serve_corrupt_images(&self) {
+        self.lock().corrupt_images = true;
+    }
+
+    /// Drop the connection halfway through the next image, which should then
+    /// be resumed with a Range request.
+    pub fn interrupt_next_image(&self) {
+        self.lock().interrupt_next_image = true;
+    }
+
+    /// Serve the named images (e.g. `mock2`) nearly black and all others
+    /// nearly white.
+    pub fn shade_images(&self, dark: &[&str]) {
+        self.lock().dark_images = Some(dark.iter().map(ToString::to_string).collect());
+    }
+
+    /// Serve image `copy` (e.g. `mock2`) as a half-size copy of `original`.
+    pub fn copy_image(&self, copy: &str, original: &str) {
+        self.lock()
+            .image_copies
+            .insert(copy.to_string(), original.to_string());
+    }
+
+    /// `Range` headers received so far.
+    pub fn ranges(&self) -> Vec<String> {
+        self.lock().ranges.clone()
+    }
+
+    /// Make `/collections/:id` return 404 for `id`.
+    pub fn hide_collection(&self, id: &str) {
+        self.lock().missing_collections.push(id.to_string());
+    }
+
+    /// `X-API-Key` headers sent to the Wallhaven endpoints so far.
+    pub fn wallhaven_api_keys(&self) -> Vec<String> {
+        self.lock().wallhaven_api_keys.clone()
+    }
+
+    /// Request lines received so far, e.g. `GET /photos/random?count=1`.
+    pub fn requests(&self) -> Vec<String> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().expect("mock state poisoned")
+    }
+}
+
+/// Read the request line and headers (with lowercase names).
+fn read_request(stream: &TcpStream) -> anyhow::Result<(String, HashMap<String, String>)> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut headers = HashMap::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    Ok((request_line, headers))
+}
+
+/// The path and query of a request, also for the absolute form that requests
+/// sent through a proxy use.
+fn request_target(request_line: &str) -> String {
+    let target = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string();
+    target
+        .strip_prefix("http://")
+        .and_then(|rest| rest.find('/').map(|i| rest[i..].to_string()))
+        .unwrap_or(target)
+}
+
+fn handle(stream: TcpStream, base: &str, state: &Mutex<MockState>) -> anyhow::Result<()> {
+    let (request_line, headers) = read_request(&stream)?;
+    let range = headers.get("range").cloned();
+    let request_line = request_line.trim().to_string();
+    let target = request_target(&request_line);
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let params: HashMap<&str, &str> = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .collect();
+
+    let mut state = state.lock().expect("mock state poisoned");
+    state.requests.push(request_line);
+
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if let (["api", "v1", ..], Some(key)) = (segments.as_slice(), headers.get("x-api-key")) {
+        state.wallhaven_api_keys.push(key.clone());
+    }
+    let (status, content_type, body) = match segments.as_slice() {
+        ["photos", "random"] => {
+            if let Some(status) = state.random_status {
+                (
+                    status,
+                    "application/json",
+                    br#"{"errors":["mock failure"]}"#.to_vec(),
+                )
+            } else {
+                let count = params
+                    .get("count")
+                    .and_then(|c| c.parse::<usize>().ok())
+                    .unwrap_or(1);
+                let photos: Vec<String> = (0..count)
+                    .map(|_| {
+                        state.next_photo += 1;
+                        fill(
+                            RANDOM_PHOTO_FIXTURE,
+                            &format!("mock{}", state.next_photo),
+                            base,
+                        )
+                    })
+                    .collect();
+                (
+                    200,
+                    "application/json",
+                    format!("[{}]", photos.join(",")).into_bytes(),
+                )
+            }
+        }
+        ["photos", id, "download"] => (
+            200,
+            "application/json",
+            format!(r#"{{"url":"{base}/images/{id}.jpg"}}"#).into_bytes(),
+        ),
+        ["collections", id] if state.missing_collections.iter().any(|m| m == id) => (
+            404,
+            "application/json",
+            br#"{"errors":["Couldn't find Collection"]}"#.to_vec(),
+        ),
+        ["collections", id] => (
+            200,
+            "application/json",
+            fill(COLLECTION_FIXTURE, id, base).into_bytes(),
+        ),
+        ["images", _] if state.tiny_images => (200, "image/jpeg", vec![0xFF, 0xD8, 0xFF]),
+        ["images", _] if state.corrupt_images => (200, "image/jpeg", vec![0xAB; 4096]),
+        ["images", name] => {
+            let stem = name.split(['.', '?']).next().unwrap_or(name);
+            let mut image = generate_jpeg(name)?;
+            if let Some(dark) = &state.dark_images {
+                image = shaded_jpeg(name, dark.iter().any(|d| d == stem))?;
+            }
+            if let Some(original) = state.image_copies.get(stem) {
+                image = half_size_jpeg(&format!("{original}.jpg"))?;
+            }
+            if std::mem::take(&mut state.interrupt_next_image) {
+                drop(state);
+                return send_truncated(stream, &image);
+            }
+            if let Some(range) = range {
+                state.ranges.push(range.clone());
+                drop(state);
+                return send_range(stream, &image, &range);
+            }
+            (200, "image/jpeg", image)
+        }
+        _ => other_source(&segments, base),
+    };
+    drop(state);
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status} Mock\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// The endpoints of sources other than Unsplash, which don't depend on state.
+fn other_source(segments: &[&str], base: &str) -> (u16, &'static str, Vec<u8>) {
+    match segments {
+        ["api", "v1", rest @ ..] => wallhaven(rest, base),
+        ["HPImageArchive.aspx"] => (200, "application/json", BING_FIXTURE.as_bytes().to_vec()),
+        ["apod"] => (
+            200,
+            "application/json",
+            fill(APOD_FIXTURE, "", base).into_bytes(),
+        ),
+        ["feeds", "photos.rss"] => (
+            200,
+            "application/rss+xml",
+            fill(FEED_FIXTURE, "", base).into_bytes(),
+        ),
+        ["feeds", "photos.json"] => (
+            200,
+            "application/json",
+            JSON_SOURCE_FIXTURE.as_bytes().to_vec(),
+        ),
+        _ => (404, "text/plain", b"not found".to_vec()),
+    }
+}
+
+/// Wallhaven's `/search` (two results, no uploader or tags) and `/w/:id`.
+fn wallhaven(segments: &[&str], base: &str) -> (u16, &'static str, Vec<u8>) {
+    match segments {
+        ["search"] => {
+            let results: Vec<String> = ["wh1", "wh2"]
+                .iter()
+                .map(|id| fill(WALLHAVEN_RESULT_FIXTURE, id, base))
+                .collect();
+            (
+                200,
+                "application/json",
+                format!(r#"{{"data":[{}],"meta":{{}}}}"#, results.join(",")).into_bytes(),
+            )
+        }
+        ["w", id] => (
+            200,
+            "application/json",
+            fill(WALLHAVEN_WALLPAPER_FIXTURE, id, base).into_bytes(),
+        ),
+        _ => (404, "text/plain", b"not found".to_vec()),
+    }
+}
+
+/// Advertise the whole image but close the connection after half of it.
+fn send_truncated(mut stream: TcpStream, image: &[u8]) -> anyhow::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        image.len()
+    )?;
+    stream.write_all(&image[..image.len() / 2])?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Answer a `Range: bytes=N-` request with the rest of the image.
+fn send_range(mut stream: TcpStream, image: &[u8], range: &str) -> anyhow::Result<()> {
+    let start: usize = range
+        .trim_start_matches("bytes=")
+        .trim_end_matches('-')
+        .parse()?;
+    if start >= image.len() {
+        write!(
+            stream,
+            "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            image.len()
+        )?;
+        return Ok(());
+    }
+    let rest = &image[start..];
+    write!(
+        stream,
+        "HTTP/1.1 206 Partial Content\r\nContent-Type: image/jpeg\r\nContent-Range: bytes {start}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        image.len() - 1,
+        image.len(),
+        rest.len()
+    )?;
+    stream.write_all(rest)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn fill(template: &str, id: &str, base: &str) -> String {
+    template.replace("{id}", id).replace("{base}", base)
+}
+
+/// A small JPEG of noisy blocks, seeded by `name` so each photo differs (also
+/// to perceptual hashing, which only sees the blocks).
+fn generate_jpeg(name: &str) -> anyhow::Result<Vec<u8>> {
+    let seed = name
+        .bytes()
+        .fold(7u32, |acc, b| acc.wrapping_mul(31) ^ u32::from(b));
+    let image = image::RgbImage::from_fn(96, 64, |x, y| {
+        let block = ((x / 12 + y / 8 * 8 + 1).wrapping_mul(0x9E37_79B9) ^ seed)
+            .wrapping_mul(0x85EB_CA6B)
+            >> 29;
+        let v = (x * 73 + y * 151).wrapping_mul(seed | 1) ^ (x * y);
+        let [r, g, b, _] = v
+            .to_le_bytes()
+            .map(|n| block.to_le_bytes()[0] * 28 + n % 32);
+        image::Rgb([r, g, b])
+    });
+    let mut bytes = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 90).encode_image(&image)?;
+    Ok(bytes)
+}
+
+/// Like [`generate_jpeg`], squeezed into the darkest or brightest quarter.
+fn shaded_jpeg(name: &str, dark: bool) -> anyhow::Result<Vec<u8>> {
+    let mut image = image::load_from_memory(&generate_jpeg(name)?)?.into_rgb8();
+    for pixel in image.pixels_mut() {
+        for c in &mut pixel.0 {
+            *c = if dark { *c / 4 } else { 192 + *c / 4 };
+        }
+    }
+    let mut bytes = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 90).encode_image(&image)?;
+    Ok(bytes)
+}
+
+/// [`generate_jpeg`] scaled down to half its size.
+pub fn half_size_jpeg(name: &str) -> anyhow::Result<Vec<u8>> {
+    let image = image::load_from_memory(&generate_jpeg(name)?)?;
+    let image = image
+        .thumbnail(image.width() / 2, image.height() / 2)
+        .into_rgb8();
+    let mut bytes = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 90).encode_image(&image)?;
+    Ok(bytes)
+}
+
+/// Start a mock server and a home with the default test config.
+pub fn start() -> anyhow::Result<(MockUnsplash, TestHome)> {
+    start_with(|_| {})
+}
+
+/// Start a mock server and a home whose config is changed by `configure`.
+pub fn start_with(
+    configure: impl FnOnce(&mut AppData),
+) -> anyhow::Result<(MockUnsplash, TestHome)> {
+    let mock = MockUnsplash::start()?;
+    let home = TestHome::new(&mock, configure)?;
+    Ok((mock, home))
+}
+
+/// An isolated `$HOME` in which `wallp` is "installed", configured against a
+/// mock server, and able to set wallpapers through a stub `dconf`.
+pub struct TestHome {
+    pub dir: TempDir,
+    api_url: String,
+}
+
+impl TestHome {
+    pub fn new(mock: &MockUnsplash, configure: impl FnOnce(&mut AppData)) -> anyhow::Result<Self> {
+        let dir = TempDir::new()?;
+        let home = Self {
+            dir,
+            api_url: mock.url.clone(),
+        };
+
+        // `is_initialized` looks for the installed binary.
+        let bin_dir = home.path().join(".local/bin");
+        fs::create_dir_all(&bin_dir)?;
+        fs::write(bin_dir.join("wallp"), "")?;
+
+        // The `wallpaper` crate sets MATE backgrounds through `dconf`.
+        home.stub("dconf", "exit 0")?;
+
+        let mut app_data = AppData::default();
+        app_data.config.unsplash_access_key = "mock_access_key".to_string();
+        app_data.config.lockscreen_enabled = false;
+        app_data.config.prefetch_count = 0;
+        configure(&mut app_data);
+        home.write_app_data(&app_data)?;
+
+        Ok(home)
+    }
+
+    /// Put a shell script called `name` on the `PATH` of [`Self::wallp`].
+    pub fn stub(&self, name: &str, script: &str) -> anyhow::Result<()> {
+        let stub_dir = self.path().join("stubs");
+        fs::create_dir_all(&stub_dir)?;
+        let stub = stub_dir.join(name);
+        fs::write(&stub, format!("#!/bin/sh\n{script}\n"))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&stub, fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(())
+    }
+
+    /// Write a generated JPEG called `name` into this home.
+    pub fn write_jpeg(&self, name: &str) -> anyhow::Result<PathBuf> {
+        let path = self.path().join(name);
+        fs::write(&path, generate_jpeg(name)?)?;
+        Ok(path)
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.path().join(".config/wallp/wallp.json")
+    }
+
+    pub fn wallpapers_dir(&self) -> PathBuf {
+        self.path().join(".local/share/wallp/wallpapers")
+    }
+
+    /// The files in [`Self::wallpapers_dir`], sorted.
+    pub fn downloaded_files(&self) -> anyhow::Result<Vec<String>> {
+        let mut files: Vec<String> = fs::read_dir(self.wallpapers_dir())?
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    pub fn app_data(&self) -> anyhow::Result<AppData> {
+        Ok(serde_json::from_str(&fs::read_to_string(
+            self.config_path(),
+        )?)?)
+    }
+
+    pub fn write_app_data(&self, app_data: &AppData) -> anyhow::Result<()> {
+        let path = self.config_path();
+        fs::create_dir_all(path.parent().expect("config path has a parent"))?;
+        fs::write(path, serde_json::to_string_pretty(app_data)?)?;
+        Ok(())
+    }
+
+    /// `wallp` with its environment confined to this home and the mock API.
+    pub fn wallp(&self) -> assert_cmd::Command {
+        let path = format!(
+            "{}:{}",
+            self.path().join("stubs").display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        let mut cmd = assert_cmd::Command::new(env!("CARGO_BIN_EXE_wallp"));
+        cmd.env("HOME", self.path())
+            .env("XDG_CONFIG_HOME", self.path().join(".config"))
+            .env("XDG_DATA_HOME", self.path().join(".local/share"))
+            .env_remove("XDG_BIN_HOME")
+            .env("XDG_CURRENT_DESKTOP", "MATE")
+            .env("PATH", path)
+            .env(API_URL_ENV, &self.api_url)
+            .env(WALLHAVEN_API_URL_ENV, format!("{}/api/v1", self.api_url))
+            .env(BING_API_URL_ENV, &self.api_url)
+            .env(APOD_API_URL_ENV, format!("{}/apod", self.api_url));
+        cmd
+    }
+}